@@ -0,0 +1,20 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+//The conformance tests in src/tests/conformance.rs run when their ROMs are there and show as
+//ignored when they aren't, so `conformance_roms` is set when the ROM directory has any in it
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(conformance_roms)");
+    println!("cargo:rerun-if-env-changed=CHIP8_TEST_ROMS");
+    println!("cargo:rerun-if-changed=roms/conformance");
+
+    let directory = env::var("CHIP8_TEST_ROMS").unwrap_or_else(|_| String::from("roms/conformance"));
+    let has_roms = fs::read_dir(PathBuf::from(directory))
+        .map(|entries| entries.filter_map(Result::ok).any(|entry| entry.path().extension().is_some_and(|e| e == "ch8")))
+        .unwrap_or(false);
+
+    if has_roms {
+        println!("cargo:rustc-cfg=conformance_roms");
+    }
+}
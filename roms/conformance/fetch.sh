@@ -0,0 +1,13 @@
+#!/bin/sh
+# Downloads the ROMs src/tests/conformance.rs runs into this directory.
+#
+# The numbered ROMs come from Timendus' chip8-test-suite. BC_test.ch8 isn't part of it and has to
+# be copied in by hand. Once a ROM is here `cargo test` stops ignoring the tests.
+set -e
+
+cd "$(dirname "$0")"
+SUITE=https://raw.githubusercontent.com/Timendus/chip8-test-suite/main/bin
+
+for rom in 1-chip8-logo.ch8 2-ibm-logo.ch8 3-corax+.ch8 4-flags.ch8 5-quirks.ch8 6-keypad.ch8; do
+    curl -fsSL -o "$rom" "$SUITE/$rom"
+done
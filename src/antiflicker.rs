@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::chip8_cpu::System;
+use crate::palette;
+
+pub type Colour = (u8, u8, u8);
+
+/// How the frontend hides the flicker of sprites being erased and drawn again, which is how XOR
+/// drawing moves them. Only what is shown changes, the screen the program sees is left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FlickerFilter {
+    #[default]
+    Off,
+    /// Each pixel is the average of its colour over the last `n` frames.
+    Blend(usize),
+    /// Pixels light at once but fade out like phosphor, keeping this much of their brightness
+    /// each frame.
+    Decay(f64),
+    /// Frames that only erase are not shown, what they erased stays until the next frame that
+    /// draws something.
+    Hold,
+}
+
+impl fmt::Display for FlickerFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FlickerFilter::Off => write!(f, "off"),
+            FlickerFilter::Blend(frames) => write!(f, "blend:{}", frames),
+            FlickerFilter::Decay(persistence) => write!(f, "decay:{}", persistence),
+            FlickerFilter::Hold => write!(f, "hold"),
+        }
+    }
+}
+
+impl FlickerFilter {
+    /// Reads a filter written by `Display`. `blend` and `decay` can leave off their setting for
+    /// 3 frames and 0.5.
+    pub fn parse(text: &str) -> Option<FlickerFilter> {
+        let (name, setting) = match text.find(':') {
+            Some(colon) => (&text[..colon], Some(&text[colon + 1..])),
+            None => (text, None),
+        };
+
+        match (name, setting) {
+            ("off", None) => Some(FlickerFilter::Off),
+            ("hold", None) => Some(FlickerFilter::Hold),
+            ("blend", None) => Some(FlickerFilter::Blend(3)),
+            ("blend", Some(frames)) => frames.parse().ok().filter(|&frames| frames > 0).map(FlickerFilter::Blend),
+            ("decay", None) => Some(FlickerFilter::Decay(0.5)),
+            ("decay", Some(persistence)) => {
+                persistence.parse().ok().filter(|persistence| (0.0..1.0).contains(persistence)).map(FlickerFilter::Decay)
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Runs the screen through a `FlickerFilter` a frame at a time, remembering what it needs of the
+/// frames before.
+#[derive(Debug, Clone)]
+pub struct AntiFlicker {
+    filter: FlickerFilter,
+    width: usize,
+    height: usize,
+    //The colours of the last frames, oldest first
+    history: VecDeque<Vec<Colour>>,
+    //What was shown last, kept unrounded so that fading is smooth
+    shown: Vec<[f64; 3]>,
+    //The pixels of the last frame, to tell frames that draw from frames that only erase
+    pixels: Vec<u8>,
+}
+
+impl AntiFlicker {
+    pub fn new(filter: FlickerFilter) -> AntiFlicker {
+        AntiFlicker {
+            filter,
+            width: 0,
+            height: 0,
+            history: VecDeque::new(),
+            shown: Vec::new(),
+            pixels: Vec::new(),
+        }
+    }
+
+    pub fn filter(&self) -> FlickerFilter {
+        self.filter
+    }
+
+    /// Forgets the frames seen so far, for when the screen jumps, like loading a save state.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.shown.clear();
+        self.pixels.clear();
+    }
+
+    /// The colours to show for the current screen of `system`, row after row. Call it once a
+    /// frame, as each call counts as a frame.
+    pub fn frame(&mut self, system: &System) -> Vec<Colour> {
+        let display = &system.display;
+        let (width, height) = (display.width(), display.height());
+        if (width, height) != (self.width, self.height) {
+            //Nothing from another resolution lines up
+            self.width = width;
+            self.height = height;
+            self.reset();
+        }
+
+        let mut colours = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                colours.push(palette::pixel_colour(system, x, y));
+            }
+        }
+
+        match self.filter {
+            FlickerFilter::Off => colours,
+            FlickerFilter::Blend(frames) => {
+                self.history.push_back(colours);
+                while self.history.len() > frames {
+                    self.history.pop_front();
+                }
+                self.blend()
+            },
+            FlickerFilter::Decay(persistence) => self.decay(&colours, persistence),
+            FlickerFilter::Hold => {
+                let pixels: Vec<u8> = display.rows().flatten().copied().collect();
+                let drew = self.pixels.is_empty()
+                    || pixels.iter().zip(self.pixels.iter()).any(|(&now, &before)| now & !before != 0);
+                self.pixels = pixels;
+
+                if drew || self.history.is_empty() {
+                    self.history.clear();
+                    self.history.push_back(colours);
+                }
+                self.history[0].clone()
+            }
+        }
+    }
+
+    fn blend(&self) -> Vec<Colour> {
+        let frames = self.history.len() as u32;
+
+        (0..self.width * self.height).map(|index| {
+            let mut sum = [0u32; 3];
+            for frame in self.history.iter() {
+                let (r, g, b) = frame[index];
+                sum[0] += r as u32;
+                sum[1] += g as u32;
+                sum[2] += b as u32;
+            }
+            ((sum[0] / frames) as u8, (sum[1] / frames) as u8, (sum[2] / frames) as u8)
+        }).collect()
+    }
+
+    //Each channel goes straight up to a brighter colour, and only part of the way down to a darker one
+    fn decay(&mut self, colours: &[Colour], persistence: f64) -> Vec<Colour> {
+        if self.shown.len() != colours.len() {
+            self.shown = colours.iter().map(|&(r, g, b)| [r as f64, g as f64, b as f64]).collect();
+        }
+
+        for (shown, &(r, g, b)) in self.shown.iter_mut().zip(colours.iter()) {
+            for (channel, target) in shown.iter_mut().zip([r, g, b].iter()) {
+                let target = *target as f64;
+                *channel = if target >= *channel { target } else { target + (*channel - target) * persistence };
+            }
+        }
+
+        self.shown.iter().map(|channels| {
+            (channels[0].round() as u8, channels[1].round() as u8, channels[2].round() as u8)
+        }).collect()
+    }
+}
@@ -0,0 +1,690 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::chip8_cpu::MEMORY_SIZE;
+use crate::instruction::Instruction;
+
+//Where System::load_program puts the first byte of a program
+pub const PROGRAM_START: u16 = 0x200;
+const MAX_INCLUDE_DEPTH: usize = 16;
+const MAX_SYMBOL_DEPTH: usize = 64;
+
+/// An error found while assembling, pointing at the file, line and column (both 1 based) at fault.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembleError {
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.file {
+            Some(ref file) => write!(f, "{}:{}:{}: {}", file.display(), self.line, self.column, self.message),
+            None => write!(f, "{}:{}: {}", self.line, self.column, self.message),
+        }
+    }
+}
+
+impl Error for AssembleError {}
+
+/// Assembles Cowgod style CHIP-8 and SUPER-CHIP source into a program image. The image starts at
+/// the lowest address anything is placed at, 0x200 unless an `org` before the first line moves
+/// it, so the output of `disassemble` at any origin comes back byte for byte.
+///
+/// Includes are resolved relative to the current directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let mut assembler = Assembler::new();
+    assembler.parse_source(source, None, 0)?;
+    assembler.finish()
+}
+
+/// Assembles the file at `path`, resolving includes relative to the file that includes them.
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, AssembleError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|e| AssembleError {
+        file: Some(path.to_path_buf()),
+        line: 0,
+        column: 0,
+        message: format!("could not read file: {}", e),
+    })?;
+
+    let mut assembler = Assembler::new();
+    assembler.parse_source(&source, Some(path.to_path_buf()), 0)?;
+    assembler.finish()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Number(i64),
+    Str(String),
+    Comma,
+    Colon,
+    Equals,
+    Plus,
+    Minus,
+    LeftBracket,
+    RightBracket,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    file: Option<PathBuf>,
+    line: usize,
+}
+
+impl Location {
+    fn error<S: Into<String>>(&self, column: usize, message: S) -> AssembleError {
+        AssembleError {
+            file: self.file.clone(),
+            line: self.line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Number(i64),
+    Symbol(String),
+}
+
+#[derive(Debug, Clone)]
+struct Expr {
+    //Each term is added, or subtracted when the flag is set
+    terms: Vec<(bool, Term, usize)>,
+    column: usize,
+}
+
+impl Expr {
+    //Whether the expression is just `name`, in any case
+    fn is_name(&self, name: &str) -> bool {
+        match self.terms[..] {
+            [(false, Term::Symbol(ref symbol), _)] => symbol.eq_ignore_ascii_case(name),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum OperandKind {
+    Register(u8),
+    I,
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    Bcd,
+    HighFont,
+    Flags,
+    Value(Expr),
+}
+
+#[derive(Debug, Clone)]
+struct Operand {
+    kind: OperandKind,
+    column: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Instruction { mnemonic: String, column: usize, operands: Vec<Operand> },
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    location: Location,
+    address: usize,
+    statement: Statement,
+}
+
+#[derive(Debug, Clone)]
+enum Symbol {
+    Address(usize),
+    Constant(Expr, Location),
+}
+
+struct Assembler {
+    lines: Vec<Line>,
+    symbols: HashMap<String, Symbol>,
+    address: usize,
+    //Which bytes a line has been placed at, so an `org` back over them is caught
+    placed: Vec<bool>,
+}
+
+impl Assembler {
+    fn new() -> Assembler {
+        Assembler {
+            lines: Vec::new(),
+            symbols: HashMap::new(),
+            address: PROGRAM_START as usize,
+            placed: vec![false; MEMORY_SIZE],
+        }
+    }
+
+    //First pass, lays out every line and records where the labels end up
+    fn parse_source(&mut self, source: &str, file: Option<PathBuf>, depth: usize) -> Result<(), AssembleError> {
+        for (index, text) in source.lines().enumerate() {
+            let location = Location { file: file.clone(), line: index + 1 };
+            let tokens = tokenize(text, &location)?;
+            self.parse_line(tokens, location, depth)?;
+        }
+
+        Ok(())
+    }
+
+    fn parse_line(&mut self, tokens: Vec<Token>, location: Location, depth: usize) -> Result<(), AssembleError> {
+        let mut tokens = &tokens[..];
+
+        //Label definitions, there may be an instruction after them on the same line
+        while tokens.len() >= 2 && tokens[1].kind == TokenKind::Colon {
+            match tokens[0].kind {
+                TokenKind::Word(ref name) => {
+                    self.define(name, Symbol::Address(self.address), &location, tokens[0].column)?;
+                },
+                _ => return Err(location.error(tokens[0].column, "expected a label name before ':'")),
+            }
+            tokens = &tokens[2..];
+        }
+
+        if tokens.is_empty() {
+            return Ok(());
+        }
+
+        let (name, column) = match tokens[0].kind {
+            TokenKind::Word(ref name) => (name.clone(), tokens[0].column),
+            _ => return Err(location.error(tokens[0].column, "expected an instruction or directive")),
+        };
+
+        //Constants, either `NAME equ value` or `NAME = value`
+        if tokens.len() >= 2 {
+            let is_constant = match tokens[1].kind {
+                TokenKind::Equals => true,
+                TokenKind::Word(ref word) => word.eq_ignore_ascii_case("equ"),
+                _ => false,
+            };
+
+            if is_constant {
+                let expr = parse_single_expr(&tokens[2..], &location, tokens[1].column)?;
+                return self.define(&name, Symbol::Constant(expr, location.clone()), &location, column);
+            }
+        }
+
+        let operands = &tokens[1..];
+        match name.to_ascii_lowercase().as_str() {
+            "include" => {
+                if operands.len() != 1 {
+                    return Err(location.error(column, "include expects a single quoted path"));
+                }
+                let path = match operands[0].kind {
+                    TokenKind::Str(ref path) => path.clone(),
+                    _ => return Err(location.error(operands[0].column, "include expects a quoted path")),
+                };
+                self.include(&path, &location, operands[0].column, depth)
+            },
+            "org" => {
+                let expr = parse_single_expr(operands, &location, column)?;
+                let value = self.evaluate(&expr, &location, 0)?;
+
+                if value < 0 || value >= MEMORY_SIZE as i64 {
+                    return Err(location.error(expr.column, format!("org address 0x{:X} is outside of memory", value)));
+                }
+                self.address = value as usize;
+                Ok(())
+            },
+            "db" => {
+                let values = parse_expr_list(operands, &location, column)?;
+                let size = values.len();
+                self.push(location, Statement::Bytes(values), size, column)
+            },
+            "dw" => {
+                let values = parse_expr_list(operands, &location, column)?;
+                let size = values.len() * 2;
+                self.push(location, Statement::Words(values), size, column)
+            },
+            _ => {
+                let operands = parse_operands(operands, &location)?;
+                let statement = Statement::Instruction { mnemonic: name.to_ascii_uppercase(), column, operands };
+                self.push(location, statement, 2, column)
+            }
+        }
+    }
+
+    fn include(&mut self, path: &str, location: &Location, column: usize, depth: usize) -> Result<(), AssembleError> {
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(location.error(column, "includes are nested too deeply"));
+        }
+
+        let path = match location.file.as_ref().and_then(|file| file.parent()) {
+            Some(directory) => directory.join(path),
+            None => PathBuf::from(path),
+        };
+        let source = fs::read_to_string(&path)
+            .map_err(|e| location.error(column, format!("could not include {}: {}", path.display(), e)))?;
+
+        self.parse_source(&source, Some(path), depth + 1)
+    }
+
+    fn define(&mut self, name: &str, symbol: Symbol, location: &Location, column: usize) -> Result<(), AssembleError> {
+        if is_reserved(name) {
+            return Err(location.error(column, format!("'{}' is reserved and cannot be used as a name", name)));
+        }
+        if self.symbols.contains_key(name) {
+            return Err(location.error(column, format!("'{}' is already defined", name)));
+        }
+
+        self.symbols.insert(name.to_string(), symbol);
+        Ok(())
+    }
+
+    fn push(&mut self, location: Location, statement: Statement, size: usize, column: usize) -> Result<(), AssembleError> {
+        if self.address + size > MEMORY_SIZE {
+            return Err(location.error(column, "program does not fit in memory"));
+        }
+        if let Some(offset) = self.placed[self.address..self.address + size].iter().position(|&placed| placed) {
+            return Err(location.error(column, format!("0x{:03X} already has something placed at it", self.address + offset)));
+        }
+        for placed in self.placed[self.address..self.address + size].iter_mut() {
+            *placed = true;
+        }
+
+        self.lines.push(Line { location, address: self.address, statement });
+        self.address += size;
+        Ok(())
+    }
+
+    //Second pass, every label is known so the lines can be encoded
+    fn finish(self) -> Result<Vec<u8>, AssembleError> {
+        let start = self.lines.iter().map(|line| line.address).min().unwrap_or(PROGRAM_START as usize);
+        let end = self.lines.iter().map(|line| line.address + line_size(&line.statement)).max();
+        let mut image = vec![0u8; end.unwrap_or(start) - start];
+
+        for line in self.lines.iter() {
+            let offset = line.address - start;
+
+            match line.statement {
+                Statement::Bytes(ref values) => {
+                    for (index, expr) in values.iter().enumerate() {
+                        image[offset + index] = self.byte(expr, &line.location)?;
+                    }
+                },
+                Statement::Words(ref values) => {
+                    for (index, expr) in values.iter().enumerate() {
+                        let value = self.evaluate(expr, &line.location, 0)?;
+                        if !(-0x8000..=0xFFFF).contains(&value) {
+                            return Err(line.location.error(expr.column, format!("value {} does not fit in a word", value)));
+                        }
+                        image[offset + index * 2] = (value >> 8) as u8;
+                        image[offset + index * 2 + 1] = value as u8;
+                    }
+                },
+                Statement::Instruction { ref mnemonic, column, ref operands } => {
+                    let opcode = self.encode(mnemonic, column, operands, &line.location)?.encode();
+                    image[offset] = (opcode >> 8) as u8;
+                    image[offset + 1] = opcode as u8;
+                }
+            }
+        }
+
+        Ok(image)
+    }
+
+    fn encode(&self, mnemonic: &str, column: usize, operands: &[Operand], location: &Location) -> Result<Instruction, AssembleError> {
+        use self::OperandKind::*;
+
+        let mut kinds: Vec<&OperandKind> = operands.iter().map(|operand| &operand.kind).collect();
+        //HF and R are only the big font and the RPL flags next to a register in LD, anywhere else
+        //they are ordinary names
+        if mnemonic == "LD" {
+            match &kinds[..] {
+                [Value(e), Register(_)] if e.is_name("HF") => { kinds[0] = &HighFont; },
+                [Value(e), Register(_)] if e.is_name("R") => { kinds[0] = &Flags; },
+                [Register(_), Value(e)] if e.is_name("R") => { kinds[1] = &Flags; },
+                _ => {}
+            }
+        }
+        let instruction = match (mnemonic, &kinds[..]) {
+            ("CLS", []) => Instruction::Cls,
+            ("RET", []) => Instruction::Ret,
+            ("SYS", [Value(e)]) => Instruction::Sys(self.address_value(e, location)?),
+            ("JP", [Register(0), Value(e)]) => Instruction::JpV0(self.address_value(e, location)?),
+            ("JP", [Value(e)]) => Instruction::Jp(self.address_value(e, location)?),
+            ("CALL", [Value(e)]) => Instruction::Call(self.address_value(e, location)?),
+            ("SE", [Register(x), Register(y)]) => Instruction::SeReg(*x, *y),
+            ("SE", [Register(x), Value(e)]) => Instruction::SeByte(*x, self.byte(e, location)?),
+            ("SNE", [Register(x), Register(y)]) => Instruction::SneReg(*x, *y),
+            ("SNE", [Register(x), Value(e)]) => Instruction::SneByte(*x, self.byte(e, location)?),
+            ("LD", [Register(x), Register(y)]) => Instruction::LdReg(*x, *y),
+            ("LD", [Register(x), Value(e)]) => Instruction::LdByte(*x, self.byte(e, location)?),
+            ("LD", [I, Value(e)]) => Instruction::LdI(self.address_value(e, location)?),
+            ("LD", [Register(x), DelayTimer]) => Instruction::LdVxDt(*x),
+            ("LD", [Register(x), Key]) => Instruction::LdVxK(*x),
+            ("LD", [DelayTimer, Register(x)]) => Instruction::LdDtVx(*x),
+            ("LD", [SoundTimer, Register(x)]) => Instruction::LdStVx(*x),
+            ("LD", [Font, Register(x)]) => Instruction::LdFVx(*x),
+            ("LD", [Bcd, Register(x)]) => Instruction::LdBVx(*x),
+            ("LD", [IndirectI, Register(x)]) => Instruction::LdIVx(*x),
+            ("LD", [Register(x), IndirectI]) => Instruction::LdVxI(*x),
+            ("ADD", [Register(x), Register(y)]) => Instruction::AddReg(*x, *y),
+            ("ADD", [Register(x), Value(e)]) => Instruction::AddByte(*x, self.byte(e, location)?),
+            ("ADD", [I, Register(x)]) => Instruction::AddIVx(*x),
+            ("OR", [Register(x), Register(y)]) => Instruction::Or(*x, *y),
+            ("AND", [Register(x), Register(y)]) => Instruction::And(*x, *y),
+            ("XOR", [Register(x), Register(y)]) => Instruction::Xor(*x, *y),
+            ("SUB", [Register(x), Register(y)]) => Instruction::Sub(*x, *y),
+            ("SUBN", [Register(x), Register(y)]) => Instruction::Subn(*x, *y),
+            //With a single register the shift reads and writes the same one, whichever quirk is in use
+            ("SHR", [Register(x)]) => Instruction::Shr(*x, *x),
+            ("SHR", [Register(x), Register(y)]) => Instruction::Shr(*x, *y),
+            ("SHL", [Register(x)]) => Instruction::Shl(*x, *x),
+            ("SHL", [Register(x), Register(y)]) => Instruction::Shl(*x, *y),
+            ("RND", [Register(x), Value(e)]) => Instruction::Rnd(*x, self.byte(e, location)?),
+            ("DRW", [Register(x), Register(y), Value(e)]) => Instruction::Drw(*x, *y, self.nibble(e, location)?),
+            ("SKP", [Register(x)]) => Instruction::Skp(*x),
+            ("SKNP", [Register(x)]) => Instruction::Sknp(*x),
+            ("SCD", [Value(e)]) => Instruction::Scd(self.nibble(e, location)?),
+            ("SCR", []) => Instruction::Scr,
+            ("SCL", []) => Instruction::Scl,
+            ("EXIT", []) => Instruction::Exit,
+            ("LOW", []) => Instruction::Low,
+            ("HIGH", []) => Instruction::High,
+            ("LD", [HighFont, Register(x)]) => Instruction::LdHfVx(*x),
+            ("LD", [Flags, Register(x)]) => Instruction::LdRVx(*x),
+            ("LD", [Register(x), Flags]) => Instruction::LdVxR(*x),
+            _ => {
+                let known = ["CLS", "RET", "SYS", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR",
+                    "SUB", "SUBN", "SHR", "SHL", "RND", "DRW", "SKP", "SKNP", "SCD", "SCR", "SCL", "EXIT", "LOW", "HIGH"];
+
+                return if known.contains(&mnemonic) {
+                    let column = operands.first().map(|operand| operand.column).unwrap_or(column);
+                    Err(location.error(column, format!("invalid operands for {}", mnemonic)))
+                } else {
+                    Err(location.error(column, format!("unknown instruction '{}'", mnemonic)))
+                };
+            }
+        };
+
+        Ok(instruction)
+    }
+
+    fn evaluate(&self, expr: &Expr, location: &Location, depth: usize) -> Result<i64, AssembleError> {
+        if depth > MAX_SYMBOL_DEPTH {
+            return Err(location.error(expr.column, "constant refers back to itself"));
+        }
+
+        let mut total: i64 = 0;
+        for &(negative, ref term, column) in expr.terms.iter() {
+            let value = match *term {
+                Term::Number(value) => value,
+                Term::Symbol(ref name) => match self.symbols.get(name) {
+                    Some(Symbol::Address(address)) => *address as i64,
+                    Some(Symbol::Constant(inner, inner_location)) => self.evaluate(inner, inner_location, depth + 1)?,
+                    None => return Err(location.error(column, format!("undefined symbol '{}'", name))),
+                },
+            };
+
+            total = if negative { total.wrapping_sub(value) } else { total.wrapping_add(value) };
+        }
+
+        Ok(total)
+    }
+
+    fn address_value(&self, expr: &Expr, location: &Location) -> Result<u16, AssembleError> {
+        let value = self.evaluate(expr, location, 0)?;
+        if !(0..=0xFFF).contains(&value) {
+            return Err(location.error(expr.column, format!("address {} does not fit in 12 bits", value)));
+        }
+        Ok(value as u16)
+    }
+
+    fn byte(&self, expr: &Expr, location: &Location) -> Result<u8, AssembleError> {
+        let value = self.evaluate(expr, location, 0)?;
+        if !(-0x80..=0xFF).contains(&value) {
+            return Err(location.error(expr.column, format!("value {} does not fit in a byte", value)));
+        }
+        Ok(value as u8)
+    }
+
+    fn nibble(&self, expr: &Expr, location: &Location) -> Result<u8, AssembleError> {
+        let value = self.evaluate(expr, location, 0)?;
+        if !(0..=0xF).contains(&value) {
+            return Err(location.error(expr.column, format!("value {} does not fit in 4 bits", value)));
+        }
+        Ok(value as u8)
+    }
+}
+
+fn line_size(statement: &Statement) -> usize {
+    match *statement {
+        Statement::Instruction { .. } => 2,
+        Statement::Bytes(ref values) => values.len(),
+        Statement::Words(ref values) => values.len() * 2,
+    }
+}
+
+fn is_reserved(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    register_index(&upper).is_some()
+        || ["I", "DT", "ST", "K", "F", "B", "EQU", "ORG", "DB", "DW", "INCLUDE"].contains(&upper.as_str())
+}
+
+fn register_index(name: &str) -> Option<u8> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('V'), Some(digit), None) | (Some('v'), Some(digit), None) => digit.to_digit(16).map(|value| value as u8),
+        _ => None,
+    }
+}
+
+fn tokenize(text: &str, location: &Location) -> Result<Vec<Token>, AssembleError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while position < chars.len() {
+        let c = chars[position];
+        let column = position + 1;
+
+        if c == ';' {
+            break;
+        }
+        if c.is_whitespace() {
+            position += 1;
+            continue;
+        }
+
+        let single = match c {
+            ',' => Some(TokenKind::Comma),
+            ':' => Some(TokenKind::Colon),
+            '=' => Some(TokenKind::Equals),
+            '+' => Some(TokenKind::Plus),
+            '-' => Some(TokenKind::Minus),
+            '[' => Some(TokenKind::LeftBracket),
+            ']' => Some(TokenKind::RightBracket),
+            _ => None,
+        };
+        if let Some(kind) = single {
+            tokens.push(Token { kind, column });
+            position += 1;
+            continue;
+        }
+
+        if c == '"' {
+            let start = position + 1;
+            let end = match chars[start..].iter().position(|&c| c == '"') {
+                Some(length) => start + length,
+                None => return Err(location.error(column, "unterminated string")),
+            };
+            tokens.push(Token { kind: TokenKind::Str(chars[start..end].iter().collect()), column });
+            position = end + 1;
+            continue;
+        }
+
+        let start = position;
+        while position < chars.len() && (chars[position].is_ascii_alphanumeric() || "_.$%#".contains(chars[position])) {
+            position += 1;
+        }
+        if start == position {
+            return Err(location.error(column, format!("unexpected character '{}'", c)));
+        }
+
+        let word: String = chars[start..position].iter().collect();
+        let kind = if c.is_ascii_digit() || c == '$' || c == '%' || c == '#' {
+            match parse_number(&word) {
+                Some(value) => TokenKind::Number(value),
+                None => return Err(location.error(column, format!("invalid number '{}'", word))),
+            }
+        } else {
+            TokenKind::Word(word)
+        };
+        tokens.push(Token { kind, column });
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let lower = text.to_ascii_lowercase();
+    let (digits, radix) = if let Some(hex) = lower.strip_prefix("0x") {
+        (hex, 16)
+    } else if let Some(hex) = lower.strip_prefix('$').or_else(|| lower.strip_prefix('#')) {
+        (hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b").or_else(|| lower.strip_prefix('%')) {
+        (binary, 2)
+    } else {
+        (lower.as_str(), 10)
+    };
+
+    if digits.is_empty() {
+        return None;
+    }
+    i64::from_str_radix(digits, radix).ok()
+}
+
+//Splits tokens on commas into operands
+fn parse_operands(tokens: &[Token], location: &Location) -> Result<Vec<Operand>, AssembleError> {
+    let mut operands = Vec::new();
+    if tokens.is_empty() {
+        return Ok(operands);
+    }
+
+    let mut start = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        if token.kind == TokenKind::Comma {
+            operands.push(parse_operand(&tokens[start..index], token.column, location)?);
+            start = index + 1;
+        }
+    }
+    let end_column = tokens.last().map(|token| token.column + 1).unwrap_or(0);
+    operands.push(parse_operand(&tokens[start..], end_column, location)?);
+
+    Ok(operands)
+}
+
+//`column` is where to point at when the operand is missing altogether
+fn parse_operand(tokens: &[Token], column: usize, location: &Location) -> Result<Operand, AssembleError> {
+    let column = match tokens.first() {
+        Some(token) => token.column,
+        None => return Err(location.error(column, "missing operand")),
+    };
+
+    if let [Token { kind: TokenKind::LeftBracket, .. }, Token { kind: TokenKind::Word(ref word), .. }, Token { kind: TokenKind::RightBracket, .. }] = tokens {
+        if word.eq_ignore_ascii_case("I") {
+            return Ok(Operand { kind: OperandKind::IndirectI, column });
+        }
+    }
+
+    if let [Token { kind: TokenKind::Word(ref word), .. }] = tokens {
+        let upper = word.to_ascii_uppercase();
+        let kind = match upper.as_str() {
+            "I" => Some(OperandKind::I),
+            "DT" => Some(OperandKind::DelayTimer),
+            "ST" => Some(OperandKind::SoundTimer),
+            "K" => Some(OperandKind::Key),
+            "F" => Some(OperandKind::Font),
+            "B" => Some(OperandKind::Bcd),
+            _ => register_index(&upper).map(OperandKind::Register),
+        };
+
+        if let Some(kind) = kind {
+            return Ok(Operand { kind, column });
+        }
+    }
+
+    let expr = parse_expr(tokens, location)?;
+    Ok(Operand { kind: OperandKind::Value(expr), column })
+}
+
+fn parse_single_expr(tokens: &[Token], location: &Location, column: usize) -> Result<Expr, AssembleError> {
+    if tokens.is_empty() {
+        return Err(location.error(column, "expected a value"));
+    }
+    if let Some(comma) = tokens.iter().find(|token| token.kind == TokenKind::Comma) {
+        return Err(location.error(comma.column, "expected a single value"));
+    }
+    parse_expr(tokens, location)
+}
+
+fn parse_expr_list(tokens: &[Token], location: &Location, column: usize) -> Result<Vec<Expr>, AssembleError> {
+    if tokens.is_empty() {
+        return Err(location.error(column, "expected at least one value"));
+    }
+
+    parse_operands(tokens, location)?.into_iter()
+        .map(|operand| match operand.kind {
+            OperandKind::Value(expr) => Ok(expr),
+            _ => Err(location.error(operand.column, "expected a value")),
+        })
+        .collect()
+}
+
+//Values are numbers and symbols joined with + and -
+fn parse_expr(tokens: &[Token], location: &Location) -> Result<Expr, AssembleError> {
+    let column = tokens.first().map(|token| token.column).unwrap_or(0);
+
+    let mut terms = Vec::new();
+    let mut negative = false;
+    let mut expect_term = true;
+
+    for token in tokens {
+        match token.kind {
+            TokenKind::Plus if expect_term => {},
+            TokenKind::Minus if expect_term => { negative = !negative; },
+            TokenKind::Plus => { expect_term = true; },
+            TokenKind::Minus => { expect_term = true; negative = true; },
+            TokenKind::Number(value) if expect_term => {
+                terms.push((negative, Term::Number(value), token.column));
+                negative = false;
+                expect_term = false;
+            },
+            TokenKind::Word(ref name) if expect_term && !is_reserved(name) => {
+                terms.push((negative, Term::Symbol(name.clone()), token.column));
+                negative = false;
+                expect_term = false;
+            },
+            _ => return Err(location.error(token.column, "unexpected token in value")),
+        }
+    }
+
+    if expect_term {
+        let last = tokens.last().map(|token| token.column).unwrap_or(column);
+        return Err(location.error(last, "expression ends without a value"));
+    }
+
+    Ok(Expr { terms, column })
+}
@@ -0,0 +1,73 @@
+use crate::chip8_cpu::System;
+
+/// Turns the sound timer and the XO-CHIP audio pattern into samples. A frontend copies the
+/// system's sound state into the voice once a frame with `update` and asks for samples with `fill`
+/// from its audio callback.
+#[derive(Debug, Clone)]
+pub struct Voice {
+    pattern: [u8; 16],
+    //Pattern bits per second
+    rate: f64,
+    //Position in the pattern, in bits
+    position: f64,
+    playing: bool,
+}
+
+impl Default for Voice {
+    fn default() -> Voice {
+        Voice::new()
+    }
+}
+
+impl Voice {
+    pub fn new() -> Voice {
+        Voice {
+            pattern: [0; 16],
+            rate: 4000.0,
+            position: 0.0,
+            playing: false,
+        }
+    }
+
+    pub fn update(&mut self, system: &System) {
+        self.pattern = system.audio_pattern;
+        self.rate = system.audio_sample_rate();
+        self.playing = system.sound_timer > 0;
+    }
+
+    /// Follows a plain square wave at `frequency` instead of a system, for the tone generator
+    /// of a `Vip` that Q switches on and off.
+    pub fn update_tone(&mut self, frequency: f64, playing: bool) {
+        //One byte high and one low is a cycle of the square wave
+        for (index, byte) in self.pattern.iter_mut().enumerate() {
+            *byte = if index % 2 == 0 { 0xFF } else { 0x00 };
+        }
+        self.rate = frequency * 16.0;
+        self.playing = playing;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Fills `samples` at `output_rate` samples a second, `volume` for set bits and `-volume` for
+    /// clear ones, or silence while the sound timer is not running.
+    pub fn fill(&mut self, samples: &mut [f32], output_rate: u32, volume: f32) {
+        if !self.playing {
+            for sample in samples.iter_mut() {
+                *sample = 0.0;
+            }
+            return;
+        }
+
+        let bits = (self.pattern.len() * 8) as f64;
+        let step = self.rate / output_rate as f64;
+        for sample in samples.iter_mut() {
+            let bit = self.position as usize;
+            let set = self.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            *sample = if set { volume } else { -volume };
+
+            self.position = (self.position + step) % bits;
+        }
+    }
+}
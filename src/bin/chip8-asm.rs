@@ -0,0 +1,68 @@
+use chip_8_emulator::assembler::{self, PROGRAM_START};
+use chip_8_emulator::disassembler;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "Usage: chip8-asm <source.asm> [-o <output.ch8>]
+       chip8-asm -d <program.ch8> [-o <output.asm>]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut disassemble = false;
+    let mut input = None;
+    let mut output = None;
+
+    let mut index = 0;
+    while index < args.len() {
+        match args[index].as_str() {
+            "-d" | "--disassemble" => { disassemble = true; },
+            "-o" | "--output" => {
+                index += 1;
+                output = args.get(index).map(PathBuf::from);
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            path => { input = Some(PathBuf::from(path)); }
+        }
+        index += 1;
+    }
+
+    let input = match input {
+        Some(input) => input,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if disassemble {
+        let program = fs::read(&input).unwrap_or_else(|e| fail(&input, e));
+        let source = disassembler::disassemble(&program, PROGRAM_START);
+
+        match output {
+            Some(output) => fs::write(&output, source).unwrap_or_else(|e| fail(&output, e)),
+            None => print!("{}", source),
+        }
+    } else {
+        let program = match assembler::assemble_file(&input) {
+            Ok(program) => program,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            }
+        };
+
+        let output = output.unwrap_or_else(|| input.with_extension("ch8"));
+        fs::write(&output, program).unwrap_or_else(|e| fail(&output, e));
+    }
+}
+
+fn fail<E: std::fmt::Display, T>(path: &Path, error: E) -> T {
+    eprintln!("error: {}: {}", path.display(), error);
+    process::exit(1);
+}
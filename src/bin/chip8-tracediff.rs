@@ -0,0 +1,67 @@
+use chip_8_emulator::trace::TraceFormat;
+use chip_8_emulator::tracediff::diff_traces;
+
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "Usage: chip8-tracediff <left.log> <right.log> [--format <f>] [--left-format <f>] [--right-format <f>]
+
+Formats are `native`, `compact` or a template such as \"{PC} {OP} V0:{V0} I:{I}\". A width after
+a field, {PC:3}, sets how many digits it is padded to, so a template can copy another emulator's log.
+Exits with 0 when the traces match, 1 when they diverge and 2 on errors.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut paths = Vec::new();
+    let mut left_format = String::from("native");
+    let mut right_format = String::from("native");
+
+    let mut index = 0;
+    while index < args.len() {
+        match args[index].as_str() {
+            option @ "--format" | option @ "--left-format" | option @ "--right-format" => {
+                index += 1;
+                let format = match args.get(index) {
+                    Some(format) => format.clone(),
+                    None => fail(&format!("{} expects a format", option)),
+                };
+                if option != "--right-format" {
+                    left_format = format.clone();
+                }
+                if option != "--left-format" {
+                    right_format = format;
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            path => paths.push(path.to_string()),
+        }
+        index += 1;
+    }
+
+    if paths.len() != 2 {
+        fail(USAGE);
+    }
+
+    let left_format = TraceFormat::named(&left_format).unwrap_or_else(|e| fail(&e));
+    let right_format = TraceFormat::named(&right_format).unwrap_or_else(|e| fail(&e));
+    let left = fs::read_to_string(&paths[0]).unwrap_or_else(|e| fail(&format!("{}: {}", paths[0], e)));
+    let right = fs::read_to_string(&paths[1]).unwrap_or_else(|e| fail(&format!("{}: {}", paths[1], e)));
+
+    match diff_traces(&left, &left_format, &right, &right_format) {
+        Ok(None) => println!("Traces match"),
+        Ok(Some(divergence)) => {
+            print!("{}", divergence);
+            process::exit(1);
+        },
+        Err(e) => fail(&e),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
@@ -0,0 +1,165 @@
+use std::cell::RefCell;
+use std::ops::{Index, IndexMut, RangeInclusive};
+use std::rc::Rc;
+use std::slice::SliceIndex;
+
+/// The memory a `System` reads and writes through. Instructions only go through `read` and
+/// `write`, so a bus can refuse writes, log accesses or watch addresses without the instructions
+/// knowing. Loading programs, save states, the tracer and the debugger use the bytes directly and
+/// are never seen by the bus.
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    fn bytes(&self) -> &[u8];
+    fn bytes_mut(&mut self) -> &mut [u8];
+
+    fn len(&self) -> usize {
+        self.bytes().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes().is_empty()
+    }
+}
+
+impl<I: SliceIndex<[u8]>> Index<I> for dyn Bus {
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &I::Output {
+        &self.bytes()[index]
+    }
+}
+
+impl<I: SliceIndex<[u8]>> IndexMut<I> for dyn Bus {
+    fn index_mut(&mut self, index: I) -> &mut I::Output {
+        &mut self.bytes_mut()[index]
+    }
+}
+
+/// Plain RAM of any size, every address readable and writable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatRam {
+    memory: Vec<u8>,
+}
+
+impl FlatRam {
+    pub fn new(size: usize) -> FlatRam {
+        FlatRam { memory: vec![0; size] }
+    }
+}
+
+impl Bus for FlatRam {
+    fn read(&mut self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.memory
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+}
+
+/// Ignores writes to some regions of another bus, like the ROM the interpreter and font were in.
+pub struct ReadOnly<B: Bus> {
+    inner: B,
+    regions: Vec<RangeInclusive<u16>>,
+}
+
+impl<B: Bus> ReadOnly<B> {
+    pub fn new(inner: B, regions: Vec<RangeInclusive<u16>>) -> ReadOnly<B> {
+        ReadOnly { inner, regions }
+    }
+}
+
+impl<B: Bus> Bus for ReadOnly<B> {
+    fn read(&mut self, address: u16) -> u8 {
+        self.inner.read(address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if !self.regions.iter().any(|region| region.contains(&address)) {
+            self.inner.write(address, value);
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.inner.bytes()
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self.inner.bytes_mut()
+    }
+}
+
+/// A read or write made by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+/// Where `Logged` puts the accesses it sees. The bus is owned by the system, so whoever wants the
+/// log keeps a clone of it.
+pub type AccessLog = Rc<RefCell<Vec<Access>>>;
+
+/// Records the accesses made to another bus, all of them or only those to watched addresses.
+/// Anything looking for watchpoints being hit checks the log after each instruction.
+pub struct Logged<B: Bus> {
+    inner: B,
+    //None logs every address
+    watched: Option<Vec<RangeInclusive<u16>>>,
+    log: AccessLog,
+}
+
+impl<B: Bus> Logged<B> {
+    /// Logs every access.
+    pub fn all(inner: B) -> (Logged<B>, AccessLog) {
+        let log = AccessLog::default();
+        (Logged { inner, watched: None, log: log.clone() }, log)
+    }
+
+    /// Logs only accesses to the watched `regions`.
+    pub fn watching(inner: B, regions: Vec<RangeInclusive<u16>>) -> (Logged<B>, AccessLog) {
+        let log = AccessLog::default();
+        (Logged { inner, watched: Some(regions), log: log.clone() }, log)
+    }
+
+    fn record(&self, address: u16, value: u8, write: bool) {
+        let logged = match self.watched {
+            Some(ref regions) => regions.iter().any(|region| region.contains(&address)),
+            None => true,
+        };
+        if logged {
+            self.log.borrow_mut().push(Access { address, value, write });
+        }
+    }
+}
+
+impl<B: Bus> Bus for Logged<B> {
+    fn read(&mut self, address: u16) -> u8 {
+        let value = self.inner.read(address);
+        self.record(address, value, false);
+        value
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.inner.write(address, value);
+        self.record(address, value, true);
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.inner.bytes()
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self.inner.bytes_mut()
+    }
+}
@@ -0,0 +1,304 @@
+/// What an 1802 is wired to: memory, the N lines for INP and OUT, and the four EF flag inputs.
+pub trait Board {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    /// OUT 1-7 puts `value` on the bus for `port`.
+    fn output(&mut self, port: u8, value: u8);
+    /// INP 1-7 reads `port` off the bus.
+    fn input(&mut self, port: u8) -> u8;
+    /// EF1-EF4, true while the flag is asserted.
+    fn flag(&self, number: u8) -> bool;
+}
+
+/// The RCA CDP1802 COSMAC, the CPU of the COSMAC VIP.
+///
+/// Every instruction takes 2 machine cycles, the long branches and skips 3. Interrupts and DMA
+/// are started by the board between instructions with `interrupt` and `dma_out`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cdp1802 {
+    /// The 16 scratchpad registers. Any of them can be the program counter (P) or the data
+    /// pointer (X), R0 is also the DMA pointer.
+    pub registers: [u16; 16],
+    pub p: u8,
+    pub x: u8,
+    pub d: u8,
+    pub df: bool,
+    //X and P saved by an interrupt
+    pub t: u8,
+    pub interrupts_enabled: bool,
+    pub q: bool,
+    //IDL waits for an interrupt or DMA
+    pub idle: bool,
+}
+
+impl Default for Cdp1802 {
+    fn default() -> Cdp1802 {
+        Cdp1802::new()
+    }
+}
+
+impl Cdp1802 {
+    /// The state after a reset: R0 is the program counter and starts at 0, interrupts are on.
+    pub fn new() -> Cdp1802 {
+        Cdp1802 {
+            registers: [0; 16],
+            p: 0,
+            x: 0,
+            d: 0,
+            df: false,
+            t: 0,
+            interrupts_enabled: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.registers[self.p as usize]
+    }
+
+    /// Takes an interrupt if they are enabled: X and P are saved in T, R2 becomes X and R1 the
+    /// program counter. Returns the machine cycles used.
+    pub fn interrupt(&mut self) -> u32 {
+        if !self.interrupts_enabled {
+            return 0;
+        }
+
+        self.t = self.x << 4 | self.p;
+        self.x = 2;
+        self.p = 1;
+        self.interrupts_enabled = false;
+        self.idle = false;
+        1
+    }
+
+    /// One DMA out cycle, reading the byte R0 points at for the board and moving R0 on.
+    pub fn dma_out<B: Board>(&mut self, board: &mut B) -> u8 {
+        let value = board.read(self.registers[0]);
+        self.registers[0] = self.registers[0].wrapping_add(1);
+        self.idle = false;
+        value
+    }
+
+    /// Runs one instruction and returns the machine cycles it took.
+    pub fn step<B: Board>(&mut self, board: &mut B) -> u32 {
+        if self.idle {
+            return 1;
+        }
+
+        let opcode = self.fetch(board);
+        let n = (opcode & 0x0F) as usize;
+        let x = self.x as usize;
+
+        match opcode >> 4 {
+            0x0 if n == 0 => { self.idle = true; },
+            0x0 => { self.d = board.read(self.registers[n]); },
+            0x1 => { self.registers[n] = self.registers[n].wrapping_add(1); },
+            0x2 => { self.registers[n] = self.registers[n].wrapping_sub(1); },
+            0x3 => {
+                let taken = match n {
+                    0x8 => false,
+                    _ => self.condition(board, n & 0x7) == (n < 0x8),
+                };
+                let target = board.read(self.program_counter());
+                if taken {
+                    let page = self.program_counter() & 0xFF00;
+                    self.set_program_counter(page | target as u16);
+                } else {
+                    self.set_program_counter(self.program_counter().wrapping_add(1));
+                }
+            },
+            0x4 => {
+                self.d = board.read(self.registers[n]);
+                self.registers[n] = self.registers[n].wrapping_add(1);
+            },
+            0x5 => { board.write(self.registers[n], self.d); },
+            0x6 if n == 0 => { self.registers[x] = self.registers[x].wrapping_add(1); },
+            0x6 if n < 0x8 => {
+                let value = board.read(self.registers[x]);
+                board.output(n as u8, value);
+                self.registers[x] = self.registers[x].wrapping_add(1);
+            },
+            //The 1804's extended instructions start with 68, on the 1802 it does nothing
+            0x6 if n == 0x8 => {},
+            0x6 => {
+                let value = board.input((n & 0x7) as u8);
+                board.write(self.registers[x], value);
+                self.d = value;
+            },
+            0x7 => { self.run_0x7(board, n); },
+            0x8 => { self.d = self.registers[n] as u8; },
+            0x9 => { self.d = (self.registers[n] >> 8) as u8; },
+            0xA => { self.registers[n] = (self.registers[n] & 0xFF00) | self.d as u16; },
+            0xB => { self.registers[n] = (self.registers[n] & 0x00FF) | (self.d as u16) << 8; },
+            0xC => {
+                self.run_0xc(board, n);
+                return 3;
+            },
+            0xD => { self.p = n as u8; },
+            0xE => { self.x = n as u8; },
+            _ => { self.run_0xf(board, n); }
+        }
+
+        2
+    }
+
+    fn fetch<B: Board>(&mut self, board: &mut B) -> u8 {
+        let value = board.read(self.program_counter());
+        self.set_program_counter(self.program_counter().wrapping_add(1));
+        value
+    }
+
+    fn set_program_counter(&mut self, address: u16) {
+        self.registers[self.p as usize] = address;
+    }
+
+    //The conditions of the branches and skips, from the low three bits of the opcode
+    fn condition<B: Board>(&self, board: &B, number: usize) -> bool {
+        match number {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            0x3 => self.df,
+            flag => board.flag(flag as u8 - 3),
+        }
+    }
+
+    fn run_0x7<B: Board>(&mut self, board: &mut B, n: usize) {
+        let x = self.x as usize;
+
+        match n {
+            0x0 | 0x1 => {
+                //RET and DIS restore X and P from the byte at R(X)
+                let value = board.read(self.registers[x]);
+                self.registers[x] = self.registers[x].wrapping_add(1);
+                self.x = value >> 4;
+                self.p = value & 0x0F;
+                self.interrupts_enabled = n == 0x0;
+            },
+            0x2 => {
+                self.d = board.read(self.registers[x]);
+                self.registers[x] = self.registers[x].wrapping_add(1);
+            },
+            0x3 => {
+                board.write(self.registers[x], self.d);
+                self.registers[x] = self.registers[x].wrapping_sub(1);
+            },
+            0x4 => {
+                let value = board.read(self.registers[x]);
+                self.add(value, self.df as u8);
+            },
+            0x5 => {
+                let value = board.read(self.registers[x]);
+                self.subtract(value, self.d, self.df);
+            },
+            0x6 => {
+                let carry = self.d & 0x01 != 0;
+                self.d = self.d >> 1 | (self.df as u8) << 7;
+                self.df = carry;
+            },
+            0x7 => {
+                let value = board.read(self.registers[x]);
+                self.subtract(self.d, value, self.df);
+            },
+            0x8 => { board.write(self.registers[x], self.t); },
+            0x9 => {
+                //MARK saves X and P for a subroutine called with SEP
+                self.t = self.x << 4 | self.p;
+                board.write(self.registers[2], self.t);
+                self.x = self.p;
+                self.registers[2] = self.registers[2].wrapping_sub(1);
+            },
+            0xA => { self.q = false; },
+            0xB => { self.q = true; },
+            0xC => {
+                let value = self.fetch(board);
+                self.add(value, self.df as u8);
+            },
+            0xD => {
+                let value = self.fetch(board);
+                self.subtract(value, self.d, self.df);
+            },
+            0xE => {
+                let carry = self.d & 0x80 != 0;
+                self.d = self.d << 1 | self.df as u8;
+                self.df = carry;
+            },
+            _ => {
+                let value = self.fetch(board);
+                self.subtract(self.d, value, self.df);
+            }
+        }
+    }
+
+    fn run_0xc<B: Board>(&mut self, board: &mut B, n: usize) {
+        //Long branches jump to the two bytes after the opcode, long skips step over them
+        let (skip, taken) = match n {
+            0x0 => (false, true),
+            0x1 => (false, self.q),
+            0x2 => (false, self.d == 0),
+            0x3 => (false, self.df),
+            0x4 => { return; },
+            0x5 => (true, !self.q),
+            0x6 => (true, self.d != 0),
+            0x7 => (true, !self.df),
+            0x8 => (true, true),
+            0x9 => (false, !self.q),
+            0xA => (false, self.d != 0),
+            0xB => (false, !self.df),
+            0xC => (true, self.interrupts_enabled),
+            0xD => (true, self.q),
+            0xE => (true, self.d == 0),
+            _ => (true, self.df),
+        };
+
+        let pc = self.program_counter();
+        if taken && !skip {
+            let high = board.read(pc) as u16;
+            let low = board.read(pc.wrapping_add(1)) as u16;
+            self.set_program_counter(high << 8 | low);
+        } else if taken || !skip {
+            self.set_program_counter(pc.wrapping_add(2));
+        }
+    }
+
+    fn run_0xf<B: Board>(&mut self, board: &mut B, n: usize) {
+        //F8-FF work on the byte after the opcode, F0-F7 on the byte at R(X)
+        let value = match n {
+            0x6 | 0xE => 0,
+            0x8..=0xF => self.fetch(board),
+            _ => board.read(self.registers[self.x as usize]),
+        };
+
+        match n & 0x7 {
+            0x0 => { self.d = value; },
+            0x1 => { self.d |= value; },
+            0x2 => { self.d &= value; },
+            0x3 => { self.d ^= value; },
+            0x4 => { self.add(value, 0); },
+            0x5 => { self.subtract(value, self.d, true); },
+            0x6 if n == 0x6 => {
+                self.df = self.d & 0x01 != 0;
+                self.d >>= 1;
+            },
+            0x6 => {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            },
+            _ => { self.subtract(self.d, value, true); }
+        }
+    }
+
+    fn add(&mut self, value: u8, carry: u8) {
+        let sum = self.d as u16 + value as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    //DF is set when there is no borrow, and a borrow in counts as DF being clear
+    fn subtract(&mut self, from: u8, value: u8, no_borrow: bool) {
+        let difference = from as i16 - value as i16 - (!no_borrow) as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+}
@@ -1,239 +1,816 @@
+use std::collections::HashMap;
 use std::io::prelude::*;
 use std::path::Path;
+use std::error::Error;
+use std::fmt;
 use std::fs;
 
+use crate::bus::{Bus, FlatRam};
+use crate::display::{Display, Rect, MAX_HEIGHT, MAX_WIDTH};
+use crate::font::{Font, DEFAULT_FONT_ADDRESS, SMALL_FONT_SIZE};
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::timing::{self, Timing, INTERPRETER_CYCLES, SKIP_CYCLES};
+use crate::trace::Tracer;
+use crate::variant::Variant;
+
+//The screen is big enough for the largest variant, smaller ones use the top left corner
+pub const SCREEN_WIDTH: usize = MAX_WIDTH;
+pub const SCREEN_HEIGHT: usize = MAX_HEIGHT;
+pub const MEMORY_SIZE: usize = 0x1000;
+//XO-CHIP addresses a full 64 KiB through F000 NNNN
+pub const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
+//A square wave, so the buzzer sounds like a plain beep until a program loads its own pattern
+const DEFAULT_AUDIO_PATTERN: [u8;16] = [0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF,
+                                        0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF];
+
+//CHIP-8X colours are set for zones 8 pixels wide and a row high
+pub const COLOUR_ZONE_COLUMNS: usize = 8;
+pub const COLOUR_ZONE_ROWS: usize = 32;
+//Red, until a program picks something else
+const DEFAULT_ZONE_COLOUR: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CpuError {
+    /// The opcode at `address` is not an instruction of the variant being run.
+    UnknownOpcode { address: u16, opcode: u16, variant: Variant },
+    /// In strict mode, the instruction at `address` wrote below the program, where the
+    /// interpreter and font live. None of the instruction's writes are made, even to the bytes
+    /// that were allowed.
+    ProtectedWrite { address: u16, target: usize },
+    /// In strict mode, the instruction at `address` read past the end of memory.
+    ReadPastEnd { address: u16, target: usize },
+    /// In strict mode, the program counter got to `address`, outside the program that was loaded.
+    LeftProgram { address: u16 },
+    /// The instruction at `address` called the machine code `routine`, which has no native routine
+    /// registered, and such calls are errors.
+    MachineCode { address: u16, routine: u16 },
+    /// The call at `address` found all 16 levels of the stack already in use.
+    StackOverflow { address: u16 },
+    /// The return at `address` found nothing on the stack to return to.
+    StackUnderflow { address: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CpuError::UnknownOpcode { address, opcode, variant } => {
+                write!(f, "{:04X} at {:03X} is not a {} instruction", opcode, address, variant.name())
+            },
+            CpuError::ProtectedWrite { address, target } => {
+                write!(f, "the instruction at {:03X} wrote to {:03X}, below the program", address, target)
+            },
+            CpuError::ReadPastEnd { address, target } => {
+                write!(f, "the instruction at {:03X} read from {:03X}, past the end of memory", address, target)
+            },
+            CpuError::LeftProgram { address } => write!(f, "the program counter left the program for {:03X}", address),
+            CpuError::MachineCode { address, routine } => {
+                write!(f, "the instruction at {:03X} calls machine code at {:03X}, which is not emulated", address, routine)
+            },
+            CpuError::StackOverflow { address } => write!(f, "the call at {:03X} overflowed the stack", address),
+            CpuError::StackUnderflow { address } => write!(f, "the return at {:03X} has nothing on the stack", address),
+        }
+    }
+}
+
+impl Error for CpuError {}
+
+/// How careful the system is about where programs read, write and run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryMode {
+    /// Anything goes, like on real hardware: addresses wrap around the end of memory and the
+    /// interpreter's area can be overwritten.
+    Permissive,
+    /// Writes below the program, reads past the end of memory and running anything but the
+    /// program are faults, stopping the program on the instruction that made them.
+    Strict,
+}
+
+/// Rust standing in for a machine code routine, with the whole system to work on. The program
+/// carries on after the 0NNN once it returns.
+pub type NativeRoutine = Box<dyn FnMut(&mut System)>;
+
+/// What 0NNN does when no native routine is registered for NNN. On the COSMAC VIP it called the
+/// 1802 machine code at NNN, which is not emulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineCode {
+    /// Moves on to the next instruction, as if the routine returned straight away.
+    Ignore,
+    /// Stops the program on the call with `CpuError::MachineCode`.
+    Error,
+}
+
 pub struct System {
-    pub memory: [u8;4096],
+    pub variant: Variant,
+    //Instructions read and write through the bus, anything else can index it like a slice
+    pub memory: Box<dyn Bus>,
     pub registers: [u8;16],
     pub index_register: u16,
     pub program_counter: u16,
-    pub screen: [[u8;32];64],
+    pub display: Display,
+    //SUPER-CHIP 128x64 mode, otherwise the screen is 64x32
+    pub hires: bool,
+    //The XO-CHIP bitplanes that drawing, clearing and scrolling work on, bit 0 for the first plane.
+    //Each screen pixel holds one bit per plane, so it is one of four colours
+    pub planes: u8,
+    //XO-CHIP sound, a 1 bit sample loop played while the sound timer runs
+    pub audio_pattern: [u8;16],
+    pub pitch: u8,
+    //CHIP-8X foreground colour (0-7) of each zone and the background colour (0-3)
+    pub colour_zones: [[u8;COLOUR_ZONE_ROWS];COLOUR_ZONE_COLUMNS],
+    pub background: u8,
+    //CHIP-8X I/O port, FXF8 writes to the output latch and FXFB reads the input
+    pub port_output: u8,
+    pub port_input: u8,
     pub delay_timer: u8,
     pub sound_timer: u8,
-    key: [u8;16],
+    //COSMAC VIP machine cycles used so far this frame, only counted down with VIP timing
+    pub cycles: u32,
+    pub key: [u8;16],
     //For Emulation
     pub stack: [u16;16],
     pub stack_pointer: u8,
+    pub quirks: Quirks,
+    //The glyphs setup_fontset loads and where, FX29 and FX30 point into them
+    pub font: Font,
+    pub font_address: u16,
+    pub rng: Rng,
+    //The HP48 RPL user flags that FX75 and FX85 save registers to, kept between runs by the frontend
+    pub rpl_flags: [u8;16],
+    //Set by 00FD, nothing more runs once the program has exited
+    pub exited: bool,
+    //Set by DXYN with the display wait quirk, under VIP timing the frame ends once it has drawn
+    pub waiting_for_display: bool,
+    //Logs every instruction before it runs when set
+    pub tracer: Option<Tracer>,
+    pub memory_mode: MemoryMode,
+    pub machine_code: MachineCode,
+    //The size of the program last loaded, where strict mode lets the program counter go
+    pub program_size: usize,
+    //The first fault of the instruction running, returned once it has finished
+    fault: Option<CpuError>,
+    //Called by 0NNN in place of the machine code at NNN
+    routines: HashMap<u16, NativeRoutine>,
 }
 
 impl Default for System {
     fn default() -> System {
         System {
-            memory: [0;4096],
+            variant: Variant::Chip8,
+            memory: Box::new(FlatRam::new(MEMORY_SIZE)),
             registers: [0;16],
             index_register: 0,
             //Stating point of program
             program_counter: 0x200,
-            screen: [[0;32];64],
+            display: Display::new(64, 32),
+            hires: false,
+            planes: 1,
+            audio_pattern: DEFAULT_AUDIO_PATTERN,
+            pitch: 64,
+            colour_zones: [[DEFAULT_ZONE_COLOUR;COLOUR_ZONE_ROWS];COLOUR_ZONE_COLUMNS],
+            background: 0,
+            port_output: 0,
+            port_input: 0,
             delay_timer: 0,
             sound_timer: 0,
+            cycles: 0,
             key: [0;16],
             stack: [0;16],
-            stack_pointer: 0
+            stack_pointer: 0,
+            quirks: Quirks::default(),
+            font: Variant::Chip8.font_set().font(),
+            font_address: DEFAULT_FONT_ADDRESS,
+            rng: Rng::default(),
+            rpl_flags: [0;16],
+            exited: false,
+            waiting_for_display: false,
+            tracer: None,
+            memory_mode: MemoryMode::Permissive,
+            machine_code: MachineCode::Error,
+            program_size: 0,
+            fault: None,
+            routines: HashMap::new(),
         }
     }
 }
 
 impl System {
+    /// A system running `variant`, with its memory, quirks and the program counter where its
+    /// programs start.
+    pub fn new(variant: Variant) -> System {
+        System {
+            variant,
+            memory: Box::new(FlatRam::new(variant.memory_size())),
+            program_counter: variant.program_start(),
+            quirks: variant.quirks(),
+            font: variant.font_set().font(),
+            display: {
+                let (width, height) = variant.screen_size(false);
+                Display::new(width, height)
+            },
+            ..System::default()
+        }
+    }
+
+    /// A system with `size` bytes of memory instead of the usual 4 KiB.
+    pub fn with_memory_size(size: usize) -> System {
+        System {
+            memory: Box::new(FlatRam::new(size)),
+            ..System::default()
+        }
+    }
+
+    /// Loads the font at the font address, followed by the big digits for variants with FX30.
     pub fn setup_fontset(&mut self) {
-        let chip8_fontset = [ 
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80  // F
-        ];
-
-        for index in 0..chip8_fontset.len() {
-            self.memory[index] = chip8_fontset[index];
-        }
-    }
-
-    pub fn run_op_at(&mut self, address: u16) {
-        //TODO: Do check if there is an op code
-        let value = self.memory[address as usize];
-        let first =  value & 0xF0;
-        let mut address_changed = false;
-        let second = self.memory[(address + 1) as usize];
-        //println!("New address : {}", address);
-        //println!("New op-code: {:x}{:x}\n", value, second);
-        match first {
-            0x00 => { self.process_0x_00(value, second); },
-            0x10 => { self.jump(value, second); },
-            0x20 => { self.call(value, second, address); },
-            0x30 => { self.skip_if_equal(value, second); },
-            0x40 => { unimplemented!("Skip if not equal (constant)"); },
-            0x50 => { unimplemented!("Skip if equal (to register)"); },
-            0x60 => { self.set_register_to(value, second); },
-            0x70 => { self.add_value_to_register(value, second); },
-            0x80 => { unimplemented!("Binary ops and maths"); },
-            0x90 => { unimplemented!("Skip if registers not equal"); },
-            0xA0 => { self.set_index_register(value, second); },
-            0xB0 => { unimplemented!("Jump to address plus value in V0"); },
-            0xC0 => { unimplemented!("Set register to random value anded with NN"); },
-            0xD0 => { self.draw(value, second); },
-            0xE0 => { unimplemented!("Key operations"); },
-            0xF0 => { self.process_0x_F0(value, second); },
-            _ => {
-                //println!("Invalid opcode");
-            }
+        let start = self.font_address as usize;
+        self.memory[start..start + SMALL_FONT_SIZE].copy_from_slice(&self.font.small);
+        if self.variant.has_big_font() {
+            let big_font = self.font.big_or_default();
+            let big_start = self.big_font_address() as usize;
+            self.memory[big_start..big_start + big_font.len()].copy_from_slice(&big_font);
         }
+    }
+
+    /// Where FX30 finds the 8x10 digits, straight after the small font.
+    pub fn big_font_address(&self) -> u16 {
+        self.font_address + SMALL_FONT_SIZE as u16
+    }
+
+    /// Runs `routine` whenever the program calls the machine code at `address` with 0NNN,
+    /// replacing any routine registered there before.
+    pub fn register_routine<F: FnMut(&mut System) + 'static>(&mut self, address: u16, routine: F) {
+        self.routines.insert(address & 0x0FFF, Box::new(routine));
+    }
+
+    /// Removes the routine at `address`, returning whether there was one.
+    pub fn unregister_routine(&mut self, address: u16) -> bool {
+        self.routines.remove(&(address & 0x0FFF)).is_some()
+    }
+
+    pub fn has_routine(&self, address: u16) -> bool {
+        self.routines.contains_key(&(address & 0x0FFF))
+    }
+
+    pub fn screen_width(&self) -> usize {
+        self.display.width()
+    }
+
+    pub fn screen_height(&self) -> usize {
+        self.display.height()
+    }
+
+    /// How many bits of the audio pattern play each second, 4000 at the default pitch of 64.
+    pub fn audio_sample_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
 
-        if !address_changed {
-            self.program_counter = self.program_counter + 2;
+    //Runs the instruction the program counter points at
+    pub fn step(&mut self) -> Result<(), CpuError> {
+        if self.exited {
+            return Ok(());
         }
+
+        self.run_op_at(self.program_counter)
+    }
+
+    //The timers count down at 60Hz, once per frame
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
     }
 
-    fn skip_if_equal(&mut self, first: u8, value: u8) {
+    //Stops at the first instruction that fails, without ticking the timers
+    pub fn run_frame(&mut self, instructions: usize) -> Result<(), CpuError> {
+        Timing::Fixed(instructions).run_frame(self)
+    }
+
+    /// Runs the instruction at `address` through the variant's instruction set. An opcode the
+    /// variant does not have is an error, and leaves the program counter on it.
+    pub fn run_op_at(&mut self, address: u16) -> Result<(), CpuError> {
+        let program_start = self.variant.program_start() as usize;
+        let in_program = (program_start..program_start + self.program_size).contains(&(address as usize));
+        if self.memory_mode == MemoryMode::Strict && !in_program {
+            return Err(CpuError::LeftProgram { address });
+        }
+
+        let first = self.memory[address as usize % self.memory.len()];
+        let second = self.memory[(address as usize + 1) % self.memory.len()];
+        let opcode = (first as u16) << 8 | second as u16;
+
+        if let Some(mut tracer) = self.tracer.take() {
+            tracer.trace(self, address);
+            self.tracer = Some(tracer);
+        }
+
+        let instruction = match self.variant.instruction_set().decode(opcode) {
+            Some(instruction) => instruction,
+            None => { return Err(CpuError::UnknownOpcode { address, opcode, variant: self.variant }); }
+        };
+        let cycles = timing::instruction_cycles(self, instruction.form, first, second);
+        //With the display wait quirk the VIP draws once the display interrupt has been, at the
+        //start of the next frame
+        if instruction.form == "DXYN" && self.quirks.display_wait {
+            self.cycles = self.cycles.max(INTERPRETER_CYCLES);
+            self.waiting_for_display = true;
+        }
+        (instruction.execute)(self, address, first, second);
+        self.cycles += cycles;
+        if let Some(fault) = self.fault.take() {
+            self.program_counter = address;
+            return Err(fault);
+        }
+
+        //Off the end of memory the program counter comes back round to the start
+        if !instruction.jumps {
+            self.program_counter = (self.program_counter.wrapping_add(2) as usize % self.memory.len()) as u16;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn skip_if_equal(&mut self, first: u8, value: u8) {
         let register = first & 0x0F;
         let register_value = self.registers[register as usize];
 
         if value == register_value {
             //Skip the next instruction as this adds to and when it returns 2 is also added
-            self.program_counter += 2;
+            self.skip_next_instruction();
         }
     }
 
-    fn add_value_to_register(&mut self, first: u8, value: u8) {
-        let register = (first & 0x0F) as usize;
-        self.registers[register] += value;
+    fn skip_next_instruction(&mut self) {
+        //F000 NNNN is twice as long as everything else on XO-CHIP, elsewhere F0 00 is just data
+        let next = self.program_counter as usize + 2;
+        let length = self.memory.len();
+        let long = self.variant == Variant::XoChip
+            && self.memory[next % length] == 0xF0 && self.memory[(next + 1) % length] == 0x00;
+
+        self.program_counter = (self.program_counter.wrapping_add(if long { 4 } else { 2 }) as usize % length) as u16;
+        self.cycles += SKIP_CYCLES;
     }
 
-    fn process_0x_00(&mut self, first_part: u8, second_part: u8) -> bool{
-        let code_redirect = false;
+    pub(crate) fn skip_if_not_equal(&mut self, first: u8, value: u8) {
+        let register = first & 0x0F;
 
-        match second_part {
-            0xEE => {
-                self.stack_pointer -= 1;
-                let new_address = self.stack[self.stack_pointer as usize];
+        if value != self.registers[register as usize] {
+            self.skip_next_instruction();
+        }
+    }
 
-                self.program_counter = new_address;
-                //println!("Returning to: {}", new_address);
-            },
-            _ => {
-                unimplemented!("0x00 opcode not implemented");
-            }
+    pub(crate) fn skip_if_registers_equal(&mut self, first: u8, second: u8) {
+        let x_register = first & 0x0F;
+        let y_register = (second & 0xF0) >> 4;
+
+        if self.registers[x_register as usize] == self.registers[y_register as usize] {
+            self.skip_next_instruction();
+        }
+    }
+
+    //Adds VY to VX a nibble at a time, each nibble wrapping around at 8
+    pub(crate) fn add_nibbles(&mut self, first_part: u8, second_part: u8) {
+        let x = (first_part & 0x0F) as usize;
+        let y = ((second_part & 0xF0) >> 4) as usize;
+        let (vx, vy) = (self.registers[x], self.registers[y]);
+        let high = ((vx >> 4) + (vy >> 4)) & 0x07;
+        let low = ((vx & 0x0F) + (vy & 0x0F)) & 0x07;
+        self.registers[x] = high << 4 | low;
+    }
+
+    //Save VX to VY at I, without moving I
+    pub(crate) fn save_register_range(&mut self, first_part: u8, second_part: u8) {
+        if !self.can_write(self.index_register as usize, register_range(first_part, second_part).count()) {
+            return;
         }
+        for (offset, register) in register_range(first_part, second_part).enumerate() {
+            self.write_memory(self.index_register as usize + offset, self.registers[register]);
+        }
+    }
 
-        code_redirect
+    pub(crate) fn load_register_range(&mut self, first_part: u8, second_part: u8) {
+        for (offset, register) in register_range(first_part, second_part).enumerate() {
+            self.registers[register] = self.read_memory(self.index_register as usize + offset);
+        }
     }
 
-    fn process_0x_F0(&mut self, first_part: u8, second_part: u8) {
-        match second_part {
-            0x07 => {
-                //Set VX to equal to delay timer
-                let register  = first_part & 0x0F;
-                self.registers[register as usize] = self.delay_timer;
+    pub(crate) fn skip_if_registers_not_equal(&mut self, first: u8, second: u8) {
+        let x_register = first & 0x0F;
+        let y_register = (second & 0xF0) >> 4;
+
+        if self.registers[x_register as usize] != self.registers[y_register as usize] {
+            self.skip_next_instruction();
+        }
+    }
+
+    pub(crate) fn add_value_to_register(&mut self, first: u8, value: u8) {
+        let register = (first & 0x0F) as usize;
+        //Wraps around and leaves VF alone
+        self.registers[register] = self.registers[register].wrapping_add(value);
+    }
+
+    pub(crate) fn arithmetic(&mut self, first_part: u8, second_part: u8) {
+        let x = (first_part & 0x0F) as usize;
+        let y = ((second_part & 0xF0) >> 4) as usize;
+        let vx = self.registers[x];
+        let vy = self.registers[y];
+
+        //VF is written last so that it wins when it is also VX
+        match second_part & 0x0F {
+            0x0 => { self.registers[x] = vy; },
+            0x1..=0x3 => {
+                self.registers[x] = match second_part & 0x0F {
+                    0x1 => vx | vy,
+                    0x2 => vx & vy,
+                    _ => vx ^ vy,
+                };
+                if self.quirks.vf_reset {
+                    self.registers[0xF] = 0;
+                }
             },
-            0x15 => {
-                //Set delay timer to the value in VX
-                let register = first_part & 0x0F;
-                let amount = self.registers[register as usize];
-                self.delay_timer = amount;
+            0x4 => {
+                let (result, carry) = vx.overflowing_add(vy);
+                self.registers[x] = result;
+                self.registers[0xF] = carry as u8;
             },
-            0x29 => {
-                //Set index register to value sprite address what represents this value
-                let register = first_part & 0x0F;
-                let value = self.registers[register as usize];
-                //Is this ok?
-                //The index is always 5 times its value that it wants to represent
-                self.index_register = (value as u16) * 5;
+            0x5 => {
+                self.registers[x] = vx.wrapping_sub(vy);
+                self.registers[0xF] = (vx >= vy) as u8;
             },
-            0x33 => {
-                //Calculate the BCD for this value and store at where index register points to
-                let register = (first_part & 0xF0) >> 4;
-                let value = self.registers[register as usize];
-                let (hundreds, tens, ones) = encode_to_bcd(value);
-
-                self.memory[self.index_register as usize] = hundreds;
-                self.memory[(self.index_register + 1) as usize] = tens;
-                self.memory[(self.index_register + 2) as usize] = ones;
+            0x7 => {
+                self.registers[x] = vy.wrapping_sub(vx);
+                self.registers[0xF] = (vy >= vx) as u8;
             },
-            0x65 => {
-                let last_register = (first_part & 0xF0) >> 4;
-                self.reg_load(last_register);
-            }
-            _ => { unimplemented!("Other 0xF0 opcodes unimplemented"); }
+            0x6 => {
+                let value = if self.quirks.shift_uses_vy { vy } else { vx };
+                self.registers[x] = value >> 1;
+                self.registers[0xF] = value & 0x01;
+            },
+            0xE => {
+                let value = if self.quirks.shift_uses_vy { vy } else { vx };
+                self.registers[x] = value << 1;
+                self.registers[0xF] = value >> 7;
+            },
+            operation => { unreachable!("8XY{:X} is not in any instruction set", operation); }
+        }
+    }
+
+    pub(crate) fn call_machine_code(&mut self, first_part: u8, second_part: u8, address: u16) {
+        let routine = ((first_part & 0x0F) as u16) << 8 | second_part as u16;
+
+        //The routine is taken out while it runs, as it needs the system
+        if let Some(mut native) = self.routines.remove(&routine) {
+            native(self);
+            self.routines.entry(routine).or_insert(native);
+            return;
+        }
+
+        match self.machine_code {
+            MachineCode::Ignore => {},
+            MachineCode::Error => { self.report_fault(CpuError::MachineCode { address, routine }); }
+        }
+    }
+
+    pub(crate) fn return_from_call(&mut self, address: u16) {
+        if self.stack_pointer == 0 {
+            self.report_fault(CpuError::StackUnderflow { address });
+            return;
+        }
+
+        self.stack_pointer -= 1;
+        let new_address = self.stack[self.stack_pointer as usize];
+
+        self.program_counter = new_address;
+    }
+
+    pub(crate) fn exit(&mut self) {
+        //Stay on the exit instruction
+        self.exited = true;
+        self.program_counter = (self.program_counter.wrapping_sub(2) as usize % self.memory.len()) as u16;
+    }
+
+    //Blue, black, green, red and around again
+    pub(crate) fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % 4;
+        self.display.mark_all_dirty();
+    }
+
+    pub(crate) fn skip_if_key(&mut self, first_part: u8, held: bool) {
+        let register = first_part & 0x0F;
+        let key = (self.registers[register as usize] & 0x0F) as usize;
+
+        if (self.key[key] != 0) == held {
+            self.skip_next_instruction();
+        }
+    }
+
+    //I is loaded from the word after the instruction, which is then stepped over
+    pub(crate) fn load_long_index(&mut self) {
+        let address = self.program_counter as usize + 2;
+        let high = self.memory[address % self.memory.len()] as u16;
+        let low = self.memory[(address + 1) % self.memory.len()] as u16;
+        self.index_register = high << 8 | low;
+        self.program_counter = (self.program_counter.wrapping_add(2) as usize % self.memory.len()) as u16;
+    }
+
+    //FN01 selects bitplanes, N is in the register position
+    pub(crate) fn select_planes(&mut self, first_part: u8) {
+        self.planes = first_part & 0x03;
+    }
+
+    pub(crate) fn load_audio_pattern(&mut self) {
+        for offset in 0..self.audio_pattern.len() {
+            self.audio_pattern[offset] = self.read_memory(self.index_register as usize + offset);
+        }
+    }
+
+    //Wait for a key, by running this instruction again until one is held
+    pub(crate) fn wait_for_key(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        match self.key.iter().position(|&held| held != 0) {
+            Some(key) => { self.registers[register as usize] = key as u8; },
+            None => { self.program_counter = (self.program_counter.wrapping_sub(2) as usize % self.memory.len()) as u16; }
+        }
+    }
+
+    //Set VX to equal to delay timer
+    pub(crate) fn load_delay_timer(&mut self, first_part: u8) {
+        let register  = first_part & 0x0F;
+        self.registers[register as usize] = self.delay_timer;
+    }
+
+    //Set delay timer to the value in VX
+    pub(crate) fn set_delay_timer(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        let amount = self.registers[register as usize];
+        self.delay_timer = amount;
+    }
+
+    pub(crate) fn set_sound_timer(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        self.sound_timer = self.registers[register as usize];
+    }
+
+    pub(crate) fn add_to_index_register(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        let value = self.registers[register as usize] as u16;
+        self.index_register = self.index_register.wrapping_add(value);
+    }
+
+    //Set index register to value sprite address what represents this value
+    pub(crate) fn point_at_character(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        let value = self.registers[register as usize];
+        //The small font digits are 5 bytes each
+        self.index_register = self.font_address + ((value & 0x0F) as u16) * 5;
+    }
+
+    //The big font digits are 10 bytes each
+    pub(crate) fn point_at_big_character(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        let value = self.registers[register as usize];
+        self.index_register = self.big_font_address() + ((value & 0x0F) as u16) * 10;
+    }
+
+    pub(crate) fn write_port(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        self.port_output = self.registers[register as usize];
+    }
+
+    //Nothing is ever waiting on the port, so whatever is latched there is read straight away
+    pub(crate) fn read_port(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        self.registers[register as usize] = self.port_input;
+    }
+
+    pub(crate) fn set_pitch(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        self.pitch = self.registers[register as usize];
+    }
+
+    //Calculate the BCD for this value and store at where index register points to
+    pub(crate) fn store_bcd(&mut self, first_part: u8) {
+        let register = first_part & 0x0F;
+        let value = self.registers[register as usize];
+        let (hundreds, tens, ones) = encode_to_bcd(value);
+
+        let address = self.index_register as usize;
+        if !self.can_write(address, 3) {
+            return;
+        }
+        self.write_memory(address, hundreds);
+        self.write_memory(address + 1, tens);
+        self.write_memory(address + 2, ones);
+    }
+
+    pub(crate) fn save_flags(&mut self, first_part: u8) {
+        let last_register = (first_part & 0x0F) as usize;
+        self.rpl_flags[..=last_register].copy_from_slice(&self.registers[..=last_register]);
+    }
+
+    pub(crate) fn load_flags(&mut self, first_part: u8) {
+        let last_register = (first_part & 0x0F) as usize;
+        self.registers[..=last_register].copy_from_slice(&self.rpl_flags[..=last_register]);
+    }
+
+    pub(crate) fn reg_store(&mut self, last_register: u8) {
+        if !self.can_write(self.index_register as usize, last_register as usize + 1) {
+            return;
+        }
+        //Inclusive of the last value
+        for register in 0..(last_register + 1) {
+            let address = self.index_register.wrapping_add(register as u16) as usize;
+            self.write_memory(address, self.registers[register as usize]);
+        }
+
+        if self.quirks.load_store_increments_i {
+            self.index_register = self.index_register.wrapping_add(last_register as u16 + 1);
         }
     }
 
-    fn reg_load(&mut self, last_register: u8) {
+    pub(crate) fn reg_load(&mut self, last_register: u8) {
         //Inclusive of the last value
         for register in 0..(last_register + 1) {
-            let value = self.memory[self.index_register as usize];
+            let value = self.read_memory(self.index_register.wrapping_add(register as u16) as usize);
             self.registers[register as usize] = value;
         }
+
+        if self.quirks.load_store_increments_i {
+            self.index_register = self.index_register.wrapping_add(last_register as u16 + 1);
+        }
     }
 
-    fn jump(&mut self, first_part: u8, second_part: u8) {
+    pub(crate) fn jump(&mut self, first_part: u8, second_part: u8) {
         let top_value: u16 = ((first_part & 0x0F) as u16) * 256;
         let total_value = top_value + (second_part as u16);
 
         self.program_counter = total_value;
     }
 
-    fn call(&mut self, first_part: u8, second_part: u8, original_address: u16) {
-        //println!("Adding {} to stack", original_address);
+    pub(crate) fn jump_with_offset(&mut self, first_part: u8, second_part: u8) {
+        //BXNN on CHIP-48 and SUPER-CHIP adds VX rather than V0
+        let register = if self.quirks.jump_uses_vx { first_part & 0x0F } else { 0 };
+        let offset = self.registers[register as usize] as u16;
+
+        self.jump(first_part, second_part);
+        self.program_counter = (self.program_counter + offset) & 0x0FFF;
+    }
+
+    pub(crate) fn call(&mut self, first_part: u8, second_part: u8, original_address: u16) {
+        if self.stack_pointer as usize >= self.stack.len() {
+            self.report_fault(CpuError::StackOverflow { address: original_address });
+            return;
+        }
+
         self.stack[self.stack_pointer as usize] = original_address;
         self.stack_pointer = self.stack_pointer + 1;
 
         self.jump(first_part, second_part);
     }
 
-    fn draw(&mut self, first_part: u8, second_part: u8) {
+    //The HIRES interpreter takes over the 1260 every HIRES program starts with
+    pub(crate) fn hires_jump(&mut self, first_part: u8, second_part: u8, original_address: u16) {
+        self.jump(first_part, second_part);
+        if original_address == 0x200 && self.program_counter == 0x260 {
+            self.program_counter = 0x2C0;
+        }
+    }
+
+    pub(crate) fn draw(&mut self, first_part: u8, second_part: u8) {
+        let height = (second_part & 0x0F) as usize;
+        self.draw_sprite(first_part, second_part, 8, height);
+    }
+
+    //DXY0 draws a SUPER-CHIP 16x16 sprite, two bytes a row
+    pub(crate) fn draw_big(&mut self, first_part: u8, second_part: u8) {
+        self.draw_sprite(first_part, second_part, 16, 16);
+    }
+
+    fn draw_sprite(&mut self, first_part: u8, second_part: u8, sprite_width: usize, sprite_height: usize) {
         let x_register = first_part & 0x0F;
         let y_register = (second_part & 0xF0) >> 4;
-        //println!("Draw");
-        //println!("Y Register: {}", y_register);
-        let height = second_part & 0x0F;
-        let initial_height = self.registers[y_register as usize];
-        let initial_width = self.registers[x_register as usize];
-        //println!("Height: {}", height);
+        let width = self.screen_width();
+        let screen_height = self.screen_height();
+        //Where the sprite starts always wraps around, the rest of it is clipped or wraps by quirk
+        let initial_width = self.registers[x_register as usize] as usize % width;
+        let initial_height = self.registers[y_register as usize] as usize % screen_height;
+        let bytes_per_row = sprite_width / 8;
+        //Each selected plane takes its own sprite, one after the other starting at I
+        let mut sprite_address = self.index_register as usize;
         //Reset 0xF register
         self.registers[0xF] = 0;
+        //The corners of what was drawn, marked dirty once the sprite is done
+        let mut drawn: Option<(usize, usize, usize, usize)> = None;
 
-        //Get the sprite at this address
-
-        for y in 0..height {
-            let sprite_line = self.memory[(self.index_register + y as u16) as usize];
-            //println!("{:x}", sprite_line);
-            for x in 0..8 {
-                let pixel = sprite_line & (0x80 >> x);
-                //println!("{:b}", pixel);
-                if pixel != 0{
-                    //Pixel is now a colour
-                    //TODO:Implement collision detection
+        for plane in 0..2 {
+            let plane_bit = 1 << plane;
+            if self.planes & plane_bit == 0 {
+                continue;
+            }
+
+            for y in 0..sprite_height {
+                let mut new_y = y + initial_height;
+                if new_y >= screen_height {
+                    if self.quirks.clip_sprites {
+                        break;
+                    }
+                    new_y %= screen_height;
                 }
-                //println!("X: {}\tY: {}", x + initial_width as u16, y + initial_height);
-                let new_x = x + initial_width as u16;
-                let new_y = y + initial_height;
 
-                if new_x < 64 && new_y < 32 {
-                    self.screen[new_x as usize][new_y as usize] = pixel;
+                let mut sprite_byte = 0;
+                for x in 0..sprite_width {
+                    if x % 8 == 0 {
+                        sprite_byte = self.read_memory(sprite_address + y * bytes_per_row + x / 8);
+                    }
+                    if sprite_byte & (0x80 >> (x % 8)) == 0 {
+                        continue;
+                    }
+
+                    let mut new_x = x + initial_width;
+                    if new_x >= width {
+                        if self.quirks.clip_sprites {
+                            continue;
+                        }
+                        new_x %= width;
+                    }
+
+                    //Pixels are XORed on, turning one off is a collision
+                    if self.display.toggle(new_x, new_y, plane_bit) {
+                        self.registers[0xF] = 1;
+                    }
+                    drawn = Some(match drawn {
+                        Some((left, top, right, bottom)) => (left.min(new_x), top.min(new_y), right.max(new_x), bottom.max(new_y)),
+                        None => (new_x, new_y, new_x, new_y),
+                    });
                 }
             }
+
+            sprite_address += sprite_height * bytes_per_row;
+        }
+
+        if let Some((left, top, right, bottom)) = drawn {
+            self.display.mark_dirty(Rect { x: left, y: top, width: right - left + 1, height: bottom - top + 1 });
+        }
+    }
+
+    //BXY0 colours whole zones, 4 rows at a time: the low nibbles of VX and VX+1 are the first
+    //zone across and down and the high nibbles how many more to colour. BXYN colours N rows of
+    //the zone column holding pixel VX, starting at row VX+1. Either way VY is the colour.
+    pub(crate) fn set_colour_zones(&mut self, first_part: u8, second_part: u8) {
+        let x = (first_part & 0x0F) as usize;
+        let y = ((second_part & 0xF0) >> 4) as usize;
+        let rows = (second_part & 0x0F) as usize;
+        let horizontal = self.registers[x];
+        let vertical = self.registers[(x + 1) % 16];
+        let colour = self.registers[y] & 0x07;
+
+        let (columns, row_range) = if rows == 0 {
+            let first_column = (horizontal & 0x0F) as usize;
+            let first_row = (vertical & 0x0F) as usize * 4;
+            let columns = first_column..=first_column + (horizontal >> 4) as usize;
+            (columns, first_row..first_row + ((vertical >> 4) as usize + 1) * 4)
+        } else {
+            let column = (horizontal as usize % (COLOUR_ZONE_COLUMNS * 8)) / 8;
+            let first_row = vertical as usize;
+            (column..=column, first_row..first_row + rows)
+        };
+
+        for column in columns {
+            for row in row_range.clone() {
+                self.colour_zones[column % COLOUR_ZONE_COLUMNS][row % COLOUR_ZONE_ROWS] = colour;
+            }
         }
+        self.display.mark_all_dirty();
+    }
+
+    pub(crate) fn clear_screen(&mut self) {
+        self.display.clear();
+    }
+
+    pub(crate) fn set_hires(&mut self, hires: bool) {
+        //Switching resolution clears the screen, as Octo and most SUPER-CHIP games expect
+        self.hires = hires;
+        let (width, height) = self.variant.screen_size(hires);
+        self.display.resize(width, height);
     }
 
-    fn set_index_register(&mut self, first_part: u8, second_part: u8) {
+    //Moves the selected planes by dx, dy pixels, what comes in from the edges is blank
+    pub(crate) fn scroll(&mut self, dx: isize, dy: isize) {
+        self.display.scroll(dx, dy, self.planes);
+    }
+
+    pub(crate) fn set_index_register(&mut self, first_part: u8, second_part: u8) {
         let top_value: u16 = ((first_part & 0x0F) as u16) * 256;
         let total_value = top_value + (second_part as u16);
 
         self.index_register = total_value;
     }
 
-    fn set_register_to(&mut self, first_part: u8, second_part: u8) {
+    pub(crate) fn set_register_random(&mut self, first_part: u8, second_part: u8) {
+        let register = (first_part & 0x0F) as usize;
+
+        self.registers[register] = self.rng.next_u8() & second_part;
+    }
+
+    pub(crate) fn set_register_to(&mut self, first_part: u8, second_part: u8) {
         let register = (first_part & 0x0F) as usize;
 
         self.registers[register] = second_part;
@@ -242,18 +819,74 @@ impl System {
     pub fn load_program(&mut self, program_path: &String) {
         match fs::read(program_path) {
             Ok(program) => {
-                let mut current_address: u16 = 0x200;
-
-                for byte in program {
-                    self.memory[current_address as usize] = byte;
-                    current_address = current_address + 1;
-                }
+                self.load_program_bytes(&program);
             },
             Err(e) => {
                 //println!("Could not load file");
             }
         }
     }
+
+    /// Reads through the bus for an instruction, wrapping around the end of memory.
+    pub(crate) fn read_memory(&mut self, address: usize) -> u8 {
+        if self.memory_mode == MemoryMode::Strict && address >= self.memory.len() {
+            self.report_fault(CpuError::ReadPastEnd { address: self.program_counter, target: address });
+        }
+
+        let address = address % self.memory.len();
+        self.memory.read(address as u16)
+    }
+
+    pub(crate) fn write_memory(&mut self, address: usize, value: u8) {
+        let address = address % self.memory.len();
+        if self.memory_mode == MemoryMode::Strict && address < self.variant.program_start() as usize {
+            self.report_fault(CpuError::ProtectedWrite { address: self.program_counter, target: address });
+            return;
+        }
+
+        self.memory.write(address as u16, value);
+    }
+
+    //In strict mode an instruction writing `count` bytes from `start` checks them all first, so a
+    //fault leaves memory and I as they were rather than half written
+    fn can_write(&mut self, start: usize, count: usize) -> bool {
+        if self.memory_mode != MemoryMode::Strict {
+            return true;
+        }
+
+        let program_start = self.variant.program_start() as usize;
+        let length = self.memory.len();
+        match (start..start + count).map(|address| address % length).find(|&address| address < program_start) {
+            Some(target) => {
+                self.report_fault(CpuError::ProtectedWrite { address: self.program_counter, target });
+                false
+            },
+            None => true,
+        }
+    }
+
+    fn report_fault(&mut self, fault: CpuError) {
+        if self.fault.is_none() {
+            self.fault = Some(fault);
+        }
+    }
+
+    //Programs go where the variant starts them, anything that does not fit in memory is left off
+    pub fn load_program_bytes(&mut self, program: &[u8]) {
+        let start = self.variant.program_start() as usize;
+        let end = (start + program.len()).min(self.memory.len());
+
+        self.memory[start..end].copy_from_slice(&program[..end - start]);
+        self.program_size = end - start;
+    }
+}
+
+//The registers 5XY2 and 5XY3 go through, from X to Y even when Y is the lower one
+fn register_range(first_part: u8, second_part: u8) -> Box<dyn Iterator<Item = usize>> {
+    let x = (first_part & 0x0F) as usize;
+    let y = ((second_part & 0xF0) >> 4) as usize;
+
+    if x <= y { Box::new(x..=y) } else { Box::new((y..=x).rev()) }
 }
 
 fn encode_to_bcd(value: u8) -> (u8, u8, u8){
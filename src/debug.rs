@@ -12,12 +12,26 @@ use termion::input::Events;
 use termion::input::TermRead;
 use termion::event::Key;
 
-use crate::chip8_cpu::System;
+use chip_8_emulator::chip8_cpu::System;
+use chip_8_emulator::variant::Variant;
 use self::tui::layout::Corner;
 use std::io;
 use std::any::Any;
 use std::io::stdin;
 
+//What the user asked for through the debug ui this update
+#[derive(Default)]
+pub struct DebugInput {
+    pub quit: bool,
+    pub switch_break: bool,
+    pub step: bool,
+    pub step_back: bool,
+    pub save_state: bool,
+    pub load_state: bool,
+    pub select_slot: Option<u8>,
+    pub screenshot: bool,
+}
+
 /**Why does this return have to be so looooooooooooooooong**/
 pub fn setup_debug_ui() -> Terminal<tui::backend::RustboxBackend> {
     let backend = RustboxBackend::new().unwrap();
@@ -28,15 +42,20 @@ pub fn setup_debug_ui() -> Terminal<tui::backend::RustboxBackend> {
 }
 
 pub fn update_and_display_debug_ui(terminal :&mut Terminal<tui::backend::RustboxBackend>,
-                               chip8_system: &System, program_break: bool) -> (bool, bool) {
-    let mut quit = false;
-    let mut change_break = false;
+                               chip8_system: &System, program_break: bool, slot: u8) -> DebugInput {
+    let mut input = DebugInput::default();
     //Poll events
     let key_input = stdin();
     for c in key_input.keys() {
         match c.unwrap() {
-            Key::Char('q') => { quit = true; },
-            Key::Char('b') => { change_break = true; },
+            Key::Char('q') => { input.quit = true; },
+            Key::Char('b') => { input.switch_break = true; },
+            Key::Char('n') => { input.step = true; },
+            Key::Char('p') => { input.step_back = true; },
+            Key::Char('s') => { input.save_state = true; },
+            Key::Char('l') => { input.load_state = true; },
+            Key::Char('c') => { input.screenshot = true; },
+            Key::Char(digit @ '0'..='9') => { input.select_slot = digit.to_digit(10).map(|slot| slot as u8); },
             _ => {}
         }
     }
@@ -79,8 +98,12 @@ pub fn update_and_display_debug_ui(terminal :&mut Terminal<tui::backend::Rustbox
         system_status_vec.push(format!("Current instruction: {}", current_instruction));
         system_status_vec.push(format!("Current instruction description: {}", instruction_description));
 
+        system_status_vec.push(format!("Save state slot: {} (s to save, l to load, 0-9 to pick)", slot));
+        system_status_vec.push(String::from("c to save a screenshot"));
+
         if program_break {
             system_status_vec.push(format!("{}", "Program has paused executing"));
+            system_status_vec.push(format!("{}", "n to step forward, p to step back"));
         } else {
             system_status_vec.push(format!("{}", "Program is executing instructions"));
         }
@@ -95,19 +118,37 @@ pub fn update_and_display_debug_ui(terminal :&mut Terminal<tui::backend::Rustbox
             .render(&mut f, chunks[2]);
     }).expect("Error displaying debug ui");
 
-    (quit, change_break)
+    input
 }
 
 fn get_opcode_description(opcode_first: u8, opcode_second: u8, system: &System) -> String {
     let mut result = String::new();
     let first = opcode_first & 0xF0;
 
+    let opcode = (opcode_first as u16) << 8 | opcode_second as u16;
+    if system.variant.instruction_set().decode(opcode).is_none() {
+        return format!("Not a {} instruction, running it stops the program", system.variant.name());
+    }
+
     match first {
+        0x00 if opcode_first == 0x02 && opcode_second == 0x30 && system.variant == Variant::HiresChip8 => {
+            result = format!("Clear the 64x64 screen");
+        },
+        0x00 if opcode_first == 0x02 && opcode_second == 0xA0 && system.variant == Variant::Chip8X => {
+            result = format!("Cycle the background colour, now {}", system.background);
+        },
         0x00 => {
             match opcode_second {
                 0xE0 => { result = format!("Clear screen"); },
                 0xEE => { result = format!("Return from subroutine, returning to address {}",
                         system.stack[(system.stack_pointer as usize)]);},
+                0xFB => { result = format!("Scroll the screen right 4 pixels"); },
+                0xFC => { result = format!("Scroll the screen left 4 pixels"); },
+                0xFD => { result = format!("Exit the program"); },
+                0xFE => { result = format!("Switch to 64x32 low resolution"); },
+                0xFF => { result = format!("Switch to 128x64 high resolution"); },
+                rows if rows & 0xF0 == 0xC0 => { result = format!("Scroll the screen down {} pixels", rows & 0x0F); },
+                rows if rows & 0xF0 == 0xD0 => { result = format!("Scroll the screen up {} pixels", rows & 0x0F); },
                 _ => { result = format!("Invalid opcode"); }
             }
         },
@@ -115,29 +156,77 @@ fn get_opcode_description(opcode_first: u8, opcode_second: u8, system: &System)
         0x20 => { result = format!("Call subroutine {}{}", (first & 0x0F), opcode_second); },
         0x30 => { result = format!("Skip next instruction if value at register {}, Value {} is equal to constant {}",
                               (opcode_first & 0x0F), system.registers[(first & 0x0F) as usize], opcode_second); },
-        0x40 => { unimplemented!("Skip if not equal (constant)"); },
-        0x50 => { unimplemented!("Skip if equal (to register)"); },
+        0x40 => { result = format!("Skip next instruction if value at register {} is not equal to constant {}",
+                              (opcode_first & 0x0F), opcode_second); },
+        0x50 => {
+            match opcode_second & 0x0F {
+                0x1 if system.variant == Variant::Chip8X => {
+                    result = format!("Add register {} to register {} a nibble at a time", (opcode_second & 0xF0) >> 4, (opcode_first & 0x0F));
+                },
+                0x0 => { result = format!("Skip next instruction if register {} is equal to register {}",
+                                          (opcode_first & 0x0F), (opcode_second & 0xF0) >> 4); },
+                0x2 => { result = format!("Store register {} to register {} into memory starting from index register",
+                                          (opcode_first & 0x0F), (opcode_second & 0xF0) >> 4); },
+                0x3 => { result = format!("Fill register {} to register {} from memory starting from index register",
+                                          (opcode_first & 0x0F), (opcode_second & 0xF0) >> 4); },
+                _ => { result = format!("Invalid opcode"); }
+            }
+        },
         0x60 => { result = format!("Set value in register {} to value {}", (opcode_first & 0x0F), opcode_second); },
         0x70 => { result = format!("Add value in register {}, value {}", (opcode_first & 0x0F), opcode_second); },
-        0x80 => { unimplemented!("Binary ops and maths"); },
-        0x90 => { unimplemented!("Skip if registers not equal"); },
+        0x80 => {
+            let operation = match opcode_second & 0x0F {
+                0x0 => "Set",
+                0x1 => "OR",
+                0x2 => "AND",
+                0x3 => "XOR",
+                0x4 => "Add, with carry in VF,",
+                0x5 => "Subtract, with borrow in VF,",
+                0x6 => "Shift right",
+                0x7 => "Reverse subtract",
+                0xE => "Shift left",
+                _ => "Invalid opcode for",
+            };
+            result = format!("{} register {} with register {}", operation, (opcode_first & 0x0F), (opcode_second & 0xF0) >> 4);
+        },
+        0x90 => { result = format!("Skip next instruction if register {} is not equal to register {}",
+                              (opcode_first & 0x0F), (opcode_second & 0xF0) >> 4); },
         0xA0 => { result = format!("Set index register to {:X}{:X}", (opcode_first & 0x0F), opcode_second); },
-        0xB0 => { unimplemented!("Jump to address plus value in V0"); },
-        0xC0 => { unimplemented!("Set register to random value anded with NN"); },
+        0xB0 if system.variant == Variant::Chip8X => {
+            result = format!("Set the colour of zones from registers {} and {} to the colour in register {}",
+                             (opcode_first & 0x0F), ((opcode_first & 0x0F) + 1) & 0x0F, (opcode_second & 0xF0) >> 4);
+        },
+        0xB0 => { result = format!("Jump to {:X}{:02X} plus the value in V0", (opcode_first & 0x0F), opcode_second); },
+        0xC0 => { result = format!("Set register {} to a random value anded with {}", (opcode_first & 0x0F), opcode_second); },
         0xD0 => { result = format!("Draw starting from X: {} - Y: {}, drawing {} pixels high", (opcode_first & 0x0F),
                           (opcode_second & 0xF0), (opcode_second & 0x0F)); },
-        0xE0 => { unimplemented!("Key operations"); },
+        0xE0 => {
+            match opcode_second {
+                0x9E => { result = format!("Skip next instruction if the key in register {} is held", (opcode_first & 0x0F)); },
+                0xA1 => { result = format!("Skip next instruction if the key in register {} is not held", (opcode_first & 0x0F)); },
+                _ => { result = format!("Invalid opcode"); }
+            }
+        },
         0xF0 => {
             match opcode_second {
+                0x00 if opcode_first == 0xF0 => { result = format!("Set index register to the 16 bit address that follows"); },
+                0x01 => { result = format!("Select bitplanes {}", (opcode_first & 0x03)); },
+                0x02 if opcode_first == 0xF0 => { result = format!("Load the 16 byte audio pattern from index register"); },
+                0xF8 if system.variant == Variant::Chip8X => { result = format!("Output register {} to the port", (opcode_first & 0x0F)); },
+                0xFB if system.variant == Variant::Chip8X => { result = format!("Read the port into register {}", (opcode_first & 0x0F)); },
+                0x3A => { result = format!("Set the audio pitch to the value in register {}", (opcode_first & 0x0F)); },
                 0x07 => { result = format!("Set register {} equal to delay timer value, {}", (opcode_first & 0x0F), system.delay_timer); },
                 0x0A => { result = format!("Halt program until a key is pressed and put key value into register {}", (opcode_first & 0x0F)); },
                 0x15 => { result = format!("Set delay timer to value in register {}", (opcode_first & 0x0F)); },
                 0x18 => { result = format!("Set sound timer to value in register {}", (opcode_first & 0x0F)); },
                 0x1E => { result = format!("Add value in register {} to index register value", (opcode_first & 0x0F)); },
                 0x29 => { result = format!("Set index register to character location which represents the value {}", (opcode_first & 0x0F)); },
+                0x30 => { result = format!("Set index register to the big character location which represents the value {}", (opcode_first & 0x0F)); },
                 0x33 => { result = format!("Set value at index register, +1 and +2, to BCD representation of number at register {}", (opcode_first & 0x0F)) },
                 0x55 => { result = format!("Store register 0 to register {} into memory starting from index register", (opcode_first & 0x0F)); },
                 0x65 => { result = format!("Fill register 0 to register {} into memory starting from index register", (opcode_first & 0x0F)); },
+                0x75 => { result = format!("Save register 0 to register {} into the RPL flags", (opcode_first & 0x0F)); },
+                0x85 => { result = format!("Load register 0 to register {} from the RPL flags", (opcode_first & 0x0F)); },
                 _ => {
                     result = format!("Invalid opcode");
                 }
@@ -0,0 +1,47 @@
+use std::fmt::Write;
+
+use crate::instruction::Instruction;
+
+/// Disassembles a program image loaded at `origin` into source that `assembler::assemble`
+/// turns back into the exact same bytes.
+///
+/// Words that do not decode to an instruction are written out as `dw`, and a trailing odd byte
+/// as `db`, so data mixed in with code survives the round trip.
+pub fn disassemble(program: &[u8], origin: u16) -> String {
+    let mut source = String::new();
+    writeln!(source, "org 0x{:03X}", origin).unwrap();
+
+    let mut offset = 0;
+    while offset < program.len() {
+        let address = origin as usize + offset;
+
+        if offset + 1 < program.len() {
+            let opcode = ((program[offset] as u16) << 8) | program[offset + 1] as u16;
+            let text = match Instruction::decode(opcode) {
+                Some(instruction) => instruction.to_string(),
+                None => format!("dw 0x{:04X}", opcode),
+            };
+
+            writeln!(source, "    {:<24}; {:03X}: {:04X}", text, address, opcode).unwrap();
+            offset += 2;
+        } else {
+            let byte = program[offset];
+            let text = format!("db 0x{:02X}", byte);
+
+            writeln!(source, "    {:<24}; {:03X}: {:02X}", text, address, byte).unwrap();
+            offset += 1;
+        }
+    }
+
+    source
+}
+
+/// Disassembles the single opcode made of `first` and `second`, for one line listings.
+pub fn disassemble_opcode(first: u8, second: u8) -> String {
+    let opcode = ((first as u16) << 8) | second as u16;
+
+    match Instruction::decode(opcode) {
+        Some(instruction) => instruction.to_string(),
+        None => format!("dw 0x{:04X}", opcode),
+    }
+}
@@ -0,0 +1,177 @@
+/// The largest screen of any variant, the SUPER-CHIP and XO-CHIP high resolution mode.
+pub const MAX_WIDTH: usize = 128;
+pub const MAX_HEIGHT: usize = 64;
+
+/// A rectangle of pixels that has changed since the frontend last looked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    fn right(&self) -> usize {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+
+    //Whether the two overlap or sit edge to edge, so that one rectangle covers both well
+    fn touches(&self, other: &Rect) -> bool {
+        self.x <= other.right() && other.x <= self.right() && self.y <= other.bottom() && other.y <= self.bottom()
+    }
+
+    fn union(&self, other: &Rect) -> Rect {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        Rect { x, y, width: self.right().max(other.right()) - x, height: self.bottom().max(other.bottom()) - y }
+    }
+}
+
+/// The screen, one byte a pixel holding a bit for each XO-CHIP plane, so lit pixels of plain
+/// CHIP-8 are 1. Drawing, clearing and scrolling note what they change, so a frontend only has
+/// to redraw the dirty rectangles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Display {
+    //Row after row, MAX_WIDTH pixels apart whatever the current size
+    pixels: Vec<u8>,
+    width: usize,
+    height: usize,
+    dirty: Vec<Rect>,
+    frame: u64,
+}
+
+impl Display {
+    pub fn new(width: usize, height: usize) -> Display {
+        let mut display = Display {
+            pixels: vec![0; MAX_WIDTH * MAX_HEIGHT],
+            width,
+            height,
+            dirty: Vec::new(),
+            frame: 0,
+        };
+        display.mark_all_dirty();
+        display
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Changes the size of the screen, clearing it.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.clear();
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * MAX_WIDTH + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        if self.get(x, y) != value {
+            self.pixels[y * MAX_WIDTH + x] = value;
+            self.mark_dirty(Rect { x, y, width: 1, height: 1 });
+        }
+    }
+
+    /// XORs `planes` into the pixel at `x`, `y`, returning whether any of them were already lit.
+    /// Drawing marks its whole sprite dirty in one go, so this does not.
+    pub fn toggle(&mut self, x: usize, y: usize, planes: u8) -> bool {
+        let pixel = &mut self.pixels[y * MAX_WIDTH + x];
+        let collided = *pixel & planes != 0;
+        *pixel ^= planes;
+        collided
+    }
+
+    pub fn clear(&mut self) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = 0;
+        }
+        self.mark_all_dirty();
+    }
+
+    /// Moves `planes` by `dx`, `dy` pixels, what comes in from the edges is blank.
+    pub fn scroll(&mut self, dx: isize, dy: isize, planes: u8) {
+        let width = self.width as isize;
+        let height = self.height as isize;
+        let previous = self.pixels.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let moved = if from_x >= 0 && from_x < width && from_y >= 0 && from_y < height {
+                    previous[from_y as usize * MAX_WIDTH + from_x as usize]
+                } else {
+                    0
+                };
+
+                let pixel = &mut self.pixels[y as usize * MAX_WIDTH + x as usize];
+                *pixel = (*pixel & !planes) | (moved & planes);
+            }
+        }
+        self.mark_all_dirty();
+    }
+
+    /// The rows of the screen from the top, each `width` pixels long.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let width = self.width;
+        self.pixels.chunks(MAX_WIDTH).take(self.height).map(move |row| &row[..width])
+    }
+
+    /// The screen packed a bit a pixel, lit where any of `planes` is, with the leftmost pixel
+    /// in the top bit. Each row starts on a new byte.
+    pub fn packed(&self, planes: u8) -> Vec<u8> {
+        let row_bytes = self.width.div_ceil(8);
+        let mut packed = vec![0; row_bytes * self.height];
+
+        for (y, row) in self.rows().enumerate() {
+            for (x, &pixel) in row.iter().enumerate() {
+                if pixel & planes != 0 {
+                    packed[y * row_bytes + x / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+
+        packed
+    }
+
+    /// Notes that `rect` has changed, merging it into the last change when they touch.
+    pub fn mark_dirty(&mut self, rect: Rect) {
+        match self.dirty.last_mut() {
+            Some(last) if last.touches(&rect) => { *last = last.union(&rect); },
+            _ => { self.dirty.push(rect); }
+        }
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty.clear();
+        self.dirty.push(Rect { x: 0, y: 0, width: self.width, height: self.height });
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    /// What has changed since the last call, for the frontend to redraw.
+    pub fn take_dirty(&mut self) -> Vec<Rect> {
+        self.dirty.drain(..).collect()
+    }
+
+    /// How many frames have ended since the display was made.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frame += 1;
+    }
+}
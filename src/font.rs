@@ -0,0 +1,246 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The 16 hex digits of the small font, 4x5 pixels in 5 bytes each.
+pub const SMALL_FONT_SIZE: usize = 16 * 5;
+/// The 16 digits of the big font FX30 points at, 8x10 pixels in 10 bytes each.
+pub const BIG_FONT_SIZE: usize = 16 * 10;
+/// Where the font goes unless told otherwise, with the big digits straight after it.
+pub const DEFAULT_FONT_ADDRESS: u16 = 0x000;
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(io::Error),
+    /// Font files hold the small font alone, or the small font followed by the big one.
+    WrongSize(usize),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FontError::Io(ref e) => write!(f, "{}", e),
+            FontError::WrongSize(size) => {
+                write!(f, "font is {} bytes, expected {} or {} with the big digits", size, SMALL_FONT_SIZE,
+                       SMALL_FONT_SIZE + BIG_FONT_SIZE)
+            },
+        }
+    }
+}
+
+impl Error for FontError {}
+
+impl From<io::Error> for FontError {
+    fn from(e: io::Error) -> FontError {
+        FontError::Io(e)
+    }
+}
+
+/// The glyphs FX29 and FX30 point at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Font {
+    pub small: [u8; SMALL_FONT_SIZE],
+    //Fonts without big digits borrow Octo's when a variant needs them
+    pub big: Option<[u8; BIG_FONT_SIZE]>,
+}
+
+impl Default for Font {
+    fn default() -> Font {
+        FontSet::Octo.font()
+    }
+}
+
+impl Font {
+    /// Reads a font from raw glyph bytes, the small font optionally followed by the big one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Font, FontError> {
+        let mut small = [0; SMALL_FONT_SIZE];
+        let mut big = [0; BIG_FONT_SIZE];
+
+        match bytes.len() {
+            SMALL_FONT_SIZE => {
+                small.copy_from_slice(bytes);
+                Ok(Font { small, big: None })
+            },
+            size if size == SMALL_FONT_SIZE + BIG_FONT_SIZE => {
+                small.copy_from_slice(&bytes[..SMALL_FONT_SIZE]);
+                big.copy_from_slice(&bytes[SMALL_FONT_SIZE..]);
+                Ok(Font { small, big: Some(big) })
+            },
+            size => Err(FontError::WrongSize(size)),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Font, FontError> {
+        Font::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn big_or_default(&self) -> [u8; BIG_FONT_SIZE] {
+        self.big.unwrap_or(OCTO_BIG)
+    }
+}
+
+/// The fonts built into well known interpreters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontSet {
+    /// The COSMAC VIP interpreter's, kept in the monitor ROM at 0x8110.
+    Vip,
+    /// The DREAM 6800's three pixel wide digits.
+    Dream6800,
+    /// The ETI-660's, like the DREAM 6800's with a thinner 1 and 4.
+    Eti660,
+    /// SUPER-CHIP 1.1, whose big font only has the digits 0-9.
+    SuperChip,
+    /// Octo's, the usual modern font, with big A-F.
+    Octo,
+}
+
+impl FontSet {
+    /// Looks up a font by name, `vip`, `dream6800`, `eti660`, `schip` or `octo`.
+    pub fn from_name(name: &str) -> Option<FontSet> {
+        match name {
+            "vip" => Some(FontSet::Vip),
+            "dream6800" => Some(FontSet::Dream6800),
+            "eti660" => Some(FontSet::Eti660),
+            "schip" => Some(FontSet::SuperChip),
+            "octo" => Some(FontSet::Octo),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FontSet::Vip => "vip",
+            FontSet::Dream6800 => "dream6800",
+            FontSet::Eti660 => "eti660",
+            FontSet::SuperChip => "schip",
+            FontSet::Octo => "octo",
+        }
+    }
+
+    pub fn font(&self) -> Font {
+        match *self {
+            FontSet::Vip => Font { small: VIP_SMALL, big: None },
+            FontSet::Dream6800 => Font { small: DREAM_6800_SMALL, big: None },
+            FontSet::Eti660 => Font { small: ETI_660_SMALL, big: None },
+            FontSet::SuperChip => Font { small: OCTO_SMALL, big: Some(SUPER_CHIP_BIG) },
+            FontSet::Octo => Font { small: OCTO_SMALL, big: Some(OCTO_BIG) },
+        }
+    }
+}
+
+const VIP_SMALL: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
+];
+
+const DREAM_6800_SMALL: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80  // F
+];
+
+const ETI_660_SMALL: [u8; SMALL_FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80  // F
+];
+
+const OCTO_SMALL: [u8; SMALL_FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
+];
+
+//A-F are left blank, FX30 was only meant for decimal digits
+const SUPER_CHIP_BIG: [u8; BIG_FONT_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // A
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // B
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // C
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // D
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // E
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00  // F
+];
+
+const OCTO_BIG: [u8; BIG_FONT_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0  // F
+];
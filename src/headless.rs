@@ -0,0 +1,45 @@
+use crate::chip8_cpu::{CpuError, System};
+use crate::movie::Movie;
+use crate::timing::Timing;
+
+/// Runs `frames` frames of `system` with no window or debugger attached. With a movie the keypad
+/// follows the movie, and the run stops early if the movie ends first. Returns the number of
+/// frames run, or the first instruction that failed.
+pub fn run(system: &mut System, frames: u64, timing: Timing, movie: Option<&Movie>) -> Result<u64, CpuError> {
+    run_with(system, frames, timing, movie, |_| {})
+}
+
+/// Runs like `run`, calling `after_frame` with the system at the end of each frame, for recording
+/// it.
+pub fn run_with<F: FnMut(&System)>(system: &mut System, frames: u64, timing: Timing, movie: Option<&Movie>,
+                                   mut after_frame: F) -> Result<u64, CpuError> {
+    for frame in 0..frames {
+        match movie {
+            Some(movie) => {
+                if !movie.play_frame(system, frame)? {
+                    return Ok(frame);
+                }
+            },
+            None => { timing.run_frame(system)?; }
+        }
+        after_frame(system);
+    }
+
+    Ok(frames)
+}
+
+/// The screen as text, one line per row. Pixels are `.` when off and `#` when lit, or for XO-CHIP
+/// `#` in the first plane, `+` in the second and `@` in both.
+pub fn screen_to_text(system: &System) -> String {
+    let display = &system.display;
+    let mut text = String::with_capacity((display.width() + 1) * display.height());
+
+    for row in display.rows() {
+        for &pixel in row {
+            text.push(['.', '#', '+', '@'][(pixel & 0x03) as usize]);
+        }
+        text.push('\n');
+    }
+
+    text
+}
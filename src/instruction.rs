@@ -0,0 +1,234 @@
+use std::fmt;
+
+/// A single decoded CHIP-8 or SUPER-CHIP instruction, named after the mnemonics in Cowgod's
+/// technical reference.
+///
+/// Register operands are stored as their index (0x0 - 0xF), addresses as the low 12 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Cls,
+    Ret,
+    Sys(u16),
+    Jp(u16),
+    Call(u16),
+    SeByte(u8, u8),
+    SneByte(u8, u8),
+    SeReg(u8, u8),
+    LdByte(u8, u8),
+    AddByte(u8, u8),
+    LdReg(u8, u8),
+    Or(u8, u8),
+    And(u8, u8),
+    Xor(u8, u8),
+    AddReg(u8, u8),
+    Sub(u8, u8),
+    Shr(u8, u8),
+    Subn(u8, u8),
+    Shl(u8, u8),
+    SneReg(u8, u8),
+    LdI(u16),
+    JpV0(u16),
+    Rnd(u8, u8),
+    Drw(u8, u8, u8),
+    Skp(u8),
+    Sknp(u8),
+    LdVxDt(u8),
+    LdVxK(u8),
+    LdDtVx(u8),
+    LdStVx(u8),
+    AddIVx(u8),
+    LdFVx(u8),
+    LdBVx(u8),
+    LdIVx(u8),
+    LdVxI(u8),
+    //SUPER-CHIP
+    Scd(u8),
+    Scr,
+    Scl,
+    Exit,
+    Low,
+    High,
+    LdHfVx(u8),
+    LdRVx(u8),
+    LdVxR(u8),
+}
+
+impl Instruction {
+    /// Decodes a big endian opcode, returning `None` if it is not a CHIP-8 instruction.
+    pub fn decode(opcode: u16) -> Option<Instruction> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        let instruction = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => Instruction::Cls,
+                0x00EE => Instruction::Ret,
+                0x00FB => Instruction::Scr,
+                0x00FC => Instruction::Scl,
+                0x00FD => Instruction::Exit,
+                0x00FE => Instruction::Low,
+                0x00FF => Instruction::High,
+                _ if opcode & 0xFFF0 == 0x00C0 => Instruction::Scd(n),
+                _ => Instruction::Sys(nnn),
+            },
+            0x1000 => Instruction::Jp(nnn),
+            0x2000 => Instruction::Call(nnn),
+            0x3000 => Instruction::SeByte(x, nn),
+            0x4000 => Instruction::SneByte(x, nn),
+            0x5000 if n == 0 => Instruction::SeReg(x, y),
+            0x6000 => Instruction::LdByte(x, nn),
+            0x7000 => Instruction::AddByte(x, nn),
+            0x8000 => match n {
+                0x0 => Instruction::LdReg(x, y),
+                0x1 => Instruction::Or(x, y),
+                0x2 => Instruction::And(x, y),
+                0x3 => Instruction::Xor(x, y),
+                0x4 => Instruction::AddReg(x, y),
+                0x5 => Instruction::Sub(x, y),
+                0x6 => Instruction::Shr(x, y),
+                0x7 => Instruction::Subn(x, y),
+                0xE => Instruction::Shl(x, y),
+                _ => return None,
+            },
+            0x9000 if n == 0 => Instruction::SneReg(x, y),
+            0xA000 => Instruction::LdI(nnn),
+            0xB000 => Instruction::JpV0(nnn),
+            0xC000 => Instruction::Rnd(x, nn),
+            0xD000 => Instruction::Drw(x, y, n),
+            0xE000 => match nn {
+                0x9E => Instruction::Skp(x),
+                0xA1 => Instruction::Sknp(x),
+                _ => return None,
+            },
+            0xF000 => match nn {
+                0x07 => Instruction::LdVxDt(x),
+                0x0A => Instruction::LdVxK(x),
+                0x15 => Instruction::LdDtVx(x),
+                0x18 => Instruction::LdStVx(x),
+                0x1E => Instruction::AddIVx(x),
+                0x29 => Instruction::LdFVx(x),
+                0x30 => Instruction::LdHfVx(x),
+                0x33 => Instruction::LdBVx(x),
+                0x55 => Instruction::LdIVx(x),
+                0x65 => Instruction::LdVxI(x),
+                0x75 => Instruction::LdRVx(x),
+                0x85 => Instruction::LdVxR(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    /// Encodes the instruction back into its big endian opcode.
+    pub fn encode(&self) -> u16 {
+        fn xy(base: u16, x: u8, y: u8, n: u16) -> u16 {
+            base | ((x as u16 & 0xF) << 8) | ((y as u16 & 0xF) << 4) | n
+        }
+        fn xnn(base: u16, x: u8, nn: u8) -> u16 {
+            base | ((x as u16 & 0xF) << 8) | nn as u16
+        }
+
+        match *self {
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::Sys(nnn) => nnn & 0x0FFF,
+            Instruction::Jp(nnn) => 0x1000 | (nnn & 0x0FFF),
+            Instruction::Call(nnn) => 0x2000 | (nnn & 0x0FFF),
+            Instruction::SeByte(x, nn) => xnn(0x3000, x, nn),
+            Instruction::SneByte(x, nn) => xnn(0x4000, x, nn),
+            Instruction::SeReg(x, y) => xy(0x5000, x, y, 0x0),
+            Instruction::LdByte(x, nn) => xnn(0x6000, x, nn),
+            Instruction::AddByte(x, nn) => xnn(0x7000, x, nn),
+            Instruction::LdReg(x, y) => xy(0x8000, x, y, 0x0),
+            Instruction::Or(x, y) => xy(0x8000, x, y, 0x1),
+            Instruction::And(x, y) => xy(0x8000, x, y, 0x2),
+            Instruction::Xor(x, y) => xy(0x8000, x, y, 0x3),
+            Instruction::AddReg(x, y) => xy(0x8000, x, y, 0x4),
+            Instruction::Sub(x, y) => xy(0x8000, x, y, 0x5),
+            Instruction::Shr(x, y) => xy(0x8000, x, y, 0x6),
+            Instruction::Subn(x, y) => xy(0x8000, x, y, 0x7),
+            Instruction::Shl(x, y) => xy(0x8000, x, y, 0xE),
+            Instruction::SneReg(x, y) => xy(0x9000, x, y, 0x0),
+            Instruction::LdI(nnn) => 0xA000 | (nnn & 0x0FFF),
+            Instruction::JpV0(nnn) => 0xB000 | (nnn & 0x0FFF),
+            Instruction::Rnd(x, nn) => xnn(0xC000, x, nn),
+            Instruction::Drw(x, y, n) => xy(0xD000, x, y, n as u16 & 0xF),
+            Instruction::Skp(x) => xnn(0xE000, x, 0x9E),
+            Instruction::Sknp(x) => xnn(0xE000, x, 0xA1),
+            Instruction::LdVxDt(x) => xnn(0xF000, x, 0x07),
+            Instruction::LdVxK(x) => xnn(0xF000, x, 0x0A),
+            Instruction::LdDtVx(x) => xnn(0xF000, x, 0x15),
+            Instruction::LdStVx(x) => xnn(0xF000, x, 0x18),
+            Instruction::AddIVx(x) => xnn(0xF000, x, 0x1E),
+            Instruction::LdFVx(x) => xnn(0xF000, x, 0x29),
+            Instruction::LdBVx(x) => xnn(0xF000, x, 0x33),
+            Instruction::LdIVx(x) => xnn(0xF000, x, 0x55),
+            Instruction::LdVxI(x) => xnn(0xF000, x, 0x65),
+            Instruction::Scd(n) => 0x00C0 | (n as u16 & 0xF),
+            Instruction::Scr => 0x00FB,
+            Instruction::Scl => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Low => 0x00FE,
+            Instruction::High => 0x00FF,
+            Instruction::LdHfVx(x) => xnn(0xF000, x, 0x30),
+            Instruction::LdRVx(x) => xnn(0xF000, x, 0x75),
+            Instruction::LdVxR(x) => xnn(0xF000, x, 0x85),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Sys(nnn) => write!(f, "SYS 0x{:03X}", nnn),
+            Instruction::Jp(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            Instruction::SeByte(x, nn) => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SneByte(x, nn) => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SeReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::LdByte(x, nn) => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            Instruction::AddByte(x, nn) => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            Instruction::LdReg(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddReg(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::Subn(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SneReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LdI(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            Instruction::JpV0(nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            Instruction::Rnd(x, nn) => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Instruction::Drw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Skp(x) => write!(f, "SKP V{:X}", x),
+            Instruction::Sknp(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LdVxDt(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::LdVxK(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::LdDtVx(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::LdStVx(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIVx(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LdFVx(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LdBVx(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::LdIVx(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LdVxI(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::Scd(n) => write!(f, "SCD {}", n),
+            Instruction::Scr => write!(f, "SCR"),
+            Instruction::Scl => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Low => write!(f, "LOW"),
+            Instruction::High => write!(f, "HIGH"),
+            Instruction::LdHfVx(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::LdRVx(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LdVxR(x) => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::chip8_cpu::System;
+use crate::variant::Variant;
+
+/// Runs an instruction, given the address it was fetched from and its two bytes.
+pub type Execute = fn(&mut System, u16, u8, u8);
+
+/// One instruction of an instruction set, matched by the hex digits of its form.
+pub struct Opcode {
+    /// How the instruction is written, e.g. `8XY4`. Hex digits have to match, X, Y and N are
+    /// operands.
+    pub form: &'static str,
+    pub mask: u16,
+    pub pattern: u16,
+    /// Sets the program counter itself, rather than moving on to the next instruction.
+    pub jumps: bool,
+    pub execute: Execute,
+}
+
+impl Opcode {
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.pattern
+    }
+}
+
+const fn opcode(form: &'static str, execute: Execute) -> Opcode {
+    let digits = form.as_bytes();
+    let mut mask = 0;
+    let mut pattern = 0;
+    let mut index = 0;
+
+    while index < 4 {
+        let nibble = match digits[index] {
+            digit @ b'0'..=b'9' => Some(digit - b'0'),
+            digit @ b'A'..=b'F' => Some(digit - b'A' + 10),
+            _ => None,
+        };
+        mask <<= 4;
+        pattern <<= 4;
+        if let Some(value) = nibble {
+            mask |= 0xF;
+            pattern |= value as u16;
+        }
+        index += 1;
+    }
+
+    Opcode { form, mask, pattern, jumps: false, execute }
+}
+
+const fn jump(form: &'static str, execute: Execute) -> Opcode {
+    Opcode { jumps: true, ..opcode(form, execute) }
+}
+
+/// The instructions a variant understands, as a list of tables searched in order so that an
+/// extension can replace an instruction of the tables after it.
+#[derive(Clone, Copy)]
+pub struct InstructionSet {
+    tables: &'static [&'static [Opcode]],
+}
+
+impl InstructionSet {
+    pub fn for_variant(variant: Variant) -> InstructionSet {
+        let tables: &'static [&'static [Opcode]] = match variant {
+            Variant::Chip8 | Variant::Chip48 => &[CHIP_8],
+            Variant::SuperChip => &[SUPER_CHIP, CHIP_8],
+            Variant::XoChip => &[XO_CHIP, SUPER_CHIP, CHIP_8],
+            Variant::HiresChip8 => &[HIRES_CHIP_8, CHIP_8],
+            Variant::Chip8X => &[CHIP_8X, CHIP_8],
+        };
+
+        InstructionSet { tables }
+    }
+
+    /// The instruction `opcode` is, or None if it is not part of the set.
+    pub fn decode(&self, opcode: u16) -> Option<&'static Opcode> {
+        self.opcodes().find(|entry| entry.matches(opcode))
+    }
+
+    /// Every instruction, in the order they are matched.
+    pub fn opcodes(&self) -> impl Iterator<Item = &'static Opcode> {
+        self.tables.iter().flat_map(|table| table.iter())
+    }
+}
+
+//0NNN comes after the other 0 instructions, anything they do not match calls machine code
+const CHIP_8: &[Opcode] = &[
+    opcode("00E0", |system, _, _, _| system.clear_screen()),
+    opcode("00EE", |system, address, _, _| system.return_from_call(address)),
+    opcode("0NNN", |system, address, first, second| system.call_machine_code(first, second, address)),
+    jump("1NNN", |system, _, first, second| system.jump(first, second)),
+    jump("2NNN", |system, address, first, second| system.call(first, second, address)),
+    opcode("3XNN", |system, _, first, second| system.skip_if_equal(first, second)),
+    opcode("4XNN", |system, _, first, second| system.skip_if_not_equal(first, second)),
+    opcode("5XY0", |system, _, first, second| system.skip_if_registers_equal(first, second)),
+    opcode("6XNN", |system, _, first, second| system.set_register_to(first, second)),
+    opcode("7XNN", |system, _, first, second| system.add_value_to_register(first, second)),
+    opcode("8XY0", |system, _, first, second| system.arithmetic(first, second)),
+    opcode("8XY1", |system, _, first, second| system.arithmetic(first, second)),
+    opcode("8XY2", |system, _, first, second| system.arithmetic(first, second)),
+    opcode("8XY3", |system, _, first, second| system.arithmetic(first, second)),
+    opcode("8XY4", |system, _, first, second| system.arithmetic(first, second)),
+    opcode("8XY5", |system, _, first, second| system.arithmetic(first, second)),
+    opcode("8XY6", |system, _, first, second| system.arithmetic(first, second)),
+    opcode("8XY7", |system, _, first, second| system.arithmetic(first, second)),
+    opcode("8XYE", |system, _, first, second| system.arithmetic(first, second)),
+    opcode("9XY0", |system, _, first, second| system.skip_if_registers_not_equal(first, second)),
+    opcode("ANNN", |system, _, first, second| system.set_index_register(first, second)),
+    jump("BNNN", |system, _, first, second| system.jump_with_offset(first, second)),
+    opcode("CXNN", |system, _, first, second| system.set_register_random(first, second)),
+    opcode("DXYN", |system, _, first, second| system.draw(first, second)),
+    opcode("EX9E", |system, _, first, _| system.skip_if_key(first, true)),
+    opcode("EXA1", |system, _, first, _| system.skip_if_key(first, false)),
+    opcode("FX07", |system, _, first, _| system.load_delay_timer(first)),
+    opcode("FX0A", |system, _, first, _| system.wait_for_key(first)),
+    opcode("FX15", |system, _, first, _| system.set_delay_timer(first)),
+    opcode("FX18", |system, _, first, _| system.set_sound_timer(first)),
+    opcode("FX1E", |system, _, first, _| system.add_to_index_register(first)),
+    opcode("FX29", |system, _, first, _| system.point_at_character(first)),
+    opcode("FX33", |system, _, first, _| system.store_bcd(first)),
+    opcode("FX55", |system, _, first, _| system.reg_store(first & 0x0F)),
+    opcode("FX65", |system, _, first, _| system.reg_load(first & 0x0F)),
+];
+
+const SUPER_CHIP: &[Opcode] = &[
+    opcode("00CN", |system, _, _, second| system.scroll(0, (second & 0x0F) as isize)),
+    opcode("00FB", |system, _, _, _| system.scroll(4, 0)),
+    opcode("00FC", |system, _, _, _| system.scroll(-4, 0)),
+    opcode("00FD", |system, _, _, _| system.exit()),
+    opcode("00FE", |system, _, _, _| system.set_hires(false)),
+    opcode("00FF", |system, _, _, _| system.set_hires(true)),
+    opcode("DXY0", |system, _, first, second| system.draw_big(first, second)),
+    opcode("FX30", |system, _, first, _| system.point_at_big_character(first)),
+    opcode("FX75", |system, _, first, _| system.save_flags(first)),
+    opcode("FX85", |system, _, first, _| system.load_flags(first)),
+];
+
+const XO_CHIP: &[Opcode] = &[
+    opcode("00DN", |system, _, _, second| system.scroll(0, -((second & 0x0F) as isize))),
+    opcode("5XY2", |system, _, first, second| system.save_register_range(first, second)),
+    opcode("5XY3", |system, _, first, second| system.load_register_range(first, second)),
+    opcode("F000", |system, _, _, _| system.load_long_index()),
+    opcode("FN01", |system, _, first, _| system.select_planes(first)),
+    opcode("F002", |system, _, _, _| system.load_audio_pattern()),
+    opcode("FX3A", |system, _, first, _| system.set_pitch(first)),
+];
+
+const HIRES_CHIP_8: &[Opcode] = &[
+    opcode("0230", |system, _, _, _| system.clear_screen()),
+    jump("1NNN", |system, address, first, second| system.hires_jump(first, second, address)),
+];
+
+//BNNN is taken over by the colour zones
+const CHIP_8X: &[Opcode] = &[
+    opcode("02A0", |system, _, _, _| system.cycle_background()),
+    opcode("5XY1", |system, _, first, second| system.add_nibbles(first, second)),
+    opcode("BXYN", |system, _, first, second| system.set_colour_zones(first, second)),
+    opcode("FXF8", |system, _, first, _| system.write_port(first)),
+    opcode("FXFB", |system, _, first, _| system.read_port(first)),
+];
@@ -0,0 +1,29 @@
+pub mod chip8_cpu;
+pub mod display;
+pub mod bus;
+pub mod audio;
+pub mod palette;
+pub mod antiflicker;
+pub mod screenshot;
+pub mod video;
+pub mod quirks;
+pub mod variant;
+pub mod font;
+pub mod instruction_set;
+pub mod rng;
+pub mod savestate;
+pub mod rewind;
+pub mod movie;
+pub mod headless;
+pub mod timing;
+pub mod cdp1802;
+pub mod vip;
+pub mod instruction;
+pub mod disassembler;
+pub mod assembler;
+pub mod octo;
+pub mod trace;
+pub mod tracediff;
+
+#[cfg(test)]
+pub mod tests;
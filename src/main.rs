@@ -2,30 +2,63 @@ extern crate sdl2;
 
 #[cfg(debug_assertions)]
 mod debug;
+mod options;
 use tui::backend::RustboxBackend;
 use rustbox::keyboard::Key;
 
-mod chip8_cpu;
-
-use chip8_cpu::System;
+use chip_8_emulator::antiflicker::{AntiFlicker, FlickerFilter};
+use chip_8_emulator::audio::Voice;
+use chip_8_emulator::bus::{AccessLog, FlatRam, Logged};
+use chip_8_emulator::chip8_cpu::{CpuError, MemoryMode, System, SCREEN_HEIGHT, SCREEN_WIDTH};
+use chip_8_emulator::font::{Font, FontSet, BIG_FONT_SIZE, SMALL_FONT_SIZE};
+use chip_8_emulator::headless;
+use chip_8_emulator::movie::Movie;
+use chip_8_emulator::octo;
+use chip_8_emulator::rewind::RewindBuffer;
+use chip_8_emulator::rng::Rng;
+use chip_8_emulator::savestate;
+use chip_8_emulator::screenshot;
+use chip_8_emulator::trace::{TraceFormat, Tracer};
+use chip_8_emulator::variant::Variant;
+use chip_8_emulator::video::{self, Recorder};
+use chip_8_emulator::vip::Vip;
 
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use sdl2::pixels::Color;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 
-use std::{thread, time};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::BufWriter;
+use std::{fs, process, thread, time};
 use core::borrow::{BorrowMut, Borrow};
 
-#[cfg(test)]
-pub mod tests;
+const AUDIO_FREQUENCY: i32 = 44100;
+const VOLUME: f32 = 0.1;
+//The pitch of the VIP's tone generator
+const VIP_TONE: f64 = 1400.0;
+
+struct Buzzer {
+    voice: Voice,
+    frequency: u32,
+}
+
+impl AudioCallback for Buzzer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.voice.fill(out, self.frequency, VOLUME);
+    }
+}
 
-fn setup_window() -> (Canvas<Window>, sdl2::EventPump){
+fn setup_window() -> (Canvas<Window>, sdl2::EventPump, Option<AudioDevice<Buzzer>>){
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
-    let window = video_subsystem.window("Chip 8 Emulator", 64, 32)
+    let window = video_subsystem.window("Chip 8 Emulator", SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
         .position_centered().build().unwrap();
 
     let mut canvas = window.into_canvas().build().unwrap();
@@ -35,17 +68,374 @@ fn setup_window() -> (Canvas<Window>, sdl2::EventPump){
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
-    (canvas,event_pump)
+    //Running without sound is better than not running at all
+    let desired = AudioSpecDesired { freq: Some(AUDIO_FREQUENCY), channels: Some(1), samples: None };
+    let buzzer = sdl_context.audio().and_then(|audio| {
+        audio.open_playback(None, &desired, |spec| Buzzer { voice: Voice::new(), frequency: spec.freq as u32 })
+    }).ok();
+    if let Some(ref device) = buzzer {
+        device.resume();
+    }
+
+    (canvas,event_pump,buzzer)
+}
+
+const SAVE_SLOTS: u8 = 10;
+const FRAMES_PER_SECOND: usize = 60;
+const REWIND_SECONDS: usize = 10;
+//How many instructions the debugger can step back through
+const DEBUG_HISTORY: usize = 1000;
+
+//The usual layout of the hex keypad on a qwerty keyboard
+//1 2 3 C       1 2 3 4
+//4 5 6 D   ->  Q W E R
+//7 8 9 E       A S D F
+//A 0 B F       Z X C V
+fn keypad_index(keycode: Keycode) -> Option<usize> {
+    let key = match keycode {
+        Keycode::Num1 => 0x1, Keycode::Num2 => 0x2, Keycode::Num3 => 0x3, Keycode::Num4 => 0xC,
+        Keycode::Q => 0x4, Keycode::W => 0x5, Keycode::E => 0x6, Keycode::R => 0xD,
+        Keycode::A => 0x7, Keycode::S => 0x8, Keycode::D => 0x9, Keycode::F => 0xE,
+        Keycode::Z => 0xA, Keycode::X => 0x0, Keycode::C => 0xB, Keycode::V => 0xF,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+fn save_state(chip8_system: &System, program_path: &str, slot: u8) -> String {
+    match chip8_system.save_state_to_file(savestate::slot_path(program_path, slot)) {
+        Ok(()) => format!("Saved state to slot {}", slot),
+        Err(e) => format!("Could not save slot {}: {}", slot, e),
+    }
+}
+
+fn take_screenshot(chip8_system: &System, program_path: &str, scale: usize) -> String {
+    let path = screenshot::screenshot_path(program_path, chip8_system.display.frame());
+    match screenshot::save(chip8_system, &path, scale) {
+        Ok(()) => format!("Saved screenshot {}", path.display()),
+        Err(e) => format!("Could not save screenshot {}: {}", path.display(), e),
+    }
+}
+
+//Starts a recording when there is none, otherwise finishes the one going
+fn toggle_recording(recording: &mut Option<(Recorder<BufWriter<File>>, String)>, chip8_system: &System,
+                    program_path: &str, scale: usize) -> String {
+    match recording.take() {
+        Some((video, path)) => match video.finish() {
+            Ok(_) => format!("Saved recording {}", path),
+            Err(e) => format!("Could not save recording {}: {}", path, e),
+        },
+        None => {
+            let path = video::recording_path(program_path, chip8_system.display.frame()).display().to_string();
+            match Recorder::create(&path, chip8_system, scale) {
+                Ok(video) => {
+                    *recording = Some((video, path.clone()));
+                    format!("Recording to {}", path)
+                },
+                Err(e) => format!("Could not record to {}: {}", path, e),
+            }
+        }
+    }
+}
+
+fn load_state(chip8_system: &mut System, program_path: &str, slot: u8) -> String {
+    match chip8_system.load_state_from_file(savestate::slot_path(program_path, slot)) {
+        Ok(()) => format!("Loaded state from slot {}", slot),
+        Err(e) => format!("Could not load slot {}: {}", slot, e),
+    }
+}
+
+//Writes out the instructions leading up to the error when only the last ones are being traced
+fn report_error(chip8_system: &mut System, error: &CpuError) {
+    eprintln!("error: {}", error);
+    if let Some(ref mut tracer) = chip8_system.tracer {
+        if let Err(e) = tracer.dump() {
+            eprintln!("error: could not write the trace: {}", e);
+        }
+    }
+}
+
+//SUPER-CHIP programs keep their RPL flags between runs, e.g. in `game.ch8.flags`
+fn flags_path(program_path: &str) -> String {
+    format!("{}.flags", program_path)
+}
+
+//Runs the program under the real interpreter on an emulated COSMAC VIP, with only the keypad and
+//the tone, none of the debugging tools of the high level emulation
+fn run_vip(options: &options::Options, interpreter_path: &str, rom_path: &str, program: &[u8]) {
+    let read = |path: &str| fs::read(path).map_err(|e| format!("could not load {}: {}", path, e));
+    let vip = read(interpreter_path).and_then(|interpreter| {
+        let rom = read(rom_path)?;
+        Vip::with_chip8(&rom, &interpreter, program).map_err(|e| e.to_string())
+    });
+    let mut vip = match vip {
+        Ok(vip) => vip,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return;
+        }
+    };
+
+    if options.headless {
+        for _ in 0..options.frames.unwrap_or(0) {
+            vip.run_frame();
+        }
+        print!("{}", vip.screen_to_text());
+        return;
+    }
+
+    let (mut canvas, mut event_pipe, mut buzzer) = setup_window();
+    canvas.window_mut().set_title("Chip 8 Emulator - COSMAC VIP").ok();
+    canvas.set_logical_size(64, 32).ok();
+
+    'running: loop {
+        vip.run_frame();
+
+        for event in event_pipe.poll_iter() {
+            match event {
+                Event::Quit {..} |
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    break 'running;
+                },
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    if let Some(key) = keypad_index(keycode) {
+                        vip.hardware.keypad |= 1 << key;
+                    }
+                },
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if let Some(key) = keypad_index(keycode) {
+                        vip.hardware.keypad &= !(1 << key);
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        canvas.set_draw_color(Color::RGB(0,0,0));
+        canvas.clear();
+        canvas.set_draw_color(Color::RGB(255,255,255));
+        for x in 0..64 {
+            for y in 0..32 {
+                if vip.pixel(x, y) {
+                    canvas.draw_point(sdl2::rect::Point::new(x as i32, y as i32)).ok();
+                }
+            }
+        }
+        canvas.present();
+
+        if let Some(ref mut device) = buzzer {
+            device.lock().voice.update_tone(VIP_TONE, vip.tone());
+        }
+        thread::sleep(time::Duration::from_secs(1) / FRAMES_PER_SECOND as u32);
+    }
+}
+
+//The first watched access since the last look, as a message for the window title
+fn watch_hit(watch_log: &Option<AccessLog>) -> Option<String> {
+    let access = RefCell::borrow_mut(watch_log.as_ref()?).drain(..).next()?;
+    let verb = if access.write { "wrote" } else { "read" };
+    Some(format!("Chip 8 Emulator - watchpoint: {} {:02X} at {:03X}", verb, access.value, access.address))
 }
 
 fn main() {
-    let mut chip8_system: System = System::default();
+    let options = match options::parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+
+    let mut chip8_system = System::new(options.variant);
+    let mut watch_log = None;
+    if !options.watch.is_empty() {
+        let regions = options.watch.iter().map(|&(start, end)| start..=end).collect();
+        let (bus, log) = Logged::watching(FlatRam::new(options.variant.memory_size()), regions);
+        chip8_system.memory = Box::new(bus);
+        watch_log = Some(log);
+    }
+    let program_path = &options.program_path;
 
-    chip8_system.load_program(&String::from("pong.rom"));
-    //chip8_system.load_program(&String::from("test.rom"));
+    //Octo sources are compiled on the fly, anything else is a ROM image
+    let program = if program_path.ends_with(".8o") {
+        match octo::compile_file(program_path) {
+            Ok(program) => program,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return;
+            }
+        }
+    } else {
+        match fs::read(program_path) {
+            Ok(program) => program,
+            Err(e) => {
+                eprintln!("error: could not load {}: {}", program_path, e);
+                return;
+            }
+        }
+    };
+    if let (Some(interpreter_path), Some(rom_path)) = (&options.vip_interpreter, &options.vip_rom) {
+        run_vip(&options, interpreter_path, rom_path, &program);
+        return;
+    }
+    if options.variant.program_start() as usize + program.len() > chip8_system.memory.len() {
+        eprintln!("error: {} is {} bytes, too big for {} bytes of memory{}", program_path, program.len(),
+                  chip8_system.memory.len(), if options.variant == Variant::XoChip { "" } else { ", try --variant xochip" });
+        return;
+    }
+    if let Some(ref font) = options.font {
+        let loaded = match FontSet::from_name(font) {
+            Some(set) => Ok(set.font()),
+            None => Font::load(font),
+        };
+        match loaded {
+            Ok(font) => { chip8_system.font = font; },
+            Err(e) => {
+                eprintln!("error: could not load font {}: {}", font, e);
+                return;
+            }
+        }
+    }
+    //The font lives below the program, where the interpreter used to be
+    let font_size = if options.variant.has_big_font() { SMALL_FONT_SIZE + BIG_FONT_SIZE } else { SMALL_FONT_SIZE };
+    if options.font_address as usize + font_size > options.variant.program_start() as usize {
+        eprintln!("error: a font at {:03X} runs into the program at {:03X}", options.font_address, options.variant.program_start());
+        return;
+    }
+    chip8_system.font_address = options.font_address;
+    chip8_system.load_program_bytes(&program);
     chip8_system.setup_fontset();
+    chip8_system.rng = Rng::new(options.seed);
+    if options.strict {
+        chip8_system.memory_mode = MemoryMode::Strict;
+    }
+    chip8_system.machine_code = options.machine_code;
+    if let Some(quirks) = options.quirks {
+        chip8_system.quirks = quirks;
+    }
+
+    //A movie replaces the keyboard, and brings the settings it was recorded with
+    let mut playback = None;
+    if let Some(ref movie_path) = options.play_movie {
+        let checked = Movie::load(movie_path).and_then(|movie| {
+            movie.check_rom(&program)?;
+            movie.check_variant(chip8_system.variant)?;
+            movie.check_font(&chip8_system.font, chip8_system.font_address)?;
+            Ok(movie)
+        });
+        let movie = match checked {
+            Ok(movie) => movie,
+            Err(e) => {
+                eprintln!("error: could not play {}: {}", movie_path, e);
+                return;
+            }
+        };
+        movie.prepare(&mut chip8_system);
+        playback = Some(movie);
+    }
+    let timing = match playback {
+        Some(ref movie) => movie.timing,
+        None => options.timing,
+    };
+    let mut recording = match options.record_movie {
+        Some(_) => {
+            let movie = Movie::new(&program, chip8_system.variant, chip8_system.quirks, options.seed, timing);
+            Some(movie.with_font(&chip8_system.font, chip8_system.font_address))
+        },
+        None => None,
+    };
+
+    if let Some(ref trace_path) = options.trace_path {
+        let mut tracer = match Tracer::to_file(trace_path) {
+            Ok(tracer) => tracer,
+            Err(e) => {
+                eprintln!("error: could not create trace file {}: {}", trace_path, e);
+                return;
+            }
+        };
+
+        if let Some(ref format) = options.trace_format {
+            match TraceFormat::named(format) {
+                Ok(format) => { tracer = tracer.with_format(format); },
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    return;
+                }
+            }
+        }
+        if let Some((start, end)) = options.trace_range {
+            tracer = tracer.with_address_range(start, end);
+        }
+        if let Some(ref classes) = options.trace_classes {
+            tracer = tracer.with_opcode_classes(classes);
+        }
+        if let Some(count) = options.trace_last {
+            tracer = tracer.with_ring_buffer(count);
+        }
+        chip8_system.tracer = Some(tracer);
+    }
 
-    //let (mut canvas, mut event_pipe) = setup_window();
+    //Flags left over from another run would make a movie play out differently
+    let keep_flags = playback.is_none() && recording.is_none();
+    if keep_flags {
+        if let Ok(flags) = fs::read(flags_path(program_path)) {
+            let count = flags.len().min(chip8_system.rpl_flags.len());
+            chip8_system.rpl_flags[..count].copy_from_slice(&flags[..count]);
+        }
+    }
+    let initial_flags = chip8_system.rpl_flags;
+
+    if options.headless {
+        let frames = match (options.frames, playback.as_ref()) {
+            (Some(frames), _) => frames,
+            (None, Some(movie)) => movie.frames,
+            (None, None) => 0,
+        };
+        let mut recorder = None;
+        if let Some(ref path) = options.record {
+            match Recorder::create(path, &chip8_system, options.screenshot_scale) {
+                Ok(created) => { recorder = Some(created); },
+                Err(e) => {
+                    eprintln!("error: could not record to {}: {}", path, e);
+                    process::exit(1);
+                }
+            }
+        }
+        //The first error stops the recording, the run carries on
+        let mut record_error = None;
+        let result = headless::run_with(&mut chip8_system, frames, timing, playback.as_ref(), |system| {
+            let failed = match recorder {
+                Some(ref mut video) => video.frame(system).err(),
+                None => None,
+            };
+            if failed.is_some() {
+                record_error = failed;
+                recorder = None;
+            }
+        });
+        if let (Some(path), Some(e)) = (options.record.as_ref(), record_error) {
+            eprintln!("error: could not record to {}: {}", path, e);
+        }
+        if let (Some(path), Some(video)) = (options.record.as_ref(), recorder) {
+            if let Err(e) = video.finish() {
+                eprintln!("error: could not record to {}: {}", path, e);
+            }
+        }
+        print!("{}", headless::screen_to_text(&chip8_system));
+        if let Some(ref path) = options.screenshot {
+            if let Err(e) = screenshot::save(&chip8_system, path, options.screenshot_scale) {
+                eprintln!("error: could not save screenshot {}: {}", path, e);
+            }
+        }
+        if let Err(e) = result {
+            report_error(&mut chip8_system, &e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let (mut canvas, mut event_pipe, mut buzzer) = setup_window();
     let mut terminal = None;
 
     if cfg!(debug_assertions) {
@@ -53,14 +443,24 @@ fn main() {
     }
 
     let mut break_program = false;
+    let mut slot: u8 = 0;
+    let mut rewind = RewindBuffer::with_seconds(REWIND_SECONDS, FRAMES_PER_SECOND);
+    let mut history = RewindBuffer::new(DEBUG_HISTORY);
+    let mut rewinding = false;
+    let mut frame: u64 = 0;
+    let mut exit_reported = false;
+    let mut anti_flicker = AntiFlicker::new(options.anti_flicker);
+    let mut video_recording: Option<(Recorder<BufWriter<File>>, String)> = None;
+    //Anything that jumps around in time would leave the movie out of step with the program
+    let movie_active = playback.is_some() || recording.is_some();
 
     'running: loop {
         if cfg!(debug_assertions) {
             let mut t = terminal.as_mut().unwrap();
 
-            let (quit, switch_break) = debug::update_and_display_debug_ui(&mut t, &chip8_system, break_program);
+            let input = debug::update_and_display_debug_ui(&mut t, &chip8_system, break_program, slot);
 
-            if switch_break {
+            if input.switch_break {
                 if break_program == false {
                     break_program = true;
                 } else if break_program == true {
@@ -68,39 +468,192 @@ fn main() {
                 }
             }
 
-            if quit {
+            if let Some(selected) = input.select_slot {
+                slot = selected;
+            }
+            if input.save_state {
+                let message = save_state(&chip8_system, program_path, slot);
+                canvas.window_mut().set_title(&message).ok();
+            }
+            if input.screenshot {
+                let message = take_screenshot(&chip8_system, program_path, options.screenshot_scale);
+                canvas.window_mut().set_title(&message).ok();
+            }
+            if input.load_state && !movie_active {
+                let message = load_state(&mut chip8_system, program_path, slot);
+                canvas.window_mut().set_title(&message).ok();
+            }
+
+            if input.step_back && !movie_active {
+                history.rewind(&mut chip8_system);
+            }
+            if input.step && break_program && !movie_active {
+                history.push(&chip8_system);
+                if let Err(e) = chip8_system.step() {
+                    report_error(&mut chip8_system, &e);
+                    canvas.window_mut().set_title(&format!("Chip 8 Emulator - {}", e)).ok();
+                }
+                if let Some(message) = watch_hit(&watch_log) {
+                    canvas.window_mut().set_title(&message).ok();
+                }
+            }
+
+            if input.quit {
                 break 'running;
             }
         }
 
-        if !break_program {
-            chip8_system.run_op_at(chip8_system.program_counter);
+        if rewinding {
+            rewind.rewind(&mut chip8_system);
+            //The instruction history no longer leads up to where the program is
+            history.clear();
+        } else if !break_program {
+            rewind.push(&chip8_system);
+
+            if let Some(ref movie) = playback {
+                if frame < movie.frames {
+                    chip8_system.set_keypad_bits(movie.keys_at(frame));
+                } else if frame == movie.frames {
+                    canvas.window_mut().set_title("Chip 8 Emulator - movie finished").ok();
+                }
+            }
+            if let Some(ref mut movie) = recording {
+                movie.record(chip8_system.keypad_bits());
+            }
+
+            let mut result = Ok(());
+            let mut instructions = 0;
+            while !timing.frame_done(&chip8_system, instructions) {
+                if cfg!(debug_assertions) {
+                    history.push(&chip8_system);
+                }
+                result = chip8_system.step();
+                if result.is_err() {
+                    break;
+                }
+                instructions += 1;
+            }
+
+            match result {
+                Ok(()) => {
+                    timing.end_frame(&mut chip8_system);
+                    frame += 1;
+                    if let Some((ref mut video, ref path)) = video_recording {
+                        if let Err(e) = video.frame(&chip8_system) {
+                            canvas.window_mut().set_title(&format!("Could not record to {}: {}", path, e)).ok();
+                            video_recording = None;
+                        }
+                    }
+                },
+                Err(e) => {
+                    //Stop on the bad instruction, so it can be looked at in the debugger
+                    report_error(&mut chip8_system, &e);
+                    canvas.window_mut().set_title(&format!("Chip 8 Emulator - {}", e)).ok();
+                    break_program = true;
+                },
+            }
+
+            //The frame is finished first, so pausing does not change how the program runs
+            if let Some(message) = watch_hit(&watch_log) {
+                canvas.window_mut().set_title(&message).ok();
+                break_program = true;
+            }
+
+            if chip8_system.exited && !exit_reported {
+                canvas.window_mut().set_title("Chip 8 Emulator - program exited").ok();
+                exit_reported = true;
+            }
         }
         /**Handle SDL2 events and drawing**/
-        /*
-        canvas.clear();
         for event in event_pipe.poll_iter() {
             match event {
                 Event::Quit {..} |
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     break 'running;
                 },
+                //The window was uncovered or resized, so the whole screen has to be drawn again
+                Event::Window {..} => { chip8_system.display.mark_all_dirty(); },
+                //Holding backspace runs the game backwards
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => { rewinding = !movie_active; },
+                Event::KeyUp { keycode: Some(Keycode::Backspace), .. } => { rewinding = false; },
+                //F5 saves, F9 loads and F6/F7 step through the save slots
+                Event::KeyDown { keycode: Some(Keycode::F5), .. } => {
+                    let message = save_state(&chip8_system, program_path, slot);
+                    canvas.window_mut().set_title(&message).ok();
+                },
+                Event::KeyDown { keycode: Some(Keycode::F9), .. } if !movie_active => {
+                    let message = load_state(&mut chip8_system, program_path, slot);
+                    canvas.window_mut().set_title(&message).ok();
+                },
+                //F10 starts recording a GIF next to the program, and stops it again
+                Event::KeyDown { keycode: Some(Keycode::F10), .. } => {
+                    let message = toggle_recording(&mut video_recording, &chip8_system, program_path, options.screenshot_scale);
+                    canvas.window_mut().set_title(&message).ok();
+                },
+                //F12 saves a screenshot next to the program
+                Event::KeyDown { keycode: Some(Keycode::F12), .. } => {
+                    let message = take_screenshot(&chip8_system, program_path, options.screenshot_scale);
+                    canvas.window_mut().set_title(&message).ok();
+                },
+                Event::KeyDown { keycode: Some(Keycode::F6), .. } => {
+                    slot = (slot + SAVE_SLOTS - 1) % SAVE_SLOTS;
+                    canvas.window_mut().set_title(&format!("Chip 8 Emulator - slot {}", slot)).ok();
+                },
+                Event::KeyDown { keycode: Some(Keycode::F7), .. } => {
+                    slot = (slot + 1) % SAVE_SLOTS;
+                    canvas.window_mut().set_title(&format!("Chip 8 Emulator - slot {}", slot)).ok();
+                },
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    if let Some(key) = keypad_index(keycode) {
+                        chip8_system.key[key] = 1;
+                    }
+                },
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if let Some(key) = keypad_index(keycode) {
+                        chip8_system.key[key] = 0;
+                    }
+                },
                 _ => {}
             }
         }
 
-        for x in 0..64 {
-            for y in 0..32 {
-                if chip8_system.screen[x][y] == 1 {
-                    let point = sdl2::rect::Point::new(x as i32, y as i32);
+        //Frames where nothing was drawn are not presented again, unless a flicker filter is still
+        //changing what is shown. SDL does not keep the back buffer between presents, so a changed
+        //frame is drawn in full rather than just its dirty rectangles
+        let filtering = anti_flicker.filter() != FlickerFilter::Off;
+        if !chip8_system.display.take_dirty().is_empty() || filtering {
+            //Low resolution is drawn at half the size and scaled up by SDL
+            let (width, height) = (chip8_system.screen_width(), chip8_system.screen_height());
+            canvas.set_logical_size(width as u32, height as u32).ok();
+            canvas.set_draw_color(Color::RGB(0,0,0));
+            canvas.clear();
+            //CHIP-8X has a coloured background, so every pixel is drawn
+            for (index, &(r, g, b)) in anti_flicker.frame(&chip8_system).iter().enumerate() {
+                let point = sdl2::rect::Point::new((index % width) as i32, (index / width) as i32);
 
-                    canvas.draw_point(point);
-                }
+                canvas.set_draw_color(Color::RGB(r, g, b));
+                canvas.draw_point(point).ok();
             }
+
+            canvas.present();
         }
 
-        canvas.present();*/
-        thread::sleep(time::Duration::from_secs(1));
+        if let Some(ref mut device) = buzzer {
+            device.lock().voice.update(&chip8_system);
+        }
+        thread::sleep(time::Duration::from_secs(1) / FRAMES_PER_SECOND as u32);
+    }
+
+    if keep_flags && chip8_system.rpl_flags != initial_flags {
+        if let Err(e) = fs::write(flags_path(program_path), &chip8_system.rpl_flags) {
+            eprintln!("error: could not save RPL flags: {}", e);
+        }
+    }
+
+    if let (Some(movie), Some(movie_path)) = (recording, options.record_movie.as_ref()) {
+        if let Err(e) = movie.save(movie_path) {
+            eprintln!("error: could not save movie {}: {}", movie_path, e);
+        }
     }
 
     /*for byte in chip8_system.memory[0x200..0x210].iter() {
@@ -0,0 +1,325 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::chip8_cpu::{CpuError, System};
+use crate::font::{Font, DEFAULT_FONT_ADDRESS};
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::timing::Timing;
+use crate::variant::Variant;
+
+const MAGIC: &str = "chip8-movie";
+/// Bumped whenever the movie format changes, older movies are then refused.
+pub const MOVIE_VERSION: u32 = 4;
+
+#[derive(Debug)]
+pub enum MovieError {
+    Io(io::Error),
+    NotAMovie,
+    UnsupportedVersion(u32),
+    Invalid(usize, String),
+    WrongRom { expected: u64, found: u64 },
+    WrongVariant { expected: Variant, found: Variant },
+    WrongFont { expected: u64, found: u64 },
+    WrongFontAddress { expected: u16, found: u16 },
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MovieError::Io(ref e) => write!(f, "{}", e),
+            MovieError::NotAMovie => write!(f, "not a movie file"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "movie is version {}, this build only plays version {}", version, MOVIE_VERSION)
+            },
+            MovieError::Invalid(line, ref message) => write!(f, "movie is invalid at line {}: {}", line, message),
+            MovieError::WrongRom { expected, found } => {
+                write!(f, "movie was recorded with ROM {:016x}, this ROM is {:016x}", expected, found)
+            },
+            MovieError::WrongVariant { expected, found } => {
+                write!(f, "movie was recorded running {}, this is running {}", expected.name(), found.name())
+            },
+            MovieError::WrongFont { expected, found } => {
+                write!(f, "movie was recorded with font {:016x}, this font is {:016x}", expected, found)
+            },
+            MovieError::WrongFontAddress { expected, found } => {
+                write!(f, "movie was recorded with the font at {:03X}, this has it at {:03X}", expected, found)
+            },
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(e: io::Error) -> MovieError {
+        MovieError::Io(e)
+    }
+}
+
+/// FNV-1a over the program image, enough to tell ROMs apart in a movie header.
+pub fn rom_hash(program: &[u8]) -> u64 {
+    program.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// The same hash over the small font and the big one if the font has it, so movies can tell
+/// fonts apart whether they are built in or loaded from a file.
+pub fn font_hash(font: &Font) -> u64 {
+    let mut glyphs = font.small.to_vec();
+    if let Some(ref big) = font.big {
+        glyphs.extend_from_slice(big);
+    }
+
+    rom_hash(&glyphs)
+}
+
+impl System {
+    /// The keypad as a bit mask, bit N set while key N is held.
+    pub fn keypad_bits(&self) -> u16 {
+        self.key.iter().enumerate().fold(0, |bits, (key, &held)| bits | (((held != 0) as u16) << key))
+    }
+
+    pub fn set_keypad_bits(&mut self, bits: u16) {
+        for (key, held) in self.key.iter_mut().enumerate() {
+            *held = ((bits >> key) & 1) as u8;
+        }
+    }
+}
+
+/// A recording of the keypad, one state per frame, together with everything else a run
+/// depends on. Playing it back from power on reproduces the run exactly.
+///
+/// Movies are plain text so they can be attached to bug reports and read by hand:
+///
+/// ```text
+/// chip8-movie 4
+/// rom 3ad1c5e0a0b0a3f1
+/// variant chip8
+/// font 48a96f4ca3979b75
+/// font-address 000
+/// quirks 3b
+/// seed 2545f491
+/// speed 10
+/// frames 300
+/// 0 0000
+/// 42 0020
+/// ```
+///
+/// Only frames where the keypad changes are listed, as the frame number and the keypad bits. The
+/// font is `font_hash` of the glyphs FX29 and FX30 point at, and the speed is the instructions
+/// run each frame, or `vip` for COSMAC VIP timing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub variant: Variant,
+    pub font_hash: u64,
+    pub font_address: u16,
+    pub quirks: Quirks,
+    pub seed: u32,
+    pub timing: Timing,
+    pub frames: u64,
+    //Frame and keypad bits, for every frame where the keypad changed
+    changes: Vec<(u64, u16)>,
+}
+
+impl Movie {
+    /// A movie starting from power on with the variant's own font in the usual place, which
+    /// `with_font` changes.
+    pub fn new(program: &[u8], variant: Variant, quirks: Quirks, seed: u32, timing: Timing) -> Movie {
+        Movie {
+            rom_hash: rom_hash(program),
+            variant,
+            font_hash: font_hash(&variant.font_set().font()),
+            font_address: DEFAULT_FONT_ADDRESS,
+            quirks,
+            seed,
+            timing,
+            frames: 0,
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn with_font(mut self, font: &Font, font_address: u16) -> Movie {
+        self.font_hash = font_hash(font);
+        self.font_address = font_address;
+        self
+    }
+
+    /// Adds the next frame, which runs with `keys` held.
+    pub fn record(&mut self, keys: u16) {
+        if self.changes.last().map(|&(_, last)| last) != Some(keys) {
+            self.changes.push((self.frames, keys));
+        }
+        self.frames += 1;
+    }
+
+    /// The keypad bits held during `frame`, frames past the end keep the last state.
+    pub fn keys_at(&self, frame: u64) -> u16 {
+        let index = self.changes.iter().take_while(|&&(start, _)| start <= frame).count();
+
+        if index == 0 { 0 } else { self.changes[index - 1].1 }
+    }
+
+    pub fn changes(&self) -> &[(u64, u16)] {
+        &self.changes
+    }
+
+    pub fn check_rom(&self, program: &[u8]) -> Result<(), MovieError> {
+        let found = rom_hash(program);
+        if found != self.rom_hash {
+            return Err(MovieError::WrongRom { expected: self.rom_hash, found });
+        }
+
+        Ok(())
+    }
+
+    /// A movie only plays back on the variant it was recorded on, which has to be picked before
+    /// the program is loaded.
+    pub fn check_variant(&self, variant: Variant) -> Result<(), MovieError> {
+        if variant != self.variant {
+            return Err(MovieError::WrongVariant { expected: self.variant, found: variant });
+        }
+
+        Ok(())
+    }
+
+    /// FX29 and FX30 point into the font, so a movie only plays back with the font it was
+    /// recorded with, at the same address.
+    pub fn check_font(&self, font: &Font, font_address: u16) -> Result<(), MovieError> {
+        let found = font_hash(font);
+        if found != self.font_hash {
+            return Err(MovieError::WrongFont { expected: self.font_hash, found });
+        }
+        if font_address != self.font_address {
+            return Err(MovieError::WrongFontAddress { expected: self.font_address, found: font_address });
+        }
+
+        Ok(())
+    }
+
+    /// Sets up a freshly loaded system the way it was when recording started.
+    pub fn prepare(&self, system: &mut System) {
+        system.quirks = self.quirks;
+        system.rng = Rng::new(self.seed);
+    }
+
+    /// Runs `frame` of the movie on `system`. Returns false once the movie has ended, without
+    /// running anything.
+    pub fn play_frame(&self, system: &mut System, frame: u64) -> Result<bool, CpuError> {
+        if frame >= self.frames {
+            return Ok(false);
+        }
+
+        system.set_keypad_bits(self.keys_at(frame));
+        self.timing.run_frame(system)?;
+        Ok(true)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "{} {}", MAGIC, MOVIE_VERSION).unwrap();
+        writeln!(text, "rom {:016x}", self.rom_hash).unwrap();
+        writeln!(text, "variant {}", self.variant.name()).unwrap();
+        writeln!(text, "font {:016x}", self.font_hash).unwrap();
+        writeln!(text, "font-address {:03x}", self.font_address).unwrap();
+        writeln!(text, "quirks {:02x}", self.quirks.to_bits()).unwrap();
+        writeln!(text, "seed {:08x}", self.seed).unwrap();
+        writeln!(text, "speed {}", self.timing).unwrap();
+        writeln!(text, "frames {}", self.frames).unwrap();
+        for &(frame, keys) in self.changes.iter() {
+            writeln!(text, "{} {:04x}", frame, keys).unwrap();
+        }
+
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, MovieError> {
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim()));
+
+        let mut magic = lines.next().map(|(_, line)| line).unwrap_or("").split_whitespace();
+        if magic.next() != Some(MAGIC) {
+            return Err(MovieError::NotAMovie);
+        }
+        let version = magic.next().and_then(|version| version.parse().ok()).ok_or(MovieError::NotAMovie)?;
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let mut rom_hash = None;
+        let mut variant = None;
+        let mut font = None;
+        let mut font_address = None;
+        let mut quirks = None;
+        let mut seed = None;
+        let mut speed = None;
+        let mut frames = None;
+        let mut changes: Vec<(u64, u16)> = Vec::new();
+
+        for (number, line) in lines {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let name = parts.next().unwrap();
+            let value = parts.next().ok_or_else(|| MovieError::Invalid(number, format!("'{}' has no value", name)))?;
+            if parts.next().is_some() {
+                return Err(MovieError::Invalid(number, format!("unexpected text after '{} {}'", name, value)));
+            }
+            let invalid = || MovieError::Invalid(number, format!("'{}' is not a valid value for {}", value, name));
+
+            match name {
+                "rom" => { rom_hash = Some(u64::from_str_radix(value, 16).map_err(|_| invalid())?); },
+                "variant" => { variant = Some(Variant::from_name(value).ok_or_else(invalid)?); },
+                "font" => { font = Some(u64::from_str_radix(value, 16).map_err(|_| invalid())?); },
+                "font-address" => { font_address = Some(u16::from_str_radix(value, 16).map_err(|_| invalid())?); },
+                "quirks" => { quirks = Some(Quirks::from_bits(u8::from_str_radix(value, 16).map_err(|_| invalid())?)); },
+                "seed" => { seed = Some(u32::from_str_radix(value, 16).map_err(|_| invalid())?); },
+                "speed" => { speed = Some(Timing::parse(value).ok_or_else(invalid)?); },
+                "frames" => { frames = Some(value.parse().map_err(|_| invalid())?); },
+                _ => {
+                    let frame: u64 = name.parse()
+                        .map_err(|_| MovieError::Invalid(number, format!("unknown entry '{}'", name)))?;
+                    let keys = u16::from_str_radix(value, 16).map_err(|_| invalid())?;
+                    if let Some(&(last, _)) = changes.last() {
+                        if frame <= last {
+                            return Err(MovieError::Invalid(number, format!("frame {} comes after frame {}", frame, last)));
+                        }
+                    }
+                    changes.push((frame, keys));
+                }
+            }
+        }
+
+        let missing = |name: &str| MovieError::Invalid(1, format!("the header has no {}", name));
+        let movie = Movie {
+            rom_hash: rom_hash.ok_or_else(|| missing("rom"))?,
+            variant: variant.ok_or_else(|| missing("variant"))?,
+            font_hash: font.ok_or_else(|| missing("font"))?,
+            font_address: font_address.ok_or_else(|| missing("font-address"))?,
+            quirks: quirks.ok_or_else(|| missing("quirks"))?,
+            seed: seed.ok_or_else(|| missing("seed"))?,
+            timing: speed.ok_or_else(|| missing("speed"))?,
+            frames: frames.ok_or_else(|| missing("frames"))?,
+            changes,
+        };
+
+        Ok(movie)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), MovieError> {
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Movie, MovieError> {
+        let text = fs::read_to_string(path)?;
+        Movie::parse(&text)
+    }
+}
@@ -0,0 +1,960 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::assembler::{AssembleError, PROGRAM_START};
+
+//XO-CHIP programs can fill all of 64 KiB
+const MEMORY_SIZE: usize = 0x10000;
+//Stops a macro that expands into itself from running forever
+const MAX_EXPANSIONS: usize = 100_000;
+
+/// Compiles Octo source into a program image that starts at 0x200, ready for
+/// `System::load_program_bytes`.
+///
+/// The supported language covers labels (`: name`), `:const`, `:alias`, `:macro`, `:calc`,
+/// `:byte`, `:org`, `:unpack`, the `:=` style statements, `if ... then`, `if ... begin ... else ... end`,
+/// `loop ... while ... again`, the SUPER-CHIP statements (`hires`, `scroll-down`, `saveflags`...)
+/// and the XO-CHIP ones (`i := long`, `plane`, `audio`, `pitch :=`, `save vx - vy`...).
+/// Like Octo, `:calc` expressions have no operator precedence and are evaluated right to left,
+/// so use parentheses to group.
+pub fn compile(source: &str) -> Result<Vec<u8>, AssembleError> {
+    Compiler::new(source, None).run()
+}
+
+/// Compiles the Octo (.8o) file at `path`.
+pub fn compile_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, AssembleError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|e| AssembleError {
+        file: Some(path.to_path_buf()),
+        line: 0,
+        column: 0,
+        message: format!("could not read file: {}", e),
+    })?;
+
+    Compiler::new(&source, Some(path.to_path_buf())).run()
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Token>,
+}
+
+enum Fixup {
+    //Low 12 bits of the opcode at this offset
+    Address(usize),
+    //The two instructions written by :unpack, with the nibble in front of the address
+    Unpack(usize, u8),
+    //The full 16 bit word after F000
+    Long(usize),
+}
+
+#[derive(Clone, Copy)]
+enum Condition {
+    EqualByte(u8, u8),
+    NotEqualByte(u8, u8),
+    EqualRegister(u8, u8),
+    NotEqualRegister(u8, u8),
+    Key(u8),
+    NotKey(u8),
+}
+
+impl Condition {
+    fn negate(self) -> Condition {
+        match self {
+            Condition::EqualByte(x, nn) => Condition::NotEqualByte(x, nn),
+            Condition::NotEqualByte(x, nn) => Condition::EqualByte(x, nn),
+            Condition::EqualRegister(x, y) => Condition::NotEqualRegister(x, y),
+            Condition::NotEqualRegister(x, y) => Condition::EqualRegister(x, y),
+            Condition::Key(x) => Condition::NotKey(x),
+            Condition::NotKey(x) => Condition::Key(x),
+        }
+    }
+
+    //The skip instruction that jumps over the next one when the condition does not hold
+    fn skip_unless(self) -> u16 {
+        match self {
+            Condition::EqualByte(x, nn) => 0x4000 | (x as u16) << 8 | nn as u16,
+            Condition::NotEqualByte(x, nn) => 0x3000 | (x as u16) << 8 | nn as u16,
+            Condition::EqualRegister(x, y) => 0x9000 | (x as u16) << 8 | (y as u16) << 4,
+            Condition::NotEqualRegister(x, y) => 0x5000 | (x as u16) << 8 | (y as u16) << 4,
+            Condition::Key(x) => 0xE0A1 | (x as u16) << 8,
+            Condition::NotKey(x) => 0xE09E | (x as u16) << 8,
+        }
+    }
+}
+
+enum Operand {
+    Register(u8),
+    Value(i64),
+}
+
+struct Loop {
+    start: usize,
+    breaks: Vec<usize>,
+}
+
+struct Compiler {
+    file: Option<PathBuf>,
+    tokens: VecDeque<Token>,
+    last: Token,
+    rom: Vec<u8>,
+    here: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<(Fixup, Token)>,
+    loops: Vec<Loop>,
+    branches: Vec<usize>,
+    entry_decided: bool,
+    expansions: usize,
+}
+
+impl Compiler {
+    fn new(source: &str, file: Option<PathBuf>) -> Compiler {
+        Compiler {
+            file,
+            tokens: tokenize(source),
+            last: Token { text: String::new(), line: 1, column: 1 },
+            rom: Vec::new(),
+            here: PROGRAM_START as usize,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            loops: Vec::new(),
+            branches: Vec::new(),
+            entry_decided: false,
+            expansions: 0,
+        }
+    }
+
+    fn run(mut self) -> Result<Vec<u8>, AssembleError> {
+        while !self.tokens.is_empty() {
+            self.statement()?;
+        }
+
+        if let Some(start) = self.loops.last().map(|open| open.start) {
+            return Err(self.error_at_end(format!("'loop' at 0x{:03X} has no matching 'again'", start)));
+        }
+        if !self.branches.is_empty() {
+            return Err(self.error_at_end("'begin' has no matching 'end'"));
+        }
+        if !self.labels.contains_key("main") {
+            return Err(self.error_at_end("this program has no 'main' label"));
+        }
+
+        for (fixup, token) in std::mem::take(&mut self.fixups) {
+            let address = match self.labels.get(&token.text) {
+                Some(&address) => address,
+                None => return Err(self.error(&token, format!("undefined name '{}'", token.text))),
+            };
+
+            match fixup {
+                Fixup::Address(at) => {
+                    self.check_address(address, &token)?;
+                    self.rom[at] |= (address >> 8) as u8 & 0x0F;
+                    self.rom[at + 1] = address as u8;
+                },
+                Fixup::Unpack(at, nibble) => {
+                    self.check_address(address, &token)?;
+                    self.rom[at + 1] = (nibble << 4) | ((address >> 8) as u8 & 0x0F);
+                    self.rom[at + 3] = address as u8;
+                },
+                Fixup::Long(at) => {
+                    self.rom[at] = (address >> 8) as u8;
+                    self.rom[at + 1] = address as u8;
+                }
+            }
+        }
+
+        Ok(self.rom)
+    }
+
+    fn statement(&mut self) -> Result<(), AssembleError> {
+        let token = self.next()?;
+
+        if let Some(body) = self.expand_macro(&token)? {
+            for token in body.into_iter().rev() {
+                self.tokens.push_front(token);
+            }
+            return Ok(());
+        }
+
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                if self.labels.contains_key(&name.text) {
+                    return Err(self.error(&name, format!("'{}' is already defined", name.text)));
+                }
+                if !self.entry_decided && name.text != "main" {
+                    self.emit_entry_jump();
+                }
+                self.entry_decided = true;
+                self.labels.insert(name.text, self.here);
+            },
+            ":const" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                let value = self.value()?;
+                self.constants.insert(name.text, value);
+            },
+            ":alias" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register);
+            },
+            ":calc" => {
+                let name = self.next()?;
+                self.check_name(&name)?;
+                let value = self.calc_block()?;
+                self.constants.insert(name.text, value);
+            },
+            ":macro" => { self.define_macro()?; },
+            ":byte" => {
+                let value = if self.peek_is("{") { self.calc_block()? } else { self.value()? };
+                let byte = self.byte_value(value)?;
+                self.emit(&[byte])?;
+            },
+            ":org" => {
+                let value = self.value()?;
+                if value < PROGRAM_START as i64 || value >= MEMORY_SIZE as i64 {
+                    return Err(self.error_last(format!("address 0x{:X} is outside of program memory", value)));
+                }
+                self.emit_entry_jump();
+                self.here = value as usize;
+            },
+            ":unpack" => {
+                let nibble = self.value()?;
+                if !(0..=0xF).contains(&nibble) {
+                    return Err(self.error_last(format!("{} does not fit in 4 bits", nibble)));
+                }
+                let label = self.next()?;
+                let at = self.here - PROGRAM_START as usize;
+                self.emit(&[0x60, (nibble as u8) << 4, 0x61, 0x00])?;
+                self.fixups.push((Fixup::Unpack(at, nibble as u8), label));
+            },
+            ":breakpoint" => { self.next()?; },
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            },
+            ";" | "return" => { self.emit_op(0x00EE)?; },
+            "clear" => { self.emit_op(0x00E0)?; },
+            "hires" => { self.emit_op(0x00FF)?; },
+            "lores" => { self.emit_op(0x00FE)?; },
+            "scroll-down" => {
+                let n = self.value()?;
+                if !(0..=0xF).contains(&n) {
+                    return Err(self.error_last(format!("scroll amount {} does not fit in 4 bits", n)));
+                }
+                self.emit_op(0x00C0 | n as u16)?;
+            },
+            "scroll-up" => {
+                let n = self.value()?;
+                if !(0..=0xF).contains(&n) {
+                    return Err(self.error_last(format!("scroll amount {} does not fit in 4 bits", n)));
+                }
+                self.emit_op(0x00D0 | n as u16)?;
+            },
+            "scroll-right" => { self.emit_op(0x00FB)?; },
+            "scroll-left" => { self.emit_op(0x00FC)?; },
+            "exit" => { self.emit_op(0x00FD)?; },
+            "plane" => {
+                let n = self.value()?;
+                if !(0..=3).contains(&n) {
+                    return Err(self.error_last(format!("plane mask {} must be 0 to 3", n)));
+                }
+                self.emit_op(0xF001 | (n as u16) << 8)?;
+            },
+            "audio" => { self.emit_op(0xF002)?; },
+            "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit_op(0xF03A | (x as u16) << 8)?;
+            },
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit_op(0xF075 | (x as u16) << 8)?;
+            },
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit_op(0xF085 | (x as u16) << 8)?;
+            },
+            "bcd" => {
+                let x = self.register()?;
+                self.emit_op(0xF033 | (x as u16) << 8)?;
+            },
+            "save" | "load" => {
+                let x = self.register()?;
+                //`save vx - vy` is the XO-CHIP range form
+                if self.peek_is("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    let n = if token.text == "save" { 0x2 } else { 0x3 };
+                    self.emit_op(0x5000 | (x as u16) << 8 | (y as u16) << 4 | n)?;
+                } else {
+                    let op = if token.text == "save" { 0xF055 } else { 0xF065 };
+                    self.emit_op(op | (x as u16) << 8)?;
+                }
+            },
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.value()?;
+                if !(0..=0xF).contains(&n) {
+                    return Err(self.error_last(format!("sprite height {} does not fit in 4 bits", n)));
+                }
+                self.emit_op(0xD000 | (x as u16) << 8 | (y as u16) << 4 | n as u16)?;
+            },
+            "jump" => { self.emit_address_op(0x1000)?; },
+            "jump0" => { self.emit_address_op(0xB000)?; },
+            "native" => { self.emit_address_op(0x0000)?; },
+            "loop" => {
+                self.emit_entry_jump();
+                self.loops.push(Loop { start: self.here, breaks: Vec::new() });
+            },
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err(self.error(&token, "'while' is only allowed inside a loop"));
+                }
+                let condition = self.condition()?;
+                self.emit_op(condition.negate().skip_unless())?;
+                let at = self.here - PROGRAM_START as usize;
+                self.emit_op(0x1000)?;
+                self.loops.last_mut().unwrap().breaks.push(at);
+            },
+            "again" => {
+                let open = match self.loops.pop() {
+                    Some(open) => open,
+                    None => return Err(self.error(&token, "'again' without a matching 'loop'")),
+                };
+                self.check_address(open.start, &token)?;
+                self.emit_op(0x1000 | open.start as u16)?;
+                for at in open.breaks {
+                    self.patch_jump(at, &token)?;
+                }
+            },
+            "if" => {
+                let condition = self.condition()?;
+                let keyword = self.next()?;
+                match keyword.text.as_str() {
+                    "then" => { self.emit_op(condition.skip_unless())?; },
+                    "begin" => {
+                        self.emit_op(condition.negate().skip_unless())?;
+                        let at = self.here - PROGRAM_START as usize;
+                        self.emit_op(0x1000)?;
+                        self.branches.push(at);
+                    },
+                    _ => return Err(self.error(&keyword, "expected 'then' or 'begin'")),
+                }
+            },
+            "else" => {
+                let at = match self.branches.pop() {
+                    Some(at) => at,
+                    None => return Err(self.error(&token, "'else' without a matching 'begin'")),
+                };
+                let skip = self.here - PROGRAM_START as usize;
+                self.emit_op(0x1000)?;
+                self.patch_jump(at, &token)?;
+                self.branches.push(skip);
+            },
+            "end" => {
+                match self.branches.pop() {
+                    Some(at) => { self.patch_jump(at, &token)?; },
+                    None => return Err(self.error(&token, "'end' without a matching 'begin'")),
+                }
+            },
+            "i" => { self.index_statement()?; },
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let op = if token.text == "delay" { 0xF015 } else { 0xF018 };
+                self.emit_op(op | (x as u16) << 8)?;
+            },
+            _ => {
+                if let Some(x) = self.register_named(&token.text) {
+                    return self.register_statement(x);
+                }
+                if let Some(value) = self.literal(&token) {
+                    let byte = self.byte_value(value)?;
+                    return self.emit(&[byte]);
+                }
+                if token.text.starts_with(':') {
+                    return Err(self.error(&token, format!("unknown directive '{}'", token.text)));
+                }
+
+                //Anything else names a subroutine, which might only be defined further down
+                self.check_name(&token)?;
+                self.tokens.push_front(token);
+                self.emit_address_op(0x2000)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), AssembleError> {
+        let x16 = (x as u16) << 8;
+        let operator = self.next()?;
+
+        match operator.text.as_str() {
+            ":=" => {
+                let source = self.next()?;
+                match source.text.as_str() {
+                    "delay" => self.emit_op(0xF007 | x16),
+                    "key" => self.emit_op(0xF00A | x16),
+                    "random" => {
+                        let value = self.value()?;
+                        let nn = self.byte_value(value)?;
+                        self.emit_op(0xC000 | x16 | nn as u16)
+                    },
+                    _ => {
+                        self.tokens.push_front(source);
+                        match self.operand()? {
+                            Operand::Register(y) => self.emit_op(0x8000 | x16 | (y as u16) << 4),
+                            Operand::Value(value) => {
+                                let nn = self.byte_value(value)?;
+                                self.emit_op(0x6000 | x16 | nn as u16)
+                            }
+                        }
+                    }
+                }
+            },
+            "+=" | "-=" => {
+                match self.operand()? {
+                    Operand::Register(y) => {
+                        let n = if operator.text == "+=" { 0x4 } else { 0x5 };
+                        self.emit_op(0x8000 | x16 | (y as u16) << 4 | n)
+                    },
+                    Operand::Value(value) => {
+                        let value = if operator.text == "+=" { value } else { -value };
+                        let nn = self.byte_value(value)?;
+                        self.emit_op(0x7000 | x16 | nn as u16)
+                    }
+                }
+            },
+            "|=" | "&=" | "^=" | "=-" | ">>=" | "<<=" => {
+                let y = self.register()?;
+                let n = match operator.text.as_str() {
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    "=-" => 0x7,
+                    ">>=" => 0x6,
+                    _ => 0xE,
+                };
+                self.emit_op(0x8000 | x16 | (y as u16) << 4 | n)
+            },
+            _ => Err(self.error(&operator, format!("unknown operator '{}'", operator.text))),
+        }
+    }
+
+    fn index_statement(&mut self) -> Result<(), AssembleError> {
+        let operator = self.next()?;
+
+        match operator.text.as_str() {
+            ":=" => {
+                if self.peek_is("hex") {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.emit_op(0xF029 | (x as u16) << 8)
+                } else if self.peek_is("bighex") {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.emit_op(0xF030 | (x as u16) << 8)
+                } else if self.peek_is("long") {
+                    self.next()?;
+                    self.emit_long_address()
+                } else {
+                    self.emit_address_op(0xA000)
+                }
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.emit_op(0xF01E | (x as u16) << 8)
+            },
+            _ => Err(self.error(&operator, format!("unknown operator '{}' for i", operator.text))),
+        }
+    }
+
+    //Reads a condition, emitting the vf arithmetic needed for the comparison pseudo-ops
+    fn condition(&mut self) -> Result<Condition, AssembleError> {
+        let x = self.register()?;
+        let operator = self.next()?;
+
+        match operator.text.as_str() {
+            "key" => return Ok(Condition::Key(x)),
+            "-key" => return Ok(Condition::NotKey(x)),
+            _ => {}
+        }
+
+        let operand = self.operand()?;
+        let condition = match (operator.text.as_str(), operand) {
+            ("==", Operand::Register(y)) => Condition::EqualRegister(x, y),
+            ("!=", Operand::Register(y)) => Condition::NotEqualRegister(x, y),
+            ("==", Operand::Value(value)) => Condition::EqualByte(x, self.byte_value(value)?),
+            ("!=", Operand::Value(value)) => Condition::NotEqualByte(x, self.byte_value(value)?),
+            (comparison @ "<", operand) | (comparison @ ">", operand)
+            | (comparison @ "<=", operand) | (comparison @ ">=", operand) => {
+                //vf := a ; vf -= b leaves vf at 1 exactly when a >= b
+                let swap = comparison == ">" || comparison == "<=";
+                match operand {
+                    Operand::Register(y) => {
+                        let (a, b) = if swap { (y, x) } else { (x, y) };
+                        self.emit_op(0x8F00 | (a as u16) << 4)?;
+                        self.emit_op(0x8F05 | (b as u16) << 4)?;
+                    },
+                    Operand::Value(value) => {
+                        let nn = self.byte_value(value)?;
+                        if swap {
+                            self.emit_op(0x6F00 | nn as u16)?;
+                            self.emit_op(0x8F05 | (x as u16) << 4)?;
+                        } else {
+                            //vf := nn ; vf =- vx gives the same flag without a spare register
+                            self.emit_op(0x6F00 | nn as u16)?;
+                            self.emit_op(0x8F07 | (x as u16) << 4)?;
+                        }
+                    }
+                }
+
+                if comparison == "<" || comparison == ">" {
+                    Condition::EqualByte(0xF, 0)
+                } else {
+                    Condition::NotEqualByte(0xF, 0)
+                }
+            },
+            _ => return Err(self.error(&operator, format!("unknown comparison '{}'", operator.text))),
+        };
+
+        Ok(condition)
+    }
+
+    fn define_macro(&mut self) -> Result<(), AssembleError> {
+        let name = self.next()?;
+        self.check_name(&name)?;
+
+        let mut parameters = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.text == "{" {
+                break;
+            }
+            parameters.push(token.text);
+        }
+
+        let body = self.block_body()?;
+        self.macros.insert(name.text, Macro { parameters, body });
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, token: &Token) -> Result<Option<Vec<Token>>, AssembleError> {
+        let count = match self.macros.get(&token.text) {
+            Some(definition) => definition.parameters.len(),
+            None => return Ok(None),
+        };
+
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(self.error(token, "too many macro expansions, is a macro calling itself?"));
+        }
+
+        let mut arguments = Vec::with_capacity(count);
+        for _ in 0..count {
+            arguments.push(self.next()?);
+        }
+
+        let definition = &self.macros[&token.text];
+        let body = definition.body.iter().map(|body_token| {
+            match definition.parameters.iter().position(|parameter| *parameter == body_token.text) {
+                Some(index) => arguments[index].clone(),
+                None => body_token.clone(),
+            }
+        }).collect();
+
+        Ok(Some(body))
+    }
+
+    //Reads the tokens of a { } block, the opening brace already consumed
+    fn block_body(&mut self) -> Result<Vec<Token>, AssembleError> {
+        let mut depth = 1;
+        let mut body = Vec::new();
+
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                },
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    fn calc_block(&mut self) -> Result<i64, AssembleError> {
+        self.expect("{")?;
+        let body = self.block_body()?;
+        let mut position = 0;
+        let value = self.calc_expression(&body, &mut position)?;
+
+        if position < body.len() {
+            return Err(self.error(&body[position], format!("unexpected '{}' in expression", body[position].text)));
+        }
+        Ok(value)
+    }
+
+    //No precedence, right to left: `a op rest`
+    fn calc_expression(&self, body: &[Token], position: &mut usize) -> Result<i64, AssembleError> {
+        let left = self.calc_term(body, position)?;
+
+        let operator = match body.get(*position) {
+            Some(token) if token.text != ")" => token.clone(),
+            _ => return Ok(left),
+        };
+        *position += 1;
+        let right = self.calc_expression(body, position)?;
+
+        let value = match operator.text.as_str() {
+            "+" => left.wrapping_add(right),
+            "-" => left.wrapping_sub(right),
+            "*" => left.wrapping_mul(right),
+            "/" | "%" if right == 0 => return Err(self.error(&operator, "division by zero")),
+            "/" => left / right,
+            "%" => left % right,
+            "&" => left & right,
+            "|" => left | right,
+            "^" => left ^ right,
+            "<<" => left.wrapping_shl(right as u32),
+            ">>" => left.wrapping_shr(right as u32),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => (left < right) as i64,
+            ">" => (left > right) as i64,
+            "<=" => (left <= right) as i64,
+            ">=" => (left >= right) as i64,
+            "==" => (left == right) as i64,
+            "!=" => (left != right) as i64,
+            _ => return Err(self.error(&operator, format!("unknown operator '{}'", operator.text))),
+        };
+
+        Ok(value)
+    }
+
+    fn calc_term(&self, body: &[Token], position: &mut usize) -> Result<i64, AssembleError> {
+        let token = match body.get(*position) {
+            Some(token) => token.clone(),
+            None => return Err(self.error_last("expression ends without a value")),
+        };
+        *position += 1;
+
+        match token.text.as_str() {
+            "(" => {
+                let value = self.calc_expression(body, position)?;
+                match body.get(*position) {
+                    Some(close) if close.text == ")" => {
+                        *position += 1;
+                        Ok(value)
+                    },
+                    _ => Err(self.error(&token, "'(' is never closed")),
+                }
+            },
+            "-" => Ok(self.calc_term(body, position)?.wrapping_neg()),
+            "~" => Ok(!self.calc_term(body, position)?),
+            "!" => Ok((self.calc_term(body, position)? == 0) as i64),
+            "@" => {
+                let offset = self.calc_term(body, position)? - PROGRAM_START as i64;
+                if offset < 0 {
+                    return Ok(0);
+                }
+                Ok(self.rom.get(offset as usize).cloned().unwrap_or(0) as i64)
+            },
+            "HERE" => Ok(self.here as i64),
+            _ => match self.literal(&token).or_else(|| self.labels.get(&token.text).map(|&address| address as i64)) {
+                Some(value) => Ok(value),
+                None => Err(self.error(&token, format!("undefined name '{}'", token.text))),
+            },
+        }
+    }
+
+    fn emit_entry_jump(&mut self) {
+        //The program starts with a jump to main, unless main is the very first thing defined
+        if !self.entry_decided {
+            self.entry_decided = true;
+            let at = self.here - PROGRAM_START as usize;
+            self.write(&[0x10, 0x00]);
+            self.fixups.push((Fixup::Address(at), Token { text: String::from("main"), line: 1, column: 1 }));
+        }
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<(), AssembleError> {
+        self.emit_entry_jump();
+        if self.here + bytes.len() > MEMORY_SIZE {
+            return Err(self.error_last("program does not fit in memory"));
+        }
+        self.write(bytes);
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let offset = self.here - PROGRAM_START as usize;
+        if self.rom.len() < offset + bytes.len() {
+            self.rom.resize(offset + bytes.len(), 0);
+        }
+        self.rom[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.here += bytes.len();
+    }
+
+    fn emit_op(&mut self, opcode: u16) -> Result<(), AssembleError> {
+        self.emit(&[(opcode >> 8) as u8, opcode as u8])
+    }
+
+    //Emits an instruction taking a 12 bit address, deferring labels that are not defined yet
+    fn emit_address_op(&mut self, base: u16) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        let value = if token.text == "{" {
+            self.tokens.push_front(token.clone());
+            Some(self.calc_block()?)
+        } else {
+            self.literal(&token).or_else(|| self.labels.get(&token.text).map(|&address| address as i64))
+        };
+
+        match value {
+            Some(address) => {
+                if !(0..=0xFFF).contains(&address) {
+                    return Err(self.error(&token, format!("address {} does not fit in 12 bits", address)));
+                }
+                self.emit_op(base | address as u16)
+            },
+            None => {
+                self.check_name(&token)?;
+                self.emit_op(base)?;
+                let at = self.here - 2 - PROGRAM_START as usize;
+                self.fixups.push((Fixup::Address(at), token));
+                Ok(())
+            }
+        }
+    }
+
+    //F000 followed by a 16 bit address, for XO-CHIP's `i := long`
+    fn emit_long_address(&mut self) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        let value = if token.text == "{" {
+            self.tokens.push_front(token.clone());
+            Some(self.calc_block()?)
+        } else {
+            self.literal(&token).or_else(|| self.labels.get(&token.text).map(|&address| address as i64))
+        };
+
+        match value {
+            Some(address) => {
+                if !(0..=0xFFFF).contains(&address) {
+                    return Err(self.error(&token, format!("address {} does not fit in 16 bits", address)));
+                }
+                self.emit(&[0xF0, 0x00, (address >> 8) as u8, address as u8])
+            },
+            None => {
+                self.check_name(&token)?;
+                self.emit(&[0xF0, 0x00, 0x00, 0x00])?;
+                let at = self.here - 2 - PROGRAM_START as usize;
+                self.fixups.push((Fixup::Long(at), token));
+                Ok(())
+            }
+        }
+    }
+
+    //Points the jump at `at` here, where `token` closes the block it jumps out of
+    fn patch_jump(&mut self, at: usize, token: &Token) -> Result<(), AssembleError> {
+        let address = self.here;
+        self.check_address(address, token)?;
+        self.rom[at] = 0x10 | (address >> 8) as u8;
+        self.rom[at + 1] = address as u8;
+        Ok(())
+    }
+
+    fn check_address(&self, address: usize, token: &Token) -> Result<(), AssembleError> {
+        if address > 0xFFF {
+            return Err(self.error(token, format!("address 0x{:X} does not fit in 12 bits", address)));
+        }
+        Ok(())
+    }
+
+    fn operand(&mut self) -> Result<Operand, AssembleError> {
+        let token = self.next()?;
+        if let Some(register) = self.register_named(&token.text) {
+            return Ok(Operand::Register(register));
+        }
+        self.tokens.push_front(token);
+        Ok(Operand::Value(self.value()?))
+    }
+
+    fn register(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        match self.register_named(&token.text) {
+            Some(register) => Ok(register),
+            None => Err(self.error(&token, format!("expected a register, found '{}'", token.text))),
+        }
+    }
+
+    fn register_named(&self, name: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(name) {
+            return Some(register);
+        }
+
+        let mut chars = name.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some('v'), Some(digit), None) | (Some('V'), Some(digit), None) => digit.to_digit(16).map(|value| value as u8),
+            _ => None,
+        }
+    }
+
+    //A number, constant or label that is already defined
+    fn value(&mut self) -> Result<i64, AssembleError> {
+        let token = self.next()?;
+        match self.literal(&token).or_else(|| self.labels.get(&token.text).map(|&address| address as i64)) {
+            Some(value) => Ok(value),
+            None => Err(self.error(&token, format!("expected a number or constant, found '{}'", token.text))),
+        }
+    }
+
+    fn literal(&self, token: &Token) -> Option<i64> {
+        match self.constants.get(&token.text) {
+            Some(&value) => Some(value),
+            None => parse_number(&token.text),
+        }
+    }
+
+    fn byte_value(&self, value: i64) -> Result<u8, AssembleError> {
+        if !(-0x80..=0xFF).contains(&value) {
+            return Err(self.error_last(format!("{} does not fit in a byte", value)));
+        }
+        Ok(value as u8)
+    }
+
+    fn check_name(&self, token: &Token) -> Result<(), AssembleError> {
+        let reserved = ["i", ":=", "+=", "-=", "{", "}", ";", "then", "begin", "else", "end", "loop", "again",
+            "while", "if", "key", "-key", "delay", "buzzer", "random", "hex", "bighex", "return", "clear",
+            "hires", "lores", "scroll-down", "scroll-left", "scroll-right", "exit", "saveflags", "loadflags",
+            "long", "plane", "audio", "pitch", "scroll-up"];
+
+        if reserved.contains(&token.text.as_str()) || self.register_named(&token.text).is_some()
+            || parse_number(&token.text).is_some() || token.text.starts_with(':') {
+            return Err(self.error(token, format!("'{}' cannot be used as a name", token.text)));
+        }
+        Ok(())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(self.error(&token, format!("expected '{}', found '{}'", text, token.text)));
+        }
+        Ok(())
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.tokens.front().map(|token| token.text == text).unwrap_or(false)
+    }
+
+    fn next(&mut self) -> Result<Token, AssembleError> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.last = token.clone();
+                Ok(token)
+            },
+            None => Err(self.error_at_end("unexpected end of file")),
+        }
+    }
+
+    fn error<S: Into<String>>(&self, token: &Token, message: S) -> AssembleError {
+        AssembleError {
+            file: self.file.clone(),
+            line: token.line,
+            column: token.column,
+            message: message.into(),
+        }
+    }
+
+    fn error_last<S: Into<String>>(&self, message: S) -> AssembleError {
+        self.error(&self.last, message)
+    }
+
+    fn error_at_end<S: Into<String>>(&self, message: S) -> AssembleError {
+        let end = Token { text: String::new(), line: self.last.line, column: self.last.column + self.last.text.len() };
+        self.error(&end, message)
+    }
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = text.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        text.parse::<i64>().ok()
+    } else {
+        None
+    };
+
+    value.map(|value| if negative { -value } else { value })
+}
+
+//Octo tokens are separated by whitespace, `#` starts a comment
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut position = 0;
+
+        while position < chars.len() {
+            if chars[position] == '#' {
+                break;
+            }
+            if chars[position].is_whitespace() {
+                position += 1;
+                continue;
+            }
+
+            let start = position;
+            if chars[position] == '"' {
+                position += 1;
+                while position < chars.len() && chars[position] != '"' {
+                    position += 1;
+                }
+                position = (position + 1).min(chars.len());
+            } else {
+                while position < chars.len() && !chars[position].is_whitespace() {
+                    position += 1;
+                }
+            }
+
+            tokens.push_back(Token {
+                text: chars[start..position].iter().collect(),
+                line: index + 1,
+                column: start + 1,
+            });
+        }
+    }
+
+    tokens
+}
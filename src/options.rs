@@ -0,0 +1,212 @@
+use std::env;
+
+use chip_8_emulator::antiflicker::FlickerFilter;
+use chip_8_emulator::chip8_cpu::MachineCode;
+use chip_8_emulator::font::DEFAULT_FONT_ADDRESS;
+use chip_8_emulator::quirks::Quirks;
+use chip_8_emulator::rng::DEFAULT_SEED;
+use chip_8_emulator::timing::Timing;
+use chip_8_emulator::variant::Variant;
+
+pub const USAGE: &str = "Usage: chip_8_emulator [options] [program.ch8 | program.8o]
+
+Options:
+    --trace <file>           Log every executed instruction to <file>
+    --trace-format <format>  Trace line format, `native`, `compact` or a template like \"{PC} {OP} {I}\"
+    --trace-range <from-to>  Only trace instructions between two hex addresses, e.g. 200-2FF
+    --trace-ops <classes>    Only trace opcodes with these top nibbles, e.g. D,F
+    --trace-last <n>         Keep the last <n> traced instructions and write them only on error
+    --strict                 Stop on writes below the program, reads past the end of memory and running outside the program
+    --machine-code <mode>    What 0NNN machine code calls do, `error` (default) stops the program and `ignore` skips them
+    --watch <from-to>        Pause when an instruction reads or writes between two hex addresses, e.g. 300-30F
+    --variant <name>         Machine to emulate, `chip8` (default), `chip48`, `schip`, `xochip`, `hires` or `chip8x`
+    --quirks <preset>        Interpreter behaviour to follow, `vip`, `schip` or `xochip`, by default the variant's
+    --font <name | file>     Font to load, `vip`, `dream6800`, `eti660`, `schip`, `octo` or a file of glyphs, by default the variant's
+    --font-address <hex>     Where the font is loaded and FX29 points, 000 by default
+    --speed <n | vip>        Instructions run per frame, 10 by default, or `vip` to time them like a COSMAC VIP
+    --anti-flicker <filter>  Smooth over XOR flicker, `blend[:frames]`, `decay[:persistence]` or `hold` to keep erased pixels until the next draw
+    --seed <n>               Seed for the random number generator
+    --record-movie <file>    Record the keypad into a movie, written to <file> on exit
+    --play-movie <file>      Play back a movie recorded with --record-movie
+    --vip <interpreter>      Run the program on an emulated COSMAC VIP with this dump of its CHIP-8 interpreter
+    --vip-rom <monitor>      The VIP's monitor ROM, needed with --vip
+    --headless               Run without a window or debugger and print the screen at the end
+    --screenshot <file>      With --headless, also save the screen at the end as a PNG, or a PBM if <file> ends in .pbm
+    --record <file>          With --headless, record every frame as a GIF, or raw video if <file> ends in .y4m
+    --screenshot-scale <n>   Pixels a side each CHIP-8 pixel takes in PNG screenshots and recordings, 1 by default.
+                             F12 in the window or c in the debugger saves a screenshot next to the program, F10
+                             starts and stops recording a GIF there
+    --frames <n>             Frames to run with --headless, defaults to the length of the movie";
+
+pub struct Options {
+    pub program_path: String,
+    pub trace_path: Option<String>,
+    pub trace_format: Option<String>,
+    pub trace_range: Option<(u16, u16)>,
+    pub trace_classes: Option<Vec<u8>>,
+    pub trace_last: Option<usize>,
+    pub strict: bool,
+    pub machine_code: MachineCode,
+    pub watch: Vec<(u16, u16)>,
+    pub variant: Variant,
+    //None picks the quirks that go with the variant
+    pub quirks: Option<Quirks>,
+    //A built-in font name or a file
+    pub font: Option<String>,
+    pub font_address: u16,
+    pub timing: Timing,
+    pub anti_flicker: FlickerFilter,
+    pub seed: u32,
+    pub record_movie: Option<String>,
+    pub play_movie: Option<String>,
+    pub vip_interpreter: Option<String>,
+    pub vip_rom: Option<String>,
+    pub headless: bool,
+    pub screenshot: Option<String>,
+    pub record: Option<String>,
+    pub screenshot_scale: usize,
+    pub frames: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            program_path: String::from("pong.rom"),
+            trace_path: None,
+            trace_format: None,
+            trace_range: None,
+            trace_classes: None,
+            trace_last: None,
+            strict: false,
+            machine_code: MachineCode::Error,
+            watch: Vec::new(),
+            variant: Variant::Chip8,
+            quirks: None,
+            font: None,
+            font_address: DEFAULT_FONT_ADDRESS,
+            timing: Timing::default(),
+            anti_flicker: FlickerFilter::Off,
+            seed: DEFAULT_SEED,
+            record_movie: None,
+            play_movie: None,
+            vip_interpreter: None,
+            vip_rom: None,
+            headless: false,
+            screenshot: None,
+            record: None,
+            screenshot_scale: 1,
+            frames: None,
+        }
+    }
+}
+
+pub fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => { options.trace_path = Some(value(&mut args, &arg)?); },
+            "--trace-format" => { options.trace_format = Some(value(&mut args, &arg)?); },
+            "--trace-range" => { options.trace_range = Some(parse_range(&value(&mut args, &arg)?, &arg)?); },
+            "--strict" => { options.strict = true; },
+            "--machine-code" => {
+                let mode = value(&mut args, &arg)?;
+                options.machine_code = match mode.as_str() {
+                    "error" => MachineCode::Error,
+                    "ignore" => MachineCode::Ignore,
+                    _ => { return Err(format!("{} expects `error` or `ignore`, found '{}'", arg, mode)); }
+                };
+            },
+            "--watch" => { options.watch.push(parse_range(&value(&mut args, &arg)?, &arg)?); },
+            "--trace-ops" => {
+                let classes = value(&mut args, &arg)?;
+                let mut parsed = Vec::new();
+                for class in classes.split(',') {
+                    let class = parse_hex(class, &arg)?;
+                    if class > 0xF {
+                        return Err(format!("{} expects single hex digits", arg));
+                    }
+                    parsed.push(class as u8);
+                }
+                options.trace_classes = Some(parsed);
+            },
+            "--trace-last" => { options.trace_last = Some(parse_number(&value(&mut args, &arg)?, &arg)?); },
+            "--variant" => {
+                let name = value(&mut args, &arg)?;
+                options.variant = Variant::from_name(&name).ok_or_else(|| format!("unknown variant '{}'", name))?;
+            },
+            "--quirks" => {
+                let name = value(&mut args, &arg)?;
+                options.quirks = Some(Quirks::preset(&name).ok_or_else(|| format!("unknown quirks preset '{}'", name))?);
+            },
+            "--font" => { options.font = Some(value(&mut args, &arg)?); },
+            "--font-address" => { options.font_address = parse_hex(&value(&mut args, &arg)?, &arg)?; },
+            "--speed" => {
+                let speed = value(&mut args, &arg)?;
+                options.timing = Timing::parse(&speed).ok_or_else(|| format!("{} expects a number or `vip`, found '{}'", arg, speed))?;
+            },
+            "--anti-flicker" => {
+                let filter = value(&mut args, &arg)?;
+                options.anti_flicker = FlickerFilter::parse(&filter).ok_or_else(|| format!("unknown flicker filter '{}'", filter))?;
+            },
+            "--seed" => { options.seed = parse_number(&value(&mut args, &arg)?, &arg)?; },
+            "--record-movie" => { options.record_movie = Some(value(&mut args, &arg)?); },
+            "--play-movie" => { options.play_movie = Some(value(&mut args, &arg)?); },
+            "--vip" => { options.vip_interpreter = Some(value(&mut args, &arg)?); },
+            "--vip-rom" => { options.vip_rom = Some(value(&mut args, &arg)?); },
+            "--headless" => { options.headless = true; },
+            "--screenshot" => { options.screenshot = Some(value(&mut args, &arg)?); },
+            "--record" => { options.record = Some(value(&mut args, &arg)?); },
+            "--screenshot-scale" => { options.screenshot_scale = parse_number(&value(&mut args, &arg)?, &arg)?; },
+            "--frames" => { options.frames = Some(parse_number(&value(&mut args, &arg)?, &arg)?); },
+            "-h" | "--help" => { return Err(String::from(USAGE)); },
+            _ if arg.starts_with("--") => { return Err(format!("unknown option '{}'\n\n{}", arg, USAGE)); },
+            _ => { options.program_path = arg; }
+        }
+    }
+
+    if options.record_movie.is_some() && options.play_movie.is_some() {
+        return Err(String::from("--record-movie and --play-movie cannot be used together"));
+    }
+    if options.vip_interpreter.is_some() != options.vip_rom.is_some() {
+        return Err(String::from("--vip and --vip-rom have to be given together"));
+    }
+    //The VIP runs the real interpreter, none of the high level emulation's tools reach into it
+    if options.vip_interpreter.is_some() && (options.record_movie.is_some() || options.play_movie.is_some() || options.trace_path.is_some()) {
+        return Err(String::from("--vip cannot be used with movies or --trace"));
+    }
+    if (options.screenshot.is_some() || options.record.is_some()) && (!options.headless || options.vip_interpreter.is_some()) {
+        return Err(String::from("--screenshot and --record need --headless, and cannot be used with --vip"));
+    }
+    if options.headless {
+        if options.record_movie.is_some() {
+            return Err(String::from("--record-movie needs the window for input, it cannot be used with --headless"));
+        }
+        if options.frames.is_none() && options.play_movie.is_none() {
+            return Err(String::from("--headless needs --frames or --play-movie to know when to stop"));
+        }
+    }
+
+    Ok(options)
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("{} expects a value", option))
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str, option: &str) -> Result<T, String> {
+    text.trim().parse().map_err(|_| format!("{} expects a number, found '{}'", option, text))
+}
+
+fn parse_range(text: &str, option: &str) -> Result<(u16, u16), String> {
+    let mut parts = text.splitn(2, '-');
+    let start = parse_hex(parts.next().unwrap_or(""), option)?;
+    let end = parse_hex(parts.next().unwrap_or(""), option)?;
+    Ok((start, end))
+}
+
+fn parse_hex(text: &str, option: &str) -> Result<u16, String> {
+    let digits = text.trim().trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("{} expects hex values, found '{}'", option, text))
+}
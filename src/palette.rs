@@ -0,0 +1,29 @@
+use crate::chip8_cpu::{System, COLOUR_ZONE_COLUMNS, COLOUR_ZONE_ROWS};
+use crate::variant::Variant;
+
+//Greys for the four XO-CHIP colours, plain black and white for everything else
+pub const GREYS: [(u8, u8, u8); 4] = [(0, 0, 0), (255, 255, 255), (85, 85, 85), (170, 170, 170)];
+//The VP-590 foreground colours, in the order CHIP-8X numbers them
+pub const CHIP8X_COLOURS: [(u8, u8, u8); 8] = [
+    (0, 0, 0), (255, 0, 0), (0, 0, 255), (255, 0, 255),
+    (0, 255, 0), (255, 255, 0), (0, 255, 255), (255, 255, 255),
+];
+//What 02A0 cycles the background through
+pub const CHIP8X_BACKGROUNDS: [(u8, u8, u8); 4] = [(0, 0, 128), (0, 0, 0), (0, 128, 0), (128, 0, 0)];
+
+/// The colour a frontend should show for the pixel at `x`, `y`.
+pub fn pixel_colour(system: &System, x: usize, y: usize) -> (u8, u8, u8) {
+    let pixel = system.display.get(x, y);
+
+    match system.variant {
+        Variant::Chip8X => {
+            if pixel == 0 {
+                CHIP8X_BACKGROUNDS[(system.background & 0x03) as usize]
+            } else {
+                let zone = system.colour_zones[(x / 8) % COLOUR_ZONE_COLUMNS][y % COLOUR_ZONE_ROWS];
+                CHIP8X_COLOURS[(zone & 0x07) as usize]
+            }
+        },
+        _ => GREYS[(pixel & 0x03) as usize],
+    }
+}
@@ -0,0 +1,85 @@
+/// Behaviours that differ between CHIP-8 interpreters. The default follows the original
+/// COSMAC VIP interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VY into VX, rather than shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// FX55 and FX65 leave I pointing just past the last register copied.
+    pub load_store_increments_i: bool,
+    /// BNNN jumps to XNN plus VX instead of NNN plus V0.
+    pub jump_uses_vx: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub vf_reset: bool,
+    /// Sprites are cut off at the edges of the screen instead of wrapping around.
+    pub clip_sprites: bool,
+    /// DXYN waits for the start of the next frame before drawing.
+    pub display_wait: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+            display_wait: true,
+        }
+    }
+}
+
+impl Quirks {
+    /// SUPER-CHIP 1.1 on the HP48, which most SUPER-CHIP games are written for.
+    pub fn super_chip() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    /// XO-CHIP as Octo runs it.
+    pub fn xo_chip() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+
+    /// Looks up a set of quirks by name, `vip`, `schip` or `xochip`.
+    pub fn preset(name: &str) -> Option<Quirks> {
+        match name {
+            "vip" => Some(Quirks::default()),
+            "schip" => Some(Quirks::super_chip()),
+            "xochip" => Some(Quirks::xo_chip()),
+            _ => None,
+        }
+    }
+
+    /// Packs the quirks into one byte, one bit each in the order the fields are declared.
+    pub fn to_bits(&self) -> u8 {
+        let flags = [self.shift_uses_vy, self.load_store_increments_i, self.jump_uses_vx,
+                     self.vf_reset, self.clip_sprites, self.display_wait];
+
+        flags.iter().enumerate().fold(0, |bits, (index, &set)| bits | ((set as u8) << index))
+    }
+
+    pub fn from_bits(bits: u8) -> Quirks {
+        Quirks {
+            shift_uses_vy: bits & 0x01 != 0,
+            load_store_increments_i: bits & 0x02 != 0,
+            jump_uses_vx: bits & 0x04 != 0,
+            vf_reset: bits & 0x08 != 0,
+            clip_sprites: bits & 0x10 != 0,
+            display_wait: bits & 0x20 != 0,
+        }
+    }
+}
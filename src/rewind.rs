@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+
+use crate::chip8_cpu::System;
+
+/// A history of `System` snapshots to step back through, e.g. one per frame for rewinding
+/// gameplay or one per instruction for stepping back in the debugger.
+///
+/// Only the newest snapshot is kept whole. Every older one is stored as the bytes that differ
+/// from the snapshot after it, which between frames is usually a few dozen bytes.
+pub struct RewindBuffer {
+    capacity: usize,
+    newest: Option<Vec<u8>>,
+    //Oldest first, each one turns the snapshot after it back into its own
+    deltas: VecDeque<Delta>,
+}
+
+impl RewindBuffer {
+    /// Keeps up to `capacity` snapshots, dropping the oldest once it is full.
+    pub fn new(capacity: usize) -> RewindBuffer {
+        RewindBuffer {
+            capacity,
+            newest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Enough room for `seconds` of history at one snapshot per frame.
+    pub fn with_seconds(seconds: usize, frames_per_second: usize) -> RewindBuffer {
+        RewindBuffer::new(seconds * frames_per_second)
+    }
+
+    pub fn len(&self) -> usize {
+        if self.newest.is_some() { self.deltas.len() + 1 } else { 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+    }
+
+    /// Records the current state of `system` as the newest point to go back to.
+    pub fn push(&mut self, system: &System) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let snapshot = system.save_state();
+        if let Some(previous) = self.newest.take() {
+            self.deltas.push_back(Delta::between(&snapshot, &previous));
+        }
+        self.newest = Some(snapshot);
+
+        while self.len() > self.capacity {
+            self.deltas.pop_front();
+        }
+    }
+
+    /// Puts `system` back to the newest recorded point and forgets it, so calling this
+    /// repeatedly walks further back. Returns false once the history is used up.
+    pub fn rewind(&mut self, system: &mut System) -> bool {
+        let snapshot = match self.newest.take() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+
+        //Snapshots only ever come from save_state, a failure here is a bug rather than bad input
+        system.load_state(&snapshot).expect("rewind snapshot could not be restored");
+
+        self.newest = self.deltas.pop_back().map(|delta| delta.apply(&snapshot));
+        true
+    }
+}
+
+//The runs of bytes where one snapshot differs from another
+struct Delta {
+    length: usize,
+    runs: Vec<(usize, Vec<u8>)>,
+}
+
+impl Delta {
+    //What has to change in `from` to turn it into `to`
+    fn between(from: &[u8], to: &[u8]) -> Delta {
+        let mut runs = Vec::new();
+
+        if from.len() != to.len() {
+            runs.push((0, to.to_vec()));
+            return Delta { length: to.len(), runs };
+        }
+
+        let mut index = 0;
+        while index < to.len() {
+            if from[index] == to[index] {
+                index += 1;
+                continue;
+            }
+
+            let start = index;
+            while index < to.len() && from[index] != to[index] {
+                index += 1;
+            }
+            runs.push((start, to[start..index].to_vec()));
+        }
+
+        Delta { length: to.len(), runs }
+    }
+
+    fn apply(&self, from: &[u8]) -> Vec<u8> {
+        let mut result = from.to_vec();
+        result.resize(self.length, 0);
+
+        for &(start, ref bytes) in self.runs.iter() {
+            result[start..start + bytes.len()].copy_from_slice(bytes);
+        }
+
+        result
+    }
+}
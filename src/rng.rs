@@ -0,0 +1,35 @@
+//Anything but zero, xorshift never leaves zero once it is there
+pub const DEFAULT_SEED: u32 = 0x2545_F491;
+
+/// The xorshift generator behind CXNN. Its whole state is a single word, so a run can be
+/// saved, restored and replayed exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Default for Rng {
+    fn default() -> Rng {
+        Rng::new(DEFAULT_SEED)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Rng {
+        Rng { state: if seed == 0 { DEFAULT_SEED } else { seed } }
+    }
+
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        (x >> 24) as u8
+    }
+}
@@ -0,0 +1,260 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::chip8_cpu::{System, COLOUR_ZONE_COLUMNS, COLOUR_ZONE_ROWS, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::variant::Variant;
+
+const MAGIC: &[u8; 4] = b"C8SS";
+/// Bumped whenever the layout of a snapshot changes, older snapshots are then refused.
+pub const SNAPSHOT_VERSION: u16 = 7;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    NotASnapshot,
+    UnsupportedVersion(u16),
+    Truncated,
+    Invalid(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SnapshotError::Io(ref e) => write!(f, "{}", e),
+            SnapshotError::NotASnapshot => write!(f, "not a save state file"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "save state is version {}, this build only loads version {}", version, SNAPSHOT_VERSION)
+            },
+            SnapshotError::Truncated => write!(f, "save state is truncated"),
+            SnapshotError::Invalid(ref message) => write!(f, "save state is invalid: {}", message),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> SnapshotError {
+        SnapshotError::Io(e)
+    }
+}
+
+/// Where save state `slot` lives for the program at `program_path`, e.g. `pong.rom.state1`.
+pub fn slot_path(program_path: &str, slot: u8) -> PathBuf {
+    PathBuf::from(format!("{}.state{}", program_path, slot))
+}
+
+impl System {
+    /// Serialises everything that affects emulation: the variant, memory, registers, I, PC, stack,
+    /// SP, timers, VIP cycles into the frame, keypad, screen with its resolution and planes,
+    /// quirks, the font address, the random number generator, the RPL flags, whether the program has exited, whether
+    /// a draw is waiting for the display, the XO-CHIP audio pattern and pitch, and the CHIP-8X colours and port.
+    ///
+    /// All values are little endian, after a `C8SS` magic and the format version.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        data.push(self.variant.to_id());
+
+        data.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        data.extend_from_slice(self.memory.bytes());
+        data.extend_from_slice(&self.registers);
+        data.extend_from_slice(&self.index_register.to_le_bytes());
+        data.extend_from_slice(&self.program_counter.to_le_bytes());
+        for value in self.stack.iter() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.push(self.stack_pointer);
+        data.push(self.delay_timer);
+        data.push(self.sound_timer);
+        data.extend_from_slice(&self.cycles.to_le_bytes());
+        data.extend_from_slice(&self.key);
+
+        data.push(SCREEN_WIDTH as u8);
+        data.push(SCREEN_HEIGHT as u8);
+        for x in 0..SCREEN_WIDTH {
+            for y in 0..SCREEN_HEIGHT {
+                data.push(self.display.get(x, y));
+            }
+        }
+        data.push(self.hires as u8);
+        data.push(self.planes);
+
+        data.push(self.quirks.to_bits());
+        data.extend_from_slice(&self.font_address.to_le_bytes());
+        data.extend_from_slice(&self.rng.state().to_le_bytes());
+        data.extend_from_slice(&self.rpl_flags);
+        data.push(self.exited as u8);
+        data.push(self.waiting_for_display as u8);
+        data.extend_from_slice(&self.audio_pattern);
+        data.push(self.pitch);
+        for column in self.colour_zones.iter() {
+            data.extend_from_slice(column);
+        }
+        data.push(self.background);
+        data.push(self.port_output);
+        data.push(self.port_input);
+
+        data
+    }
+
+    /// Restores a snapshot made by `save_state`. Nothing is changed unless the whole snapshot
+    /// reads back correctly.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SnapshotError> {
+        let mut reader = Reader { data, position: 0 };
+
+        if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        reader.position = MAGIC.len();
+        let version = reader.u16()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let id = reader.u8()?;
+        let variant = Variant::from_id(id).ok_or_else(|| SnapshotError::Invalid(format!("unknown variant {}", id)))?;
+        if variant != self.variant {
+            return Err(SnapshotError::Invalid(format!("save state is for {}, this is {}", variant.name(), self.variant.name())));
+        }
+
+        let memory_size = reader.u32()? as usize;
+        if memory_size != self.memory.len() {
+            return Err(SnapshotError::Invalid(format!("memory is {} bytes, expected {}", memory_size, self.memory.len())));
+        }
+        let memory = reader.bytes(memory_size)?.to_vec();
+
+        let mut registers = [0u8; 16];
+        registers.copy_from_slice(reader.bytes(16)?);
+        let index_register = reader.u16()?;
+        let program_counter = reader.u16()?;
+        let mut stack = [0u16; 16];
+        for value in stack.iter_mut() {
+            *value = reader.u16()?;
+        }
+        let stack_pointer = reader.u8()?;
+        if stack_pointer as usize > stack.len() {
+            return Err(SnapshotError::Invalid(format!("stack pointer {} is past the end of the stack", stack_pointer)));
+        }
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let cycles = reader.u32()?;
+        let mut key = [0u8; 16];
+        key.copy_from_slice(reader.bytes(16)?);
+
+        let width = reader.u8()? as usize;
+        let height = reader.u8()? as usize;
+        if width != SCREEN_WIDTH || height != SCREEN_HEIGHT {
+            return Err(SnapshotError::Invalid(format!("screen is {}x{}, expected {}x{}", width, height,
+                                                      SCREEN_WIDTH, SCREEN_HEIGHT)));
+        }
+        let mut screen = [[0u8; SCREEN_HEIGHT]; SCREEN_WIDTH];
+        for column in screen.iter_mut() {
+            column.copy_from_slice(reader.bytes(height)?);
+        }
+        let hires = reader.u8()? != 0;
+        let planes = reader.u8()?;
+
+        let quirks = Quirks::from_bits(reader.u8()?);
+        let font_address = reader.u16()?;
+        let rng = Rng::new(reader.u32()?);
+        let mut rpl_flags = [0u8; 16];
+        rpl_flags.copy_from_slice(reader.bytes(16)?);
+        let exited = reader.u8()? != 0;
+        let waiting_for_display = reader.u8()? != 0;
+        let mut audio_pattern = [0u8; 16];
+        audio_pattern.copy_from_slice(reader.bytes(16)?);
+        let pitch = reader.u8()?;
+        let mut colour_zones = [[0u8; COLOUR_ZONE_ROWS]; COLOUR_ZONE_COLUMNS];
+        for column in colour_zones.iter_mut() {
+            column.copy_from_slice(reader.bytes(COLOUR_ZONE_ROWS)?);
+        }
+        let background = reader.u8()?;
+        let port_output = reader.u8()?;
+        let port_input = reader.u8()?;
+
+        if reader.position != data.len() {
+            return Err(SnapshotError::Invalid(format!("{} unexpected bytes at the end", data.len() - reader.position)));
+        }
+
+        self.memory.bytes_mut().copy_from_slice(&memory);
+        self.registers = registers;
+        self.index_register = index_register;
+        self.program_counter = program_counter;
+        self.stack = stack;
+        self.stack_pointer = stack_pointer;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.cycles = cycles;
+        self.key = key;
+        self.hires = hires;
+        let (width, height) = self.variant.screen_size(hires);
+        self.display.resize(width, height);
+        for (x, column) in screen.iter().enumerate() {
+            for (y, &pixel) in column.iter().enumerate() {
+                self.display.set(x, y, pixel);
+            }
+        }
+        self.planes = planes;
+        self.quirks = quirks;
+        self.font_address = font_address;
+        self.rng = rng;
+        self.rpl_flags = rpl_flags;
+        self.exited = exited;
+        self.waiting_for_display = waiting_for_display;
+        self.audio_pattern = audio_pattern;
+        self.pitch = pitch;
+        self.colour_zones = colour_zones;
+        self.background = background;
+        self.port_output = port_output;
+        self.port_input = port_input;
+
+        Ok(())
+    }
+
+    pub fn save_state_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        fs::write(path, self.save_state())?;
+        Ok(())
+    }
+
+    pub fn load_state_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SnapshotError> {
+        let data = fs::read(path)?;
+        self.load_state(&data)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.position + count > self.data.len() {
+            return Err(SnapshotError::Truncated);
+        }
+        let bytes = &self.data[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::antiflicker::Colour;
+use crate::chip8_cpu::System;
+use crate::display::Display;
+use crate::palette;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+//Stored deflate blocks hold at most this much each
+const STORED_BLOCK_SIZE: usize = 0xFFFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Colour, through the palette the frontend shows, and scaled up.
+    Png,
+    /// Plain black and white PBM, lit wherever any plane is.
+    Pbm,
+}
+
+impl ImageFormat {
+    /// PBM for paths ending in `.pbm`, PNG for anything else.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> ImageFormat {
+        match path.as_ref().extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("pbm") => ImageFormat::Pbm,
+            _ => ImageFormat::Png,
+        }
+    }
+}
+
+/// Where the frontends put a screenshot taken on `frame`, next to the program.
+pub fn screenshot_path(program_path: &str, frame: u64) -> PathBuf {
+    PathBuf::from(format!("{}.{}.png", program_path, frame))
+}
+
+/// Writes the screen of `system` to `path`, in the format its extension asks for. PNGs are
+/// scaled up `scale` times.
+pub fn save<P: AsRef<Path>>(system: &System, path: P, scale: usize) -> io::Result<()> {
+    let image = match ImageFormat::from_path(&path) {
+        ImageFormat::Png => png(system, scale),
+        ImageFormat::Pbm => pbm(&system.display),
+    };
+    fs::write(path, image)
+}
+
+/// The screen as a plain (P1) PBM, 1 for lit pixels.
+pub fn pbm(display: &Display) -> Vec<u8> {
+    let mut text = format!("P1\n{} {}\n", display.width(), display.height());
+
+    for row in display.rows() {
+        let pixels: Vec<&str> = row.iter().map(|&pixel| if pixel != 0 { "1" } else { "0" }).collect();
+        text.push_str(&pixels.join(" "));
+        text.push('\n');
+    }
+
+    text.into_bytes()
+}
+
+/// The screen as an indexed colour PNG, each pixel `scale` pixels square.
+pub fn png(system: &System, scale: usize) -> Vec<u8> {
+    let scale = scale.max(1);
+    let (width, height) = (system.screen_width(), system.screen_height());
+    let mut colours: Vec<Colour> = Vec::new();
+    let mut indices = Vec::with_capacity(width * height * scale * scale);
+
+    for y in 0..height {
+        let mut row = Vec::with_capacity(width * scale);
+        for x in 0..width {
+            let colour = palette::pixel_colour(system, x, y);
+            let index = match colours.iter().position(|&known| known == colour) {
+                Some(index) => index,
+                None => {
+                    colours.push(colour);
+                    colours.len() - 1
+                }
+            };
+            row.resize(row.len() + scale, index as u8);
+        }
+        for _ in 0..scale {
+            indices.extend_from_slice(&row);
+        }
+    }
+
+    encode_png(width * scale, height * scale, &colours, &indices)
+}
+
+/// Encodes an 8 bit indexed colour PNG of `indices` into `colours`, a row after another. The image
+/// data is stored rather than compressed, which keeps the encoder simple and screenshots small
+/// enough.
+pub fn encode_png(width: usize, height: usize, colours: &[Colour], indices: &[u8]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    //8 bits a pixel, indexed colour, deflate, adaptive filtering and no interlacing
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+
+    let palette: Vec<u8> = colours.iter().flat_map(|&(r, g, b)| vec![r, g, b]).collect();
+
+    //Every row starts with its filter type, 0 for none
+    let mut scanlines = Vec::with_capacity((width + 1) * height);
+    for row in indices.chunks(width.max(1)).take(height) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"PLTE", &palette);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+//A zlib stream of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = if data.is_empty() { vec![data] } else { data.chunks(STORED_BLOCK_SIZE).collect() };
+
+    for (number, block) in blocks.iter().enumerate() {
+        let last = number == blocks.len() - 1;
+        stream.push(last as u8);
+        let length = block.len() as u16;
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
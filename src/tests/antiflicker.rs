@@ -0,0 +1,88 @@
+use crate::antiflicker::{AntiFlicker, FlickerFilter};
+use crate::chip8_cpu::System;
+
+const WHITE: (u8, u8, u8) = (255, 255, 255);
+const BLACK: (u8, u8, u8) = (0, 0, 0);
+
+#[test]
+fn test_parse_filters() {
+    assert_eq!(FlickerFilter::parse("off"), Some(FlickerFilter::Off));
+    assert_eq!(FlickerFilter::parse("hold"), Some(FlickerFilter::Hold));
+    assert_eq!(FlickerFilter::parse("blend"), Some(FlickerFilter::Blend(3)));
+    assert_eq!(FlickerFilter::parse("blend:2"), Some(FlickerFilter::Blend(2)));
+    assert_eq!(FlickerFilter::parse("decay:0.25"), Some(FlickerFilter::Decay(0.25)));
+    assert_eq!(FlickerFilter::parse("blend:0"), None);
+    assert_eq!(FlickerFilter::parse("decay:1.5"), None);
+    assert_eq!(FlickerFilter::parse("hold:3"), None);
+
+    for filter in [FlickerFilter::Blend(4), FlickerFilter::Decay(0.5), FlickerFilter::Hold].iter() {
+        assert_eq!(FlickerFilter::parse(&filter.to_string()), Some(*filter));
+    }
+}
+
+#[test]
+fn test_off_shows_the_screen() {
+    let mut chip = System::default();
+    let mut filter = AntiFlicker::new(FlickerFilter::Off);
+    chip.display.set(1, 0, 1);
+
+    let colours = filter.frame(&chip);
+    assert_eq!(colours.len(), 64 * 32);
+    assert_eq!(colours[..2], [BLACK, WHITE]);
+}
+
+#[test]
+fn test_blend_averages_frames() {
+    let mut chip = System::default();
+    let mut filter = AntiFlicker::new(FlickerFilter::Blend(2));
+
+    chip.display.set(0, 0, 1);
+    assert_eq!(filter.frame(&chip)[0], WHITE);
+    chip.display.set(0, 0, 0);
+    assert_eq!(filter.frame(&chip)[0], (127, 127, 127));
+    assert_eq!(filter.frame(&chip)[0], BLACK);
+}
+
+#[test]
+fn test_decay_fades_out() {
+    let mut chip = System::default();
+    let mut filter = AntiFlicker::new(FlickerFilter::Decay(0.5));
+
+    chip.display.set(0, 0, 1);
+    assert_eq!(filter.frame(&chip)[0], WHITE);
+    chip.display.set(0, 0, 0);
+    assert_eq!(filter.frame(&chip)[0], (128, 128, 128));
+    assert_eq!(filter.frame(&chip)[0], (64, 64, 64));
+    //Lighting up again is immediate
+    chip.display.set(0, 0, 1);
+    assert_eq!(filter.frame(&chip)[0], WHITE);
+}
+
+#[test]
+fn test_hold_waits_for_the_next_draw() {
+    let mut chip = System::default();
+    let mut filter = AntiFlicker::new(FlickerFilter::Hold);
+
+    chip.display.set(0, 0, 1);
+    filter.frame(&chip);
+    //The sprite is erased, the frame is not shown
+    chip.display.set(0, 0, 0);
+    assert_eq!(filter.frame(&chip)[0], WHITE);
+    assert_eq!(filter.frame(&chip)[0], WHITE);
+    //And drawn again one pixel over
+    chip.display.set(1, 0, 1);
+    assert_eq!(filter.frame(&chip)[..2], [BLACK, WHITE]);
+}
+
+#[test]
+fn test_resolution_change_starts_again() {
+    let mut chip = System::default();
+    let mut filter = AntiFlicker::new(FlickerFilter::Blend(3));
+
+    chip.display.set(0, 0, 1);
+    filter.frame(&chip);
+    chip.display.resize(128, 64);
+    let colours = filter.frame(&chip);
+    assert_eq!(colours.len(), 128 * 64);
+    assert_eq!(colours[0], BLACK);
+}
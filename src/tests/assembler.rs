@@ -0,0 +1,152 @@
+use crate::assembler::{assemble, assemble_file};
+use crate::disassembler::disassemble;
+use crate::chip8_cpu::System;
+
+use std::env;
+use std::fs;
+
+#[test]
+fn test_assemble_instructions() {
+    let program = assemble("
+        CLS
+        LD VA, 0x02
+        LD I, 0x2EA
+        DRW V0, V1, 5
+        LD [I], V3
+        LD V3, [I]
+        JP V0, $300
+        SHR V4
+    ").unwrap();
+
+    assert_eq!(program, vec![0x00, 0xE0, 0x6A, 0x02, 0xA2, 0xEA, 0xD0, 0x15,
+                             0xF3, 0x55, 0xF3, 0x65, 0xB3, 0x00, 0x84, 0x46]);
+}
+
+#[test]
+fn test_labels_and_constants() {
+    let program = assemble("
+        SPEED equ 3
+        start:
+            ADD V0, SPEED
+            CALL routine
+            JP start
+        routine: RET
+        sprite:
+            db %11110000, 0x90, SPEED + 1
+            dw sprite - 1
+    ").unwrap();
+
+    assert_eq!(program, vec![0x70, 0x03, 0x22, 0x06, 0x12, 0x00, 0x00, 0xEE,
+                             0xF0, 0x90, 0x04, 0x02, 0x07]);
+}
+
+#[test]
+fn test_assembled_program_runs() {
+    let mut chip = System::default();
+    let program = assemble("LD VA, 2").unwrap();
+
+    for (offset, byte) in program.iter().enumerate() {
+        chip.memory[0x200 + offset] = *byte;
+    }
+    chip.run_op_at(0x200).unwrap();
+
+    assert_eq!(chip.registers[0xA], 2);
+}
+
+#[test]
+fn test_error_location() {
+    let error = assemble("CLS\n  LD V0, nowhere\n").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 10));
+
+    let error = assemble("  FOO V1").unwrap_err();
+    assert_eq!((error.line, error.column), (1, 3));
+
+    let error = assemble("DRW V0, V1, 16").unwrap_err();
+    assert_eq!((error.line, error.column), (1, 13));
+
+    let error = assemble("here:\nhere:").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 1));
+}
+
+#[test]
+fn test_include() {
+    let directory = env::temp_dir().join("chip8_assembler_include_test");
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("font.asm"), "glyph: db 0xF0, 0x90\n").unwrap();
+    fs::write(directory.join("main.asm"), "LD I, glyph\ninclude \"font.asm\"\n").unwrap();
+
+    let program = assemble_file(directory.join("main.asm")).unwrap();
+
+    assert_eq!(program, vec![0xA2, 0x02, 0xF0, 0x90]);
+}
+
+#[test]
+fn test_round_trip_every_opcode() {
+    //The whole opcode space does not fit in memory at once, so go through it in pieces
+    for chunk in 0..64u32 {
+        let mut program = Vec::new();
+        for opcode in (chunk * 0x400)..((chunk + 1) * 0x400) {
+            program.push((opcode >> 8) as u8);
+            program.push(opcode as u8);
+        }
+        //A trailing odd byte has to come back as `db`
+        program.push(chunk as u8);
+
+        let source = disassemble(&program, 0x200);
+
+        assert_eq!(assemble(&source).unwrap(), program);
+    }
+}
+
+#[test]
+fn test_round_trip_at_other_origins() {
+    let program = fs::read("pong.rom").unwrap();
+
+    //CHIP-8X loads programs at 0x300, and the image starts there with no padding before it. Below
+    //0x200 is fine too, for code that lives where the interpreter would
+    for &origin in [0x300, 0x2C0, 0x600, 0x000, 0x050].iter() {
+        let source = disassemble(&program, origin);
+        assert_eq!(assemble(&source).unwrap(), program, "did not round trip at {:03X}", origin);
+    }
+
+    //Anything placed lower still pulls the start down
+    assert_eq!(assemble("org 0x210\ndb 1\norg 0x208\ndb 2").unwrap(), [2, 0, 0, 0, 0, 0, 0, 0, 1]);
+}
+
+#[test]
+fn test_org_errors() {
+    let error = assemble("cls\ncls\norg 0x202\ndb 1, 2").unwrap_err();
+    assert_eq!((error.line, error.column), (4, 1));
+    assert_eq!(error.message, "0x202 already has something placed at it");
+
+    //Ending where the earlier lines start is fine
+    assert_eq!(assemble("org 0x202\ncls\norg 0x200\nret").unwrap(), [0x00, 0xEE, 0x00, 0xE0]);
+    assert!(assemble("org 0x1000").is_err());
+    assert!(assemble("org -1").is_err());
+}
+
+#[test]
+fn test_round_trip_roms() {
+    for rom in ["pong.rom", "test.rom"].iter() {
+        let program = fs::read(rom).unwrap();
+        let source = disassemble(&program, 0x200);
+
+        assert_eq!(assemble(&source).unwrap(), program, "{} did not round trip", rom);
+    }
+}
+
+#[test]
+fn test_hf_and_r_are_only_operands_in_ld() {
+    //Names from before SUPER-CHIP still work as labels and constants
+    let program = assemble("
+        HF equ 5
+        R:
+            LD V0, HF
+            LD HF, V1
+            LD R, V2
+            LD V3, R
+            JP R
+    ").unwrap();
+
+    assert_eq!(program, vec![0x60, 0x05, 0xF1, 0x30, 0xF2, 0x75, 0xF3, 0x85, 0x12, 0x00]);
+}
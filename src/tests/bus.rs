@@ -0,0 +1,71 @@
+use crate::bus::{Access, Bus, FlatRam, Logged, ReadOnly};
+use crate::chip8_cpu::{System, MEMORY_SIZE};
+
+fn system_on(bus: Box<dyn Bus>, program: &[u8]) -> System {
+    let mut chip = System::default();
+    chip.memory = bus;
+    chip.setup_fontset();
+    chip.load_program_bytes(program);
+    chip
+}
+
+#[test]
+fn test_flat_ram() {
+    let mut ram = FlatRam::new(16);
+    ram.write(3, 0x42);
+
+    assert_eq!(ram.read(3), 0x42);
+    assert_eq!(ram.len(), 16);
+    assert_eq!(ram.bytes()[3], 0x42);
+}
+
+#[test]
+fn test_bus_indexes_like_a_slice() {
+    let mut chip = System::default();
+    chip.memory[0x300] = 0x12;
+    chip.memory[0x301..0x303].copy_from_slice(&[0x34, 0x56]);
+
+    assert_eq!(chip.memory[0x300..0x303], [0x12, 0x34, 0x56]);
+    assert_eq!(chip.memory.len(), MEMORY_SIZE);
+}
+
+#[test]
+fn test_read_only_regions_ignore_writes() {
+    //LD B, V0 at 0x100 then 0x300, with the first 0x200 bytes read only
+    let bus = ReadOnly::new(FlatRam::new(MEMORY_SIZE), vec![0x000..=0x1FF]);
+    let mut chip = system_on(Box::new(bus), &[0xA1, 0x00, 0xF0, 0x33, 0xA3, 0x00, 0xF0, 0x33]);
+    chip.registers[0] = 123;
+
+    chip.run_frame(4).unwrap();
+    assert_eq!(chip.memory[0x100..0x103], [0, 0, 0]);
+    assert_eq!(chip.memory[0x300..0x303], [1, 2, 3]);
+    //Loading still goes straight into memory
+    assert_eq!(chip.memory[0x200], 0xA1);
+}
+
+#[test]
+fn test_logging_every_access() {
+    //LD I, 0x300, LD [I], V1, LD V0, [I] after I has moved on
+    let (bus, log) = Logged::all(FlatRam::new(MEMORY_SIZE));
+    let mut chip = system_on(Box::new(bus), &[0xA3, 0x00, 0xF1, 0x55, 0xF0, 0x65]);
+    chip.registers[0] = 0xAA;
+    chip.registers[1] = 0xBB;
+
+    chip.run_frame(3).unwrap();
+    assert_eq!(*log.borrow(), vec![
+        Access { address: 0x300, value: 0xAA, write: true },
+        Access { address: 0x301, value: 0xBB, write: true },
+        Access { address: 0x302, value: 0x00, write: false },
+    ]);
+}
+
+#[test]
+fn test_watchpoints_only_log_watched_addresses() {
+    //Draw the 0 digit at 0x000 and BCD to 0x300
+    let (bus, log) = Logged::watching(FlatRam::new(MEMORY_SIZE), vec![0x301..=0x301]);
+    let mut chip = system_on(Box::new(bus), &[0xD0, 0x15, 0xA3, 0x00, 0xF0, 0x33]);
+    chip.registers[0] = 42;
+
+    chip.run_frame(3).unwrap();
+    assert_eq!(*log.borrow(), vec![Access { address: 0x301, value: 4, write: true }]);
+}
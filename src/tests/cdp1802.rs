@@ -0,0 +1,205 @@
+use crate::cdp1802::{Board, Cdp1802};
+
+struct FlatRam {
+    memory: Vec<u8>,
+    outputs: Vec<(u8, u8)>,
+    flags: [bool; 4],
+}
+
+impl Board for FlatRam {
+    fn read(&mut self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        self.outputs.push((port, value));
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        port * 0x11
+    }
+
+    fn flag(&self, number: u8) -> bool {
+        self.flags[number as usize - 1]
+    }
+}
+
+fn board_with(program: &[u8]) -> FlatRam {
+    let mut memory = vec![0; 0x10000];
+    memory[..program.len()].copy_from_slice(program);
+    FlatRam { memory, outputs: Vec::new(), flags: [false; 4] }
+}
+
+fn run(cpu: &mut Cdp1802, board: &mut FlatRam, instructions: usize) -> u32 {
+    (0..instructions).map(|_| cpu.step(board)).sum()
+}
+
+#[test]
+fn test_arithmetic() {
+    //LDI 0xF0, ADI 0x20 carries, SMI 0x20 does not borrow, SDI 0x00 does
+    let mut board = board_with(&[0xF8, 0xF0, 0xFC, 0x20, 0xFF, 0x20, 0xFD, 0x00]);
+    let mut cpu = Cdp1802::new();
+
+    run(&mut cpu, &mut board, 2);
+    assert_eq!(cpu.d, 0x10);
+    assert!(cpu.df);
+
+    cpu.step(&mut board);
+    assert_eq!(cpu.d, 0xF0);
+    assert!(!cpu.df);
+
+    cpu.step(&mut board);
+    assert_eq!(cpu.d, 0x10);
+    assert!(!cpu.df);
+}
+
+#[test]
+fn test_shifts_through_df() {
+    //LDI 0x81, SHRC with DF clear, SHLC, SHL
+    let mut board = board_with(&[0xF8, 0x81, 0x76, 0x7E, 0xFE]);
+    let mut cpu = Cdp1802::new();
+
+    run(&mut cpu, &mut board, 2);
+    assert_eq!(cpu.d, 0x40);
+    assert!(cpu.df);
+
+    cpu.step(&mut board);
+    assert_eq!(cpu.d, 0x81);
+    assert!(!cpu.df);
+
+    cpu.step(&mut board);
+    assert_eq!(cpu.d, 0x02);
+    assert!(cpu.df);
+}
+
+#[test]
+fn test_registers_and_memory() {
+    //R3 = 0x1234 through D, then STR R3 and LDA R3
+    let program = [0xF8, 0x12, 0xB3, 0xF8, 0x34, 0xA3, 0xF8, 0x55, 0x53, 0xF8, 0x00, 0x43];
+    let mut board = board_with(&program);
+    let mut cpu = Cdp1802::new();
+
+    run(&mut cpu, &mut board, 8);
+    assert_eq!(cpu.registers[3], 0x1235);
+    assert_eq!(board.memory[0x1234], 0x55);
+    assert_eq!(cpu.d, 0x55);
+}
+
+#[test]
+fn test_branches() {
+    //LDI 0, BZ 0x06 is taken, BNZ is not, LBR 0x1000 always is
+    let mut board = board_with(&[0xF8, 0x00, 0x32, 0x06, 0x00, 0x00, 0x3A, 0x00, 0xC0, 0x10, 0x00]);
+    let mut cpu = Cdp1802::new();
+
+    run(&mut cpu, &mut board, 2);
+    assert_eq!(cpu.program_counter(), 0x06);
+    cpu.step(&mut board);
+    assert_eq!(cpu.program_counter(), 0x08);
+    assert_eq!(cpu.step(&mut board), 3);
+    assert_eq!(cpu.program_counter(), 0x1000);
+}
+
+#[test]
+fn test_long_skips() {
+    //SEQ, LSQ skips two NOPs, LSNQ does not skip the last one
+    let mut board = board_with(&[0x7B, 0xCD, 0xC4, 0xC4, 0xC5, 0xC4]);
+    let mut cpu = Cdp1802::new();
+
+    run(&mut cpu, &mut board, 2);
+    assert_eq!(cpu.program_counter(), 0x04);
+    run(&mut cpu, &mut board, 2);
+    assert_eq!(cpu.program_counter(), 0x06);
+    assert!(cpu.q);
+}
+
+#[test]
+fn test_flags_branch() {
+    let mut board = board_with(&[0x36, 0x10, 0x3E, 0x20]);
+    board.flags[2] = true;
+    let mut cpu = Cdp1802::new();
+
+    //B3 is taken with EF3 asserted
+    cpu.step(&mut board);
+    assert_eq!(cpu.program_counter(), 0x10);
+
+    cpu.registers[0] = 2;
+    cpu.step(&mut board);
+    assert_eq!(cpu.program_counter(), 0x04);
+}
+
+#[test]
+fn test_input_and_output() {
+    //SEX 2 with R2 = 0x100, OUT 4 then INP 5
+    let mut board = board_with(&[0xE2, 0x64, 0x6D]);
+    board.memory[0x100] = 0xAB;
+    let mut cpu = Cdp1802::new();
+    cpu.registers[2] = 0x100;
+
+    run(&mut cpu, &mut board, 3);
+    assert_eq!(board.outputs, vec![(4, 0xAB)]);
+    assert_eq!(cpu.d, 0x55);
+    assert_eq!(board.memory[0x101], 0x55);
+}
+
+#[test]
+fn test_sep_call_and_mark() {
+    //R4 = 0x10, SEP 4 runs there, MARK saves X and P, SEP 0 returns
+    let mut board = board_with(&[0xF8, 0x10, 0xA4, 0xD4, 0x00]);
+    board.memory[0x10] = 0x79;
+    board.memory[0x11] = 0xD0;
+    let mut cpu = Cdp1802::new();
+    cpu.registers[2] = 0x200;
+
+    run(&mut cpu, &mut board, 3);
+    assert_eq!(cpu.p, 4);
+    cpu.step(&mut board);
+    assert_eq!(cpu.t, 0x04);
+    assert_eq!(board.memory[0x200], 0x04);
+    assert_eq!(cpu.x, 4);
+    assert_eq!(cpu.registers[2], 0x1FF);
+    cpu.step(&mut board);
+    assert_eq!(cpu.p, 0);
+    assert_eq!(cpu.program_counter(), 0x04);
+}
+
+#[test]
+fn test_interrupt_and_return() {
+    let mut board = board_with(&[0xC4]);
+    board.memory[0x300] = 0x78;
+    board.memory[0x301] = 0x70;
+    let mut cpu = Cdp1802::new();
+    cpu.registers[1] = 0x300;
+    cpu.registers[2] = 0x400;
+    cpu.x = 3;
+
+    assert_eq!(cpu.interrupt(), 1);
+    assert_eq!((cpu.p, cpu.x, cpu.t), (1, 2, 0x30));
+    assert!(!cpu.interrupts_enabled);
+    assert_eq!(cpu.interrupt(), 0);
+
+    //SAV then RET brings X and P back
+    run(&mut cpu, &mut board, 2);
+    assert_eq!((cpu.p, cpu.x), (0, 3));
+    assert!(cpu.interrupts_enabled);
+}
+
+#[test]
+fn test_idle_waits_for_dma() {
+    let mut board = board_with(&[0x00, 0xC4]);
+    board.memory[0x80] = 0x42;
+    let mut cpu = Cdp1802::new();
+
+    cpu.step(&mut board);
+    assert!(cpu.idle);
+    assert_eq!(cpu.step(&mut board), 1);
+    assert_eq!(cpu.program_counter(), 0x01);
+
+    cpu.registers[0] = 0x80;
+    assert_eq!(cpu.dma_out(&mut board), 0x42);
+    assert!(!cpu.idle);
+    assert_eq!(cpu.registers[0], 0x81);
+}
@@ -1,7 +1,11 @@
-use crate::chip8_cpu::System;
+use crate::chip8_cpu::{CpuError, MachineCode, MemoryMode, System};
+use crate::variant::Variant;
+use super::golden::GoldenRun;
 
 use std::io::prelude::*;
 use std::fs;
+use std::cell::Cell;
+use std::rc::Rc;
 
 #[test]
 fn test_loading_rom() {
@@ -16,7 +20,7 @@ fn test_loading_rom() {
         Ok(data) => {
             let mut i = 0usize;
 
-            for byte in chip.memory[0x200..0x20F].iter() {
+            for byte in chip.memory[0x200..0x200 + data.len()].iter() {
                 assert!(byte.eq(&data[i]),"BYTES LOADED ARE NOT EQUAL");
                 i = i + 1;
             }
@@ -34,7 +38,7 @@ fn test_register_set() {
     chip.memory[0x200] = 0x6A;
     chip.memory[0x201] = 0x02;
 
-    chip.run_op_at(0x200);
+    chip.run_op_at(0x200).unwrap();
 
     assert_eq!(chip.registers[0xA], 2);
 }
@@ -45,13 +49,321 @@ fn test_set_index_register() {
     chip.memory[0x200] = 0xA2;
     chip.memory[0x201] = 0xEA;
 
-    chip.run_op_at(0x200);
+    chip.run_op_at(0x200).unwrap();
 
     assert_eq!(chip.index_register, 746);
 }
 
-//TODO: Implement screen test
 #[test]
 fn test_graphics() {
-    let mut chip = System::default();
-}
\ No newline at end of file
+    //The 16 digits of the font in two rows, checked against a golden screen
+    GoldenRun::octo("font_digits", "
+        : main
+            v0 := 0
+            v1 := 0
+            v2 := 0
+            loop
+                i := hex v2
+                sprite v0 v1 5
+                v0 += 6
+                v2 += 1
+                if v2 == 8 then v1 := 8
+                if v2 == 8 then v0 := 0
+                if v2 == 16 then jump spin
+            again
+        : spin
+            jump spin
+    ").frames(20).check();
+}
+
+fn system_with(variant: Variant, program: &[u8]) -> System {
+    let mut chip = System::new(variant);
+    chip.setup_fontset();
+    chip.load_program_bytes(program);
+    chip
+}
+
+#[test]
+fn test_jump_and_call() {
+    //JP 0x206, then CALL 0x20A which returns straight away
+    let mut chip = system_with(Variant::Chip8, &[0x12, 0x06, 0x00, 0x00, 0x00, 0x00, 0x22, 0x0A, 0x00, 0x00, 0x00, 0xEE]);
+
+    chip.step().unwrap();
+    assert_eq!(chip.program_counter, 0x206);
+    chip.step().unwrap();
+    assert_eq!(chip.program_counter, 0x20A);
+    assert_eq!(chip.stack_pointer, 1);
+    chip.step().unwrap();
+    assert_eq!(chip.program_counter, 0x208);
+    assert_eq!(chip.stack_pointer, 0);
+}
+
+#[test]
+fn test_arithmetic_flags() {
+    //V0 = 0xF0, V1 = 0x20, V0 += V1, V2 = 0x10, V2 -= V1
+    let mut chip = system_with(Variant::Chip8, &[0x60, 0xF0, 0x61, 0x20, 0x80, 0x14, 0x62, 0x10, 0x82, 0x15]);
+
+    chip.run_frame(3).unwrap();
+    assert_eq!(chip.registers[0], 0x10);
+    assert_eq!(chip.registers[0xF], 1);
+    chip.run_frame(2).unwrap();
+    assert_eq!(chip.registers[2], 0xF0);
+    assert_eq!(chip.registers[0xF], 0);
+}
+
+#[test]
+fn test_draw_collision() {
+    //Draw the 0 digit twice at 0,0
+    let mut chip = system_with(Variant::Chip8, &[0xD0, 0x05, 0xD0, 0x05]);
+
+    chip.step().unwrap();
+    assert_eq!(chip.display.get(0, 0), 1);
+    assert_eq!(chip.registers[0xF], 0);
+    chip.step().unwrap();
+    assert_eq!(chip.display.get(0, 0), 0);
+    assert_eq!(chip.registers[0xF], 1);
+}
+
+#[test]
+fn test_schip_resolution_and_big_sprites() {
+    //HIGH, V0 = 120, V1 = 60, I = 0x300, DRW V0, V1, 0
+    let mut chip = system_with(Variant::SuperChip, &[0x00, 0xFF, 0x60, 0x78, 0x61, 0x3C, 0xA3, 0x00, 0xD0, 0x10]);
+    for byte in chip.memory[0x300..0x320].iter_mut() {
+        *byte = 0xFF;
+    }
+
+    chip.step().unwrap();
+    assert!(chip.hires);
+    assert_eq!((chip.screen_width(), chip.screen_height()), (128, 64));
+
+    chip.run_frame(4).unwrap();
+    //The 16x16 sprite is clipped at the bottom right corner
+    assert_eq!(chip.display.get(120, 60), 1);
+    assert_eq!(chip.display.get(127, 63), 1);
+    assert_eq!(chip.display.get(0, 0), 0);
+}
+
+#[test]
+fn test_schip_big_font_and_flags() {
+    //V0 = 7, LD HF, V0, LD R, V0, V0 = 0, LD V0, R
+    let mut chip = system_with(Variant::SuperChip, &[0x60, 0x07, 0xF0, 0x30, 0xF0, 0x75, 0x60, 0x00, 0xF0, 0x85]);
+
+    chip.run_frame(2).unwrap();
+    assert_eq!(chip.index_register, 0x50 + 7 * 10);
+    chip.run_frame(3).unwrap();
+    assert_eq!(chip.rpl_flags[0], 7);
+    assert_eq!(chip.registers[0], 7);
+}
+
+#[test]
+fn test_schip_exit() {
+    let mut chip = system_with(Variant::SuperChip, &[0x00, 0xFD, 0x60, 0x01]);
+
+    chip.run_frame(5).unwrap();
+    assert!(chip.exited);
+    assert_eq!(chip.program_counter, 0x200);
+    assert_eq!(chip.registers[0], 0);
+}
+
+#[test]
+fn test_xo_chip_long_index_and_skip() {
+    //V0 = 1, SE V0, 1 skips all of F000 1234, then V1 = 2
+    let mut chip = system_with(Variant::XoChip, &[0x60, 0x01, 0x30, 0x01, 0xF0, 0x00, 0x12, 0x34, 0x61, 0x02, 0xF0, 0x00, 0xFE, 0xDC]);
+
+    chip.run_frame(3).unwrap();
+    assert_eq!(chip.registers[1], 2);
+    assert_eq!(chip.index_register, 0);
+    chip.step().unwrap();
+    assert_eq!(chip.index_register, 0xFEDC);
+    assert_eq!(chip.program_counter, 0x20E);
+}
+
+#[test]
+fn test_xo_chip_long_index_wraps_at_the_end_of_memory() {
+    //F000 in the last word of 64K takes its address from the first two bytes
+    let mut chip = system_with(Variant::XoChip, &[]);
+    chip.program_counter = 0xFFFE;
+    chip.memory[0xFFFE] = 0xF0;
+    chip.memory[0xFFFF] = 0x00;
+    chip.memory[0x000] = 0x12;
+    chip.memory[0x001] = 0x34;
+
+    chip.step().unwrap();
+    assert_eq!(chip.index_register, 0x1234);
+    assert_eq!(chip.program_counter, 0x002);
+}
+
+#[test]
+fn test_xo_chip_planes() {
+    //Select both planes, I = 0x300, draw 1 row, select plane 2, scroll up 1
+    let mut chip = system_with(Variant::XoChip, &[0xF3, 0x01, 0xA3, 0x00, 0xD0, 0x01, 0xF2, 0x01, 0x00, 0xD1]);
+    chip.memory[0x300] = 0x80;
+    chip.memory[0x301] = 0xC0;
+    chip.registers[0] = 0;
+    chip.display.set(0, 1, 0x02);
+
+    chip.run_frame(3).unwrap();
+    assert_eq!(chip.display.get(0, 0), 0x03);
+    assert_eq!(chip.display.get(1, 0), 0x02);
+    chip.run_frame(2).unwrap();
+    //Only the second plane moved
+    assert_eq!(chip.display.get(0, 0), 0x03);
+    assert_eq!(chip.display.get(1, 0), 0x00);
+}
+
+#[test]
+fn test_xo_chip_audio() {
+    //I = 0x300, load the pattern, V0 = 112, pitch from V0
+    let mut chip = system_with(Variant::XoChip, &[0xA3, 0x00, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A]);
+    chip.memory[0x30F] = 0xAA;
+
+    chip.run_frame(4).unwrap();
+    assert_eq!(chip.audio_pattern[15], 0xAA);
+    assert_eq!(chip.pitch, 112);
+    assert!((chip.audio_sample_rate() - 8000.0).abs() < 0.001);
+}
+
+#[test]
+fn test_strict_mode_protects_the_interpreter_area() {
+    //LD I, 0x050 then LD B, V0 over the font
+    let program = [0xA0, 0x50, 0xF0, 0x33];
+
+    let mut chip = system_with(Variant::Chip8, &program);
+    chip.memory_mode = MemoryMode::Strict;
+    let font = chip.memory[0x50];
+    chip.step().unwrap();
+    assert_eq!(chip.step(), Err(CpuError::ProtectedWrite { address: 0x202, target: 0x050 }));
+    assert_eq!(chip.memory[0x50], font);
+    assert_eq!(chip.program_counter, 0x202);
+
+    //Real hardware lets it happen
+    let mut chip = system_with(Variant::Chip8, &program);
+    chip.run_frame(2).unwrap();
+    assert_eq!(chip.memory[0x50..0x53], [0, 0, 0]);
+}
+
+#[test]
+fn test_strict_mode_makes_none_of_a_partly_protected_write() {
+    //LD I, 0x1FE, LD V2, 3, then LD [I], V2 over 0x1FE to 0x200
+    let mut chip = system_with(Variant::Chip8, &[0xA1, 0xFE, 0x62, 0x03, 0xF2, 0x55]);
+    chip.memory_mode = MemoryMode::Strict;
+    chip.quirks.load_store_increments_i = true;
+    chip.step().unwrap();
+    chip.step().unwrap();
+
+    assert_eq!(chip.step(), Err(CpuError::ProtectedWrite { address: 0x204, target: 0x1FE }));
+    assert_eq!(chip.memory[0x200], 0xA1);
+    assert_eq!(chip.index_register, 0x1FE);
+}
+
+#[test]
+fn test_strict_mode_reads_past_the_end() {
+    //LD I, 0xFFF then LD V1, [I]
+    let program = [0xAF, 0xFF, 0xF1, 0x65];
+
+    let mut chip = system_with(Variant::Chip8, &program);
+    chip.memory_mode = MemoryMode::Strict;
+    chip.step().unwrap();
+    assert_eq!(chip.step(), Err(CpuError::ReadPastEnd { address: 0x202, target: 0x1000 }));
+
+    let mut chip = system_with(Variant::Chip8, &program);
+    chip.run_frame(2).unwrap();
+    assert_eq!(chip.registers[1], chip.memory[0]);
+}
+
+#[test]
+fn test_strict_mode_keeps_the_program_counter_in_the_program() {
+    //JP 0x300, well past the end of the program
+    let mut chip = system_with(Variant::Chip8, &[0x13, 0x00]);
+    chip.memory_mode = MemoryMode::Strict;
+
+    chip.step().unwrap();
+    assert_eq!(chip.step(), Err(CpuError::LeftProgram { address: 0x300 }));
+}
+
+#[test]
+fn test_permissive_mode_wraps_the_program_counter() {
+    //JP 0xFFE, then LD V0, 1 in the last word of memory
+    let mut chip = system_with(Variant::Chip8, &[0x1F, 0xFE]);
+    chip.memory[0xFFE] = 0x60;
+    chip.memory[0xFFF] = 0x01;
+    chip.run_frame(2).unwrap();
+    assert_eq!(chip.registers[0], 1);
+    assert_eq!(chip.program_counter, 0x000);
+
+    //Waiting for a key, or exiting, at the very start stays put
+    for &opcode in [[0xF0, 0x0A], [0x00, 0xFD]].iter() {
+        let mut chip = system_with(Variant::SuperChip, &[]);
+        chip.program_counter = 0x000;
+        chip.memory[0x000..0x002].copy_from_slice(&opcode);
+        chip.step().unwrap();
+        assert_eq!(chip.program_counter, 0x000);
+    }
+
+    //Skipping from the last two words lands back at the start too
+    for &(address, landing) in [(0xFFC, 0x000), (0xFFE, 0x002)].iter() {
+        let mut chip = system_with(Variant::Chip8, &[0x1F, 0xFC]);
+        chip.program_counter = address;
+        //SE V0, 0
+        chip.memory[address as usize] = 0x30;
+        chip.memory[address as usize + 1] = 0x00;
+        chip.step().unwrap();
+        assert_eq!(chip.program_counter, landing);
+    }
+}
+
+#[test]
+fn test_clear_screen() {
+    //Draw the 0 digit at 0,0 then CLS
+    let mut chip = system_with(Variant::Chip8, &[0xD0, 0x05, 0x00, 0xE0]);
+
+    chip.step().unwrap();
+    assert_eq!(chip.display.get(0, 0), 1);
+    chip.step().unwrap();
+    assert_eq!(chip.display.get(0, 0), 0);
+    assert_eq!(chip.program_counter, 0x204);
+}
+
+#[test]
+fn test_machine_code_calls() {
+    //SYS 0x123 then LD V0, 1
+    let program = [0x01, 0x23, 0x60, 0x01];
+
+    let mut chip = system_with(Variant::Chip8, &program);
+    assert_eq!(chip.step(), Err(CpuError::MachineCode { address: 0x200, routine: 0x123 }));
+    assert_eq!(chip.program_counter, 0x200);
+
+    let mut chip = system_with(Variant::Chip8, &program);
+    chip.machine_code = MachineCode::Ignore;
+    chip.run_frame(2).unwrap();
+    assert_eq!(chip.registers[0], 1);
+}
+
+#[test]
+fn test_native_routines() {
+    //SYS 0x123, SYS 0x456, SYS 0x123
+    let mut chip = system_with(Variant::Chip8, &[0x01, 0x23, 0x04, 0x56, 0x01, 0x23]);
+    let calls = Rc::new(Cell::new(0));
+    let counted = calls.clone();
+    chip.register_routine(0x123, move |system| {
+        counted.set(counted.get() + 1);
+        system.registers[0] += 1;
+        system.index_register = 0x300;
+    });
+    assert!(chip.has_routine(0x123));
+
+    chip.step().unwrap();
+    assert_eq!((chip.registers[0], chip.index_register), (1, 0x300));
+    assert_eq!(chip.program_counter, 0x202);
+    //Calls without a routine still follow the machine code mode
+    assert_eq!(chip.step(), Err(CpuError::MachineCode { address: 0x202, routine: 0x456 }));
+    chip.program_counter = 0x204;
+    chip.step().unwrap();
+    assert_eq!(chip.registers[0], 2);
+    assert_eq!(calls.get(), 2);
+
+    assert!(chip.unregister_routine(0x123));
+    assert!(!chip.unregister_routine(0x123));
+    chip.program_counter = 0x200;
+    assert!(chip.step().is_err());
+}
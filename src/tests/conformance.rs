@@ -0,0 +1,106 @@
+//The community conformance ROMs, run through the headless runner with each quirks preset and
+//compared against the pass screens in src/tests/golden/conformance.
+//
+//The ROMs aren't ours to check in. roms/conformance/fetch.sh downloads Timendus' chip8-test-suite
+//into roms/conformance, BC_test.ch8 has to be copied there by hand, and CHIP8_TEST_ROMS can name
+//another directory. The build script turns the tests on once there are ROMs, until then they show
+//as ignored, and a ROM missing from a directory that has others fails its test.
+//
+//The pass screens are drawn from what each ROM documents a pass looks like. They are never
+//blessed from our own output, which would make whatever we get wrong pass.
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use super::golden::{visual_diff, GoldenRun, GOLDEN_DIRECTORY};
+use crate::headless::screen_to_text;
+use crate::quirks::Quirks;
+use crate::variant::Variant;
+
+const ROM_DIRECTORY: &str = "roms/conformance";
+//The presets every ROM is run under, with the variant each belongs to
+const PRESETS: [(&str, Variant); 3] = [("vip", Variant::Chip8), ("schip", Variant::SuperChip), ("xochip", Variant::XoChip)];
+//Where the test suite ROMs look for a platform chosen ahead of time, skipping their menus
+const PLATFORM_ADDRESS: u16 = 0x1FF;
+
+fn load_rom(file: &str) -> Vec<u8> {
+    let directory = env::var("CHIP8_TEST_ROMS").unwrap_or_else(|_| String::from(ROM_DIRECTORY));
+    let path = PathBuf::from(directory).join(file);
+
+    fs::read(&path).unwrap_or_else(|e| panic!("could not read {}: {}", path.display(), e))
+}
+
+//Runs `file` under every preset, `setup` adding the script, and checks each result screen
+fn check_rom<F: Fn(GoldenRun, usize) -> GoldenRun>(file: &str, name: &str, frames: u64, setup: F) {
+    let program = load_rom(file);
+
+    for (number, &(preset, variant)) in PRESETS.iter().enumerate() {
+        let name = format!("{}_{}", name, preset);
+        let run = GoldenRun::new(&name, &program)
+            .variant(variant)
+            .quirks(Quirks::preset(preset).unwrap())
+            .frames(frames);
+        check_pass_screen(&setup(run, number), &name);
+    }
+}
+
+//Like GoldenRun::check, but BLESS is ignored, as the pass screens are drawn by hand
+fn check_pass_screen(run: &GoldenRun, name: &str) {
+    let actual = screen_to_text(&run.run());
+    let path = PathBuf::from(GOLDEN_DIRECTORY).join("conformance").join(format!("{}.txt", name));
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!("could not read {}: {}, draw it from the ROM's documented pass screen. The screen was\n{}",
+               path.display(), e, actual)
+    });
+    if expected != actual {
+        panic!("{} does not match the screen\n{}", path.display(), visual_diff(&expected, &actual));
+    }
+}
+
+#[test]
+#[cfg_attr(not(conformance_roms), ignore = "needs roms/conformance")]
+fn test_chip8_logo() {
+    check_rom("1-chip8-logo.ch8", "chip8_logo", 60, |run, _| run);
+}
+
+#[test]
+#[cfg_attr(not(conformance_roms), ignore = "needs roms/conformance")]
+fn test_ibm_logo() {
+    check_rom("2-ibm-logo.ch8", "ibm_logo", 60, |run, _| run);
+}
+
+#[test]
+#[cfg_attr(not(conformance_roms), ignore = "needs roms/conformance")]
+fn test_corax_opcodes() {
+    check_rom("3-corax+.ch8", "corax", 120, |run, _| run);
+}
+
+#[test]
+#[cfg_attr(not(conformance_roms), ignore = "needs roms/conformance")]
+fn test_flags() {
+    check_rom("4-flags.ch8", "flags", 120, |run, _| run);
+}
+
+#[test]
+#[cfg_attr(not(conformance_roms), ignore = "needs roms/conformance")]
+fn test_quirks() {
+    //1 is CHIP-8, 2 modern SUPER-CHIP and 3 XO-CHIP, in the order of PRESETS. The display wait
+    //test needs a good few frames to measure
+    check_rom("5-quirks.ch8", "quirks", 600, |run, number| run.poke(PLATFORM_ADDRESS, number as u8 + 1));
+}
+
+#[test]
+#[cfg_attr(not(conformance_roms), ignore = "needs roms/conformance")]
+fn test_keypad() {
+    //3 picks the FX0A test, which passes once a key is pressed and let go
+    check_rom("6-keypad.ch8", "keypad", 120, |run, _| {
+        run.poke(PLATFORM_ADDRESS, 3).keys_from(30, &[0x5]).keys_from(40, &[])
+    });
+}
+
+#[test]
+#[cfg_attr(not(conformance_roms), ignore = "needs roms/conformance")]
+fn test_bc() {
+    check_rom("BC_test.ch8", "bc", 120, |run, _| run);
+}
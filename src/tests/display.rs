@@ -0,0 +1,100 @@
+use crate::chip8_cpu::System;
+use crate::display::{Display, Rect};
+use crate::timing::Timing;
+use crate::variant::Variant;
+
+fn system_with(variant: Variant, program: &[u8]) -> System {
+    let mut chip = System::new(variant);
+    chip.setup_fontset();
+    chip.load_program_bytes(program);
+    chip.display.take_dirty();
+    chip
+}
+
+#[test]
+fn test_new_display_is_blank_and_dirty() {
+    let mut display = Display::new(64, 32);
+
+    assert_eq!((display.width(), display.height()), (64, 32));
+    assert_eq!(display.get(63, 31), 0);
+    assert_eq!(display.take_dirty(), vec![Rect { x: 0, y: 0, width: 64, height: 32 }]);
+    assert!(!display.is_dirty());
+}
+
+#[test]
+fn test_set_marks_changed_pixels() {
+    let mut display = Display::new(64, 32);
+    display.take_dirty();
+
+    display.set(3, 4, 1);
+    display.set(4, 4, 1);
+    display.set(40, 20, 1);
+    //Setting a pixel to what it already is changes nothing
+    display.set(40, 20, 1);
+
+    assert_eq!(display.get(3, 4), 1);
+    assert_eq!(display.take_dirty(), vec![Rect { x: 3, y: 4, width: 2, height: 1 },
+                                          Rect { x: 40, y: 20, width: 1, height: 1 }]);
+}
+
+#[test]
+fn test_draw_marks_sprite_dirty() {
+    //V0 = 10, V1 = 5, draw the 0 digit there
+    let mut chip = system_with(Variant::Chip8, &[0x60, 0x0A, 0x61, 0x05, 0xD0, 0x15]);
+
+    chip.run_frame(2).unwrap();
+    assert!(!chip.display.is_dirty());
+    chip.step().unwrap();
+    //The 0 is 4 pixels wide, the blank right half of its byte is not drawn
+    assert_eq!(chip.display.take_dirty(), vec![Rect { x: 10, y: 5, width: 4, height: 5 }]);
+    assert_eq!(chip.display.get(10, 5), 1);
+}
+
+#[test]
+fn test_clear_and_resolution_change_mark_everything() {
+    //HIGH, LOW
+    let mut chip = system_with(Variant::SuperChip, &[0x00, 0xFF, 0x00, 0xFE]);
+    chip.display.set(1, 1, 1);
+    chip.display.take_dirty();
+
+    chip.step().unwrap();
+    assert_eq!((chip.display.width(), chip.display.height()), (128, 64));
+    assert_eq!(chip.display.get(1, 1), 0);
+    assert_eq!(chip.display.take_dirty(), vec![Rect { x: 0, y: 0, width: 128, height: 64 }]);
+
+    chip.display.set(1, 1, 1);
+    chip.clear_screen();
+    assert_eq!(chip.display.get(1, 1), 0);
+    assert_eq!(chip.display.take_dirty(), vec![Rect { x: 0, y: 0, width: 128, height: 64 }]);
+}
+
+#[test]
+fn test_rows_and_packed() {
+    let mut display = Display::new(16, 2);
+    display.set(0, 0, 1);
+    display.set(9, 0, 2);
+    display.set(15, 1, 3);
+
+    let rows: Vec<&[u8]> = display.rows().collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].len(), 16);
+    assert_eq!(rows[0][9], 2);
+    assert_eq!(rows[1][15], 3);
+
+    assert_eq!(display.packed(0x03), vec![0x80, 0x40, 0x00, 0x01]);
+    //Only the first plane
+    assert_eq!(display.packed(0x01), vec![0x80, 0x00, 0x00, 0x01]);
+}
+
+#[test]
+fn test_frame_counter() {
+    let mut chip = system_with(Variant::Chip8, &[0x12, 0x00]);
+    let timing = Timing::default();
+
+    assert_eq!(chip.display.frame(), 0);
+    timing.run_frame(&mut chip).unwrap();
+    timing.run_frame(&mut chip).unwrap();
+    assert_eq!(chip.display.frame(), 2);
+    //Nothing was drawn, so there is nothing to redraw
+    assert!(!chip.display.is_dirty());
+}
@@ -0,0 +1,79 @@
+use crate::chip8_cpu::System;
+use crate::font::{Font, FontError, FontSet, BIG_FONT_SIZE, SMALL_FONT_SIZE};
+use crate::variant::Variant;
+
+const FONT_SETS: [FontSet; 5] = [FontSet::Vip, FontSet::Dream6800, FontSet::Eti660, FontSet::SuperChip, FontSet::Octo];
+
+#[test]
+fn test_font_set_names() {
+    for set in FONT_SETS.iter() {
+        assert_eq!(FontSet::from_name(set.name()), Some(*set));
+    }
+    assert_eq!(FontSet::from_name("comic"), None);
+    assert_eq!(Font::default(), FontSet::Octo.font());
+}
+
+#[test]
+fn test_font_sets_differ() {
+    //The 1 of each font, which is where they differ most
+    let ones: Vec<[u8; 5]> = FONT_SETS.iter().map(|set| {
+        let mut one = [0; 5];
+        one.copy_from_slice(&set.font().small[5..10]);
+        one
+    }).collect();
+
+    assert_eq!(ones[0], [0x60, 0x20, 0x20, 0x20, 0x70]);
+    assert_eq!(ones[1], [0x40; 5]);
+    assert_eq!(ones[2], [0x20; 5]);
+    assert_eq!(ones[3], ones[4]);
+    assert_ne!(FontSet::SuperChip.font().big, FontSet::Octo.font().big);
+}
+
+#[test]
+fn test_font_from_bytes() {
+    let small = Font::from_bytes(&[0x11; SMALL_FONT_SIZE]).unwrap();
+    assert_eq!(small.small[0], 0x11);
+    assert_eq!(small.big, None);
+
+    let mut bytes = vec![0x11; SMALL_FONT_SIZE];
+    bytes.extend_from_slice(&[0x22; BIG_FONT_SIZE]);
+    let both = Font::from_bytes(&bytes).unwrap();
+    assert_eq!(both.big.unwrap()[0], 0x22);
+
+    assert!(matches!(Font::from_bytes(&[0; 100]), Err(FontError::WrongSize(100))));
+}
+
+#[test]
+fn test_font_address_moves_fx29_and_fx30() {
+    let mut chip = System::new(Variant::SuperChip);
+    chip.font = FontSet::Vip.font();
+    chip.font_address = 0x050;
+    chip.setup_fontset();
+    chip.registers[3] = 0x1;
+    //LD F, V3 then LD HF, V3
+    chip.load_program_bytes(&[0xF3, 0x29, 0xF3, 0x30]);
+
+    chip.step().unwrap();
+    assert_eq!(chip.index_register, 0x055);
+    assert_eq!(chip.memory[0x055], 0x60);
+    assert_eq!(chip.memory[..0x050], [0; 0x050][..]);
+
+    //The VIP font has no big digits, so Octo's are used
+    chip.step().unwrap();
+    assert_eq!(chip.index_register, 0x050 + SMALL_FONT_SIZE as u16 + 10);
+    assert_eq!(chip.memory[chip.index_register as usize], 0x18);
+}
+
+#[test]
+fn test_variants_load_their_own_font() {
+    assert_eq!(System::new(Variant::Chip8).font, FontSet::Vip.font());
+    assert_eq!(System::new(Variant::Chip48).font, FontSet::Vip.font());
+    assert_eq!(System::new(Variant::SuperChip).font, FontSet::SuperChip.font());
+    assert_eq!(System::new(Variant::XoChip).font, FontSet::Octo.font());
+    assert_eq!(System::default().font, FontSet::Vip.font());
+
+    //The VIP's 1 has its foot on the right
+    let mut chip = System::new(Variant::Chip8);
+    chip.setup_fontset();
+    assert_eq!(chip.memory[5..10], [0x60, 0x20, 0x20, 0x20, 0x70]);
+}
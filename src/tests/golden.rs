@@ -0,0 +1,193 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::chip8_cpu::System;
+use crate::headless::{self, screen_to_text};
+use crate::movie::Movie;
+use crate::octo;
+use crate::quirks::Quirks;
+use crate::rng::DEFAULT_SEED;
+use crate::timing::Timing;
+use crate::variant::Variant;
+
+//Golden screens are checked in next to the tests, as text laid out like `screen_to_text`
+pub(crate) const GOLDEN_DIRECTORY: &str = "src/tests/golden";
+
+/// A program to run for a number of frames with the keypad following a script, ending in a
+/// screen to compare against a golden file.
+pub(crate) struct GoldenRun {
+    //The golden file, relative to the golden directory and without .txt
+    name: String,
+    program: Vec<u8>,
+    variant: Variant,
+    //None for the variant's own
+    quirks: Option<Quirks>,
+    frames: u64,
+    //The keys held from each frame on, as keypad bits
+    inputs: Vec<(u64, u16)>,
+    //Bytes written after the program is loaded
+    pokes: Vec<(u16, u8)>,
+}
+
+impl GoldenRun {
+    pub(crate) fn new(name: &str, program: &[u8]) -> GoldenRun {
+        GoldenRun {
+            name: String::from(name),
+            program: program.to_vec(),
+            variant: Variant::Chip8,
+            quirks: None,
+            frames: 1,
+            inputs: Vec::new(),
+            pokes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn octo(name: &str, source: &str) -> GoldenRun {
+        GoldenRun::new(name, &octo::compile(source).unwrap())
+    }
+
+    pub(crate) fn variant(mut self, variant: Variant) -> GoldenRun {
+        self.variant = variant;
+        self
+    }
+
+    pub(crate) fn quirks(mut self, quirks: Quirks) -> GoldenRun {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    /// Writes `value` to `address` before the program starts.
+    pub(crate) fn poke(mut self, address: u16, value: u8) -> GoldenRun {
+        self.pokes.push((address, value));
+        self
+    }
+
+    pub(crate) fn frames(mut self, frames: u64) -> GoldenRun {
+        self.frames = frames;
+        self
+    }
+
+    /// Holds `keys` down from `frame` on, until the next change.
+    pub(crate) fn keys_from(mut self, frame: u64, keys: &[usize]) -> GoldenRun {
+        let bits = keys.iter().fold(0, |bits, key| bits | 1 << key);
+        self.inputs.push((frame, bits));
+        self
+    }
+
+    /// Runs the program through the headless runner, the script played back as a movie.
+    pub(crate) fn run(&self) -> System {
+        let quirks = self.quirks.unwrap_or_else(|| self.variant.quirks());
+        let mut movie = Movie::new(&self.program, self.variant, quirks, DEFAULT_SEED, Timing::default());
+        for frame in 0..self.frames {
+            let keys = self.inputs.iter().rev().find(|&&(from, _)| from <= frame).map(|&(_, keys)| keys);
+            movie.record(keys.unwrap_or(0));
+        }
+
+        let mut chip = System::new(self.variant);
+        chip.setup_fontset();
+        chip.load_program_bytes(&self.program);
+        for &(address, value) in self.pokes.iter() {
+            chip.memory[address as usize] = value;
+        }
+        movie.prepare(&mut chip);
+
+        headless::run(&mut chip, self.frames, movie.timing, Some(&movie)).unwrap();
+        chip
+    }
+
+    /// Runs the program and compares the screen with the golden file, or with `BLESS=1` in the
+    /// environment writes the screen as the new golden file.
+    pub(crate) fn check(&self) {
+        let actual = screen_to_text(&self.run());
+        let path = PathBuf::from(GOLDEN_DIRECTORY).join(format!("{}.txt", self.name));
+
+        if env::var("BLESS").map(|bless| bless == "1").unwrap_or(false) {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &actual).unwrap();
+            return;
+        }
+
+        let expected = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("could not read {}: {}, run with BLESS=1 to create it", path.display(), e));
+        if expected != actual {
+            panic!("{} does not match the screen, run with BLESS=1 if the change is intended\n{}",
+                   path.display(), visual_diff(&expected, &actual));
+        }
+    }
+}
+
+/// The two screens one above the other, then a map of where they differ: `+` for pixels only
+/// lit in the actual screen, `-` for ones only lit in the expected one and `*` where both are lit
+/// in different colours.
+pub(crate) fn visual_diff(expected: &str, actual: &str) -> String {
+    let mut diff = format!("expected:\n{}actual:\n{}differences:\n", expected, actual);
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+
+    for row in 0..expected_lines.len().max(actual_lines.len()) {
+        let expected_row: Vec<char> = expected_lines.get(row).map(|line| line.chars().collect()).unwrap_or_default();
+        let actual_row: Vec<char> = actual_lines.get(row).map(|line| line.chars().collect()).unwrap_or_default();
+
+        for column in 0..expected_row.len().max(actual_row.len()) {
+            let before = expected_row.get(column).copied().unwrap_or('.');
+            let after = actual_row.get(column).copied().unwrap_or('.');
+            diff.push(match (before, after) {
+                _ if before == after => ' ',
+                ('.', _) => '+',
+                (_, '.') => '-',
+                _ => '*',
+            });
+        }
+        diff.push('\n');
+    }
+
+    diff
+}
+
+#[test]
+fn test_visual_diff() {
+    let diff = visual_diff("#.#\n...\n", "#..\n.#+\n");
+
+    assert!(diff.starts_with("expected:\n#.#\n...\nactual:\n#..\n.#+\n"));
+    assert!(diff.ends_with("differences:\n  -\n ++\n"));
+    assert!(visual_diff("#@\n", "#+\n").ends_with("differences:\n *\n"));
+}
+
+#[test]
+fn test_pong_attract() {
+    GoldenRun::new("pong", include_bytes!("../../pong.rom")).frames(120).check();
+}
+
+#[test]
+fn test_pong_paddle_moves_up() {
+    //Pong waits 96 frames before the ball is served, then 1 moves the left paddle up
+    GoldenRun::new("pong_paddle", include_bytes!("../../pong.rom"))
+        .keys_from(100, &[0x1])
+        .keys_from(110, &[])
+        .frames(130)
+        .check();
+}
+
+#[test]
+fn test_super_chip_big_digits() {
+    GoldenRun::octo("schip_big_digits", "
+        : main
+            hires
+            v0 := 0
+            v1 := 2
+            v2 := 0
+            loop
+                i := bighex v2
+                sprite v0 v1 10
+                v0 += 10
+                v2 += 1
+                if v2 == 10 then jump done
+            again
+        : done
+            scroll-down 4
+            scroll-right
+        : spin
+            jump spin
+    ").variant(Variant::SuperChip).frames(10).check();
+}
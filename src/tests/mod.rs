@@ -1 +1,21 @@
-mod chip8_cpu;
\ No newline at end of file
+mod chip8_cpu;
+mod assembler;
+mod octo;
+mod trace;
+mod tracediff;
+mod savestate;
+mod rewind;
+mod movie;
+mod variant;
+mod timing;
+mod cdp1802;
+mod vip;
+mod font;
+mod bus;
+mod display;
+mod antiflicker;
+mod screenshot;
+mod video;
+mod golden;
+mod conformance;
+mod opcodes;
@@ -0,0 +1,108 @@
+use crate::chip8_cpu::System;
+use crate::font::FontSet;
+use crate::headless;
+use crate::movie::{Movie, MovieError, MOVIE_VERSION};
+use crate::quirks::Quirks;
+use crate::timing::Timing;
+use crate::variant::Variant;
+
+//Waits for a key, keeps a random number, counts in V2 if 5 is still held and waits again
+const PROGRAM: [u8; 14] = [0xF0, 0x0A, 0xC1, 0xFF, 0x65, 0x05, 0xE5, 0xA1, 0x72, 0x01, 0x72, 0x01, 0xF3, 0x0A];
+
+fn fresh_system() -> System {
+    let mut chip = System::default();
+    chip.load_program_bytes(&PROGRAM);
+    chip.setup_fontset();
+    chip
+}
+
+#[test]
+fn test_record_only_keeps_changes() {
+    let mut movie = Movie::new(&PROGRAM, Variant::Chip8, Quirks::default(), 1234, Timing::Fixed(10));
+    for &keys in [0x0000, 0x0000, 0x0020, 0x0020, 0x0000].iter() {
+        movie.record(keys);
+    }
+
+    assert_eq!(movie.frames, 5);
+    assert_eq!(movie.changes(), &[(0, 0x0000), (2, 0x0020), (4, 0x0000)]);
+    assert_eq!(movie.keys_at(1), 0x0000);
+    assert_eq!(movie.keys_at(3), 0x0020);
+    assert_eq!(movie.keys_at(100), 0x0000);
+}
+
+#[test]
+fn test_text_round_trip() {
+    let quirks = Quirks { jump_uses_vx: true, ..Quirks::default() };
+    let mut movie = Movie::new(&PROGRAM, Variant::Chip8, quirks, 0xDEAD_BEEF, Timing::Vip);
+    movie.record(0x0001);
+    movie.record(0x8001);
+
+    let text = movie.to_text();
+    assert!(text.starts_with(&format!("chip8-movie {}\n", MOVIE_VERSION)));
+    assert_eq!(Movie::parse(&text).unwrap(), movie);
+}
+
+#[test]
+fn test_parse_errors() {
+    assert!(matches!(Movie::parse("not a movie"), Err(MovieError::NotAMovie)));
+    assert!(matches!(Movie::parse("chip8-movie 99"), Err(MovieError::UnsupportedVersion(99))));
+
+    let text = "chip8-movie 4\nrom 00\nvariant chip8\nfont 00\nfont-address 000\nquirks 3b\nseed 1\nspeed 10\n\
+                frames 4\n2 0001\n1 0000\n";
+    assert!(matches!(Movie::parse(text), Err(MovieError::Invalid(11, _))));
+    assert!(matches!(Movie::parse("chip8-movie 4\nrom 00\n"), Err(MovieError::Invalid(_, _))));
+    let text = "chip8-movie 4\nrom 00\nvariant chip8\nquirks 3b\nseed 1\nspeed 10\nframes 4\n";
+    assert!(matches!(Movie::parse(text), Err(MovieError::Invalid(_, _))));
+}
+
+#[test]
+fn test_wrong_rom_is_refused() {
+    let movie = Movie::new(&PROGRAM, Variant::Chip8, Quirks::default(), 1, Timing::Fixed(10));
+
+    assert!(movie.check_rom(&PROGRAM).is_ok());
+    assert!(matches!(movie.check_rom(&PROGRAM[..12]), Err(MovieError::WrongRom { .. })));
+    assert!(movie.check_variant(Variant::Chip8).is_ok());
+    assert!(matches!(movie.check_variant(Variant::Chip8X), Err(MovieError::WrongVariant { .. })));
+}
+
+#[test]
+fn test_wrong_font_is_refused() {
+    let movie = Movie::new(&PROGRAM, Variant::Chip8, Quirks::default(), 1, Timing::Fixed(10))
+        .with_font(&FontSet::Dream6800.font(), 0x050);
+    let movie = Movie::parse(&movie.to_text()).unwrap();
+
+    assert!(movie.check_font(&FontSet::Dream6800.font(), 0x050).is_ok());
+    assert!(matches!(movie.check_font(&FontSet::Vip.font(), 0x050), Err(MovieError::WrongFont { .. })));
+    assert!(matches!(movie.check_font(&FontSet::Dream6800.font(), 0x000),
+                     Err(MovieError::WrongFontAddress { expected: 0x050, found: 0x000 })));
+
+    //Without with_font the movie expects what a fresh system has
+    let chip = fresh_system();
+    let movie = Movie::new(&PROGRAM, Variant::Chip8, Quirks::default(), 1, Timing::Fixed(10));
+    assert!(movie.check_font(&chip.font, chip.font_address).is_ok());
+}
+
+#[test]
+fn test_playback_matches_recording() {
+    let mut movie = Movie::new(&PROGRAM, Variant::Chip8, Quirks::default(), 0x1357_9BDF, Timing::Fixed(2));
+    let mut recorded = fresh_system();
+    movie.prepare(&mut recorded);
+
+    //Nothing held for a while, then 5 held and released
+    for frame in 0..20 {
+        recorded.set_keypad_bits(if (5..7).contains(&frame) { 0x0020 } else { 0 });
+        movie.record(recorded.keypad_bits());
+        movie.timing.run_frame(&mut recorded).unwrap();
+    }
+
+    let mut played = fresh_system();
+    let movie = Movie::parse(&movie.to_text()).unwrap();
+    movie.prepare(&mut played);
+    let frames = headless::run(&mut played, 1000, Timing::Fixed(10), Some(&movie)).unwrap();
+
+    assert_eq!(frames, 20);
+    assert_eq!(played.save_state(), recorded.save_state());
+    assert_eq!(played.registers[0], 5);
+    assert_eq!(played.registers[2], 2);
+    assert_eq!(played.program_counter, 0x20C);
+}
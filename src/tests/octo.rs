@@ -0,0 +1,154 @@
+use crate::octo::compile;
+use crate::chip8_cpu::System;
+
+#[test]
+fn test_main_first_needs_no_jump() {
+    let program = compile(": main  v0 := 5  v1 += v0  i := 0x300  return").unwrap();
+
+    assert_eq!(program, vec![0x60, 0x05, 0x81, 0x04, 0xA3, 0x00, 0x00, 0xEE]);
+}
+
+#[test]
+fn test_jump_to_main_and_calls() {
+    let program = compile("
+        : draw-thing   # called before it is used
+            sprite v0 v1 5
+            ;
+        : main
+            draw-thing
+            jump main
+    ").unwrap();
+
+    assert_eq!(program, vec![0x12, 0x06, 0xD0, 0x15, 0x00, 0xEE, 0x22, 0x02, 0x12, 0x06]);
+}
+
+#[test]
+fn test_forward_reference() {
+    let program = compile(": main  i := data  later  : later  ;  : data  0xF0 0x90").unwrap();
+
+    assert_eq!(program, vec![0xA2, 0x06, 0x22, 0x04, 0x00, 0xEE, 0xF0, 0x90]);
+}
+
+#[test]
+fn test_if_then_and_begin_else() {
+    let program = compile("
+        : main
+            if v0 == 3 then v1 := 1
+            if v2 key begin
+                v3 := 1
+            else
+                v3 := 2
+            end
+    ").unwrap();
+
+    assert_eq!(program, vec![
+        0x40, 0x03, 0x61, 0x01,
+        0xE2, 0x9E, 0x12, 0x0C,
+        0x63, 0x01, 0x12, 0x0E,
+        0x63, 0x02,
+    ]);
+}
+
+#[test]
+fn test_loop_while_again() {
+    let program = compile(": main  loop  v0 += 1  while v0 != 10  again").unwrap();
+
+    assert_eq!(program, vec![0x70, 0x01, 0x40, 0x0A, 0x12, 0x08, 0x12, 0x00]);
+}
+
+#[test]
+fn test_macro_calc_alias_const() {
+    let program = compile("
+        :const SPEED 2
+        :alias ball-x v4
+        :calc DOUBLE { SPEED * 2 + 1 }
+        :macro move reg amount { reg += amount }
+        : main
+            move ball-x SPEED
+            ball-x := DOUBLE
+            :byte { 1 + 2 * 3 }
+    ").unwrap();
+
+    //Right to left without precedence, 2 * (2 + 1) and 1 + (2 * 3)
+    assert_eq!(program, vec![0x74, 0x02, 0x64, 0x06, 0x07]);
+}
+
+#[test]
+fn test_comparisons_run() {
+    let program = compile("
+        : main
+            v0 := 5
+            v1 := 9
+            v2 := 0
+            if v0 < v1 then v2 += 1
+            if v1 > v0 then v2 += 1
+            if v0 >= 5 then v2 += 1
+            if v0 <= 4 then v2 += 1
+            if v0 < 5 then v2 += 1
+    ").unwrap();
+
+    //Run it on the interpreter until it steps off the end
+    let mut chip = System::default();
+    chip.load_program_bytes(&program);
+    while (chip.program_counter as usize) < 0x200 + program.len() {
+        chip.step().unwrap();
+    }
+
+    assert_eq!(chip.registers[2], 3);
+}
+
+#[test]
+fn test_loads_into_system() {
+    let mut chip = System::default();
+    chip.load_program_bytes(&compile(": main  va := 2").unwrap());
+
+    chip.run_op_at(0x200).unwrap();
+
+    assert_eq!(chip.registers[0xA], 2);
+}
+
+#[test]
+fn test_errors() {
+    let error = compile(": main\n  v0 := nowhere\n").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 9));
+
+    let error = compile(": main\n  missing\n").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 3));
+
+    let error = compile(": start  ;").unwrap_err();
+    assert!(error.message.contains("main"));
+
+    let error = compile(": main  loop  v0 += 1").unwrap_err();
+    assert!(error.message.contains("again"));
+}
+
+#[test]
+fn test_super_chip_statements() {
+    let program = compile(": main  hires  scroll-down 3  scroll-left  scroll-right  i := bighex v2  saveflags v4  loadflags v4  lores  exit").unwrap();
+
+    assert_eq!(program, vec![0x00, 0xFF, 0x00, 0xC3, 0x00, 0xFC, 0x00, 0xFB, 0xF2, 0x30,
+                             0xF4, 0x75, 0xF4, 0x85, 0x00, 0xFE, 0x00, 0xFD]);
+}
+
+#[test]
+fn test_xo_chip_statements() {
+    let program = compile(": main  i := long data  plane 3  audio  pitch := v1  save v2 - v4  load v4 - v2  scroll-up 2  : data").unwrap();
+
+    assert_eq!(program, vec![0xF0, 0x00, 0x02, 0x10, 0xF3, 0x01, 0xF0, 0x02, 0xF1, 0x3A,
+                             0x52, 0x42, 0x54, 0x23, 0x00, 0xD2]);
+}
+
+#[test]
+fn test_jumps_past_4k_are_errors() {
+    //Far enough into XO-CHIP's memory that a 1NNN can't reach
+    let error = compile(": main\n:org 0x1000\nloop\n  v0 += 1\nagain\n").unwrap_err();
+    assert_eq!((error.line, error.column), (5, 1));
+    assert!(error.message.contains("12 bits"));
+
+    //A branch whose end lands past 0xFFF
+    let error = compile(": main\n:org 0xFFA\nif v0 == 1 begin\n  v1 := 2\n  v1 := 3\nend\n").unwrap_err();
+    assert_eq!((error.line, error.column), (6, 1));
+
+    let error = compile(": main\n:org 0xFFA\nloop\n  while v0 != 1\n  v1 := 2\n  v1 := 3\nagain\n").unwrap_err();
+    assert_eq!((error.line, error.column), (7, 1));
+}
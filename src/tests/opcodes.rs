@@ -0,0 +1,610 @@
+use crate::chip8_cpu::{CpuError, System};
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::variant::Variant;
+
+type Setup = Box<dyn Fn(&mut System)>;
+
+/// A system set up a field at a time, then a few opcodes run on it:
+/// `Chip8Test::new().regs(&[0, 0xFF, 0x01]).run("8124").expect_v(1, 0x00).expect_vf(1)`.
+pub(crate) struct Chip8Test {
+    variant: Variant,
+    //None for the variant's own
+    quirks: Option<Quirks>,
+    registers: Vec<(usize, u8)>,
+    index_register: Option<u16>,
+    stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    keys: Vec<usize>,
+    memory: Vec<(u16, Vec<u8>)>,
+    //At most this many instructions run, by default one for each opcode given
+    steps: Option<usize>,
+    setup: Vec<Setup>,
+}
+
+/// The system once the opcodes have run, to check with the `expect_` methods.
+pub(crate) struct Chip8Run {
+    system: System,
+    result: Result<(), CpuError>,
+}
+
+impl Chip8Test {
+    pub(crate) fn new() -> Chip8Test {
+        Chip8Test {
+            variant: Variant::Chip8,
+            quirks: None,
+            registers: Vec::new(),
+            index_register: None,
+            stack: Vec::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            keys: Vec::new(),
+            memory: Vec::new(),
+            steps: None,
+            setup: Vec::new(),
+        }
+    }
+
+    pub(crate) fn variant(mut self, variant: Variant) -> Chip8Test {
+        self.variant = variant;
+        self
+    }
+
+    pub(crate) fn quirks(mut self, quirks: Quirks) -> Chip8Test {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    /// Sets V0 onwards to `values`.
+    pub(crate) fn regs(mut self, values: &[u8]) -> Chip8Test {
+        self.registers.extend(values.iter().copied().enumerate());
+        self
+    }
+
+    pub(crate) fn v(mut self, register: usize, value: u8) -> Chip8Test {
+        self.registers.push((register, value));
+        self
+    }
+
+    pub(crate) fn i(mut self, address: u16) -> Chip8Test {
+        self.index_register = Some(address);
+        self
+    }
+
+    /// Fills the stack from the bottom, leaving the stack pointer just above it.
+    pub(crate) fn stack(mut self, addresses: &[u16]) -> Chip8Test {
+        self.stack = addresses.to_vec();
+        self
+    }
+
+    pub(crate) fn delay(mut self, delay_timer: u8) -> Chip8Test {
+        self.delay_timer = delay_timer;
+        self
+    }
+
+    pub(crate) fn sound(mut self, sound_timer: u8) -> Chip8Test {
+        self.sound_timer = sound_timer;
+        self
+    }
+
+    pub(crate) fn keys(mut self, keys: &[usize]) -> Chip8Test {
+        self.keys = keys.to_vec();
+        self
+    }
+
+    pub(crate) fn memory(mut self, address: u16, bytes: &[u8]) -> Chip8Test {
+        self.memory.push((address, bytes.to_vec()));
+        self
+    }
+
+    pub(crate) fn steps(mut self, steps: usize) -> Chip8Test {
+        self.steps = Some(steps);
+        self
+    }
+
+    /// Anything the other methods don't cover, done to the system just before it runs.
+    pub(crate) fn setup<F: Fn(&mut System) + 'static>(mut self, setup: F) -> Chip8Test {
+        self.setup.push(Box::new(setup));
+        self
+    }
+
+    /// Loads `program`, opcodes as hex words separated by spaces, and runs it until it leaves the
+    /// program, an instruction fails or it has run as many instructions as there are words.
+    pub(crate) fn run(&self, program: &str) -> Chip8Run {
+        let bytes: Vec<u8> = program.split_whitespace().flat_map(|word| {
+            assert_eq!(word.len(), 4, "{} is not a 16 bit opcode", word);
+            u16::from_str_radix(word, 16).unwrap().to_be_bytes().to_vec()
+        }).collect();
+
+        let mut system = System::new(self.variant);
+        system.setup_fontset();
+        system.load_program_bytes(&bytes);
+        if let Some(quirks) = self.quirks {
+            system.quirks = quirks;
+        }
+        for &(register, value) in self.registers.iter() {
+            system.registers[register] = value;
+        }
+        if let Some(address) = self.index_register {
+            system.index_register = address;
+        }
+        system.stack[..self.stack.len()].copy_from_slice(&self.stack);
+        system.stack_pointer = self.stack.len() as u8;
+        system.delay_timer = self.delay_timer;
+        system.sound_timer = self.sound_timer;
+        for &key in self.keys.iter() {
+            system.key[key] = 1;
+        }
+        for (address, data) in self.memory.iter() {
+            let start = *address as usize;
+            system.memory[start..start + data.len()].copy_from_slice(data);
+        }
+        for setup in self.setup.iter() {
+            setup(&mut system);
+        }
+
+        let start = self.variant.program_start();
+        let end = start + bytes.len() as u16;
+        let mut result = Ok(());
+        for _ in 0..self.steps.unwrap_or(bytes.len() / 2) {
+            if !(start..end).contains(&system.program_counter) || system.exited {
+                break;
+            }
+            result = system.step();
+            if result.is_err() {
+                break;
+            }
+        }
+
+        Chip8Run { system, result }
+    }
+}
+
+impl Chip8Run {
+    /// The system after the run, which must not have failed.
+    #[track_caller]
+    pub(crate) fn system(&self) -> &System {
+        if let Err(e) = &self.result {
+            panic!("the program failed: {}", e);
+        }
+        &self.system
+    }
+
+    #[track_caller]
+    pub(crate) fn expect_v(self, register: usize, value: u8) -> Chip8Run {
+        assert_eq!(self.system().registers[register], value, "V{:X}", register);
+        self
+    }
+
+    #[track_caller]
+    pub(crate) fn expect_vf(self, value: u8) -> Chip8Run {
+        self.expect_v(0xF, value)
+    }
+
+    #[track_caller]
+    pub(crate) fn expect_i(self, address: u16) -> Chip8Run {
+        assert_eq!(self.system().index_register, address, "I");
+        self
+    }
+
+    #[track_caller]
+    pub(crate) fn expect_pc(self, address: u16) -> Chip8Run {
+        assert_eq!(self.system().program_counter, address, "the program counter");
+        self
+    }
+
+    /// The stack from the bottom up to the stack pointer.
+    #[track_caller]
+    pub(crate) fn expect_stack(self, addresses: &[u16]) -> Chip8Run {
+        let system = self.system();
+        assert_eq!(&system.stack[..system.stack_pointer as usize], addresses, "the stack");
+        self
+    }
+
+    #[track_caller]
+    pub(crate) fn expect_memory(self, address: u16, bytes: &[u8]) -> Chip8Run {
+        let start = address as usize;
+        assert_eq!(&self.system().memory[start..start + bytes.len()], bytes, "memory at {:03X}", address);
+        self
+    }
+
+    #[track_caller]
+    pub(crate) fn expect_delay(self, delay_timer: u8) -> Chip8Run {
+        assert_eq!(self.system().delay_timer, delay_timer, "the delay timer");
+        self
+    }
+
+    #[track_caller]
+    pub(crate) fn expect_sound(self, sound_timer: u8) -> Chip8Run {
+        assert_eq!(self.system().sound_timer, sound_timer, "the sound timer");
+        self
+    }
+
+    #[track_caller]
+    pub(crate) fn expect_pixel(self, x: usize, y: usize, value: u8) -> Chip8Run {
+        assert_eq!(self.system().display.get(x, y), value, "the pixel at {},{}", x, y);
+        self
+    }
+
+    #[track_caller]
+    pub(crate) fn expect_lit(self, count: usize) -> Chip8Run {
+        let lit = self.system().display.rows().flatten().filter(|&&pixel| pixel != 0).count();
+        assert_eq!(lit, count, "lit pixels");
+        self
+    }
+
+    /// Checks the run failed with `error`, giving back the system as the failure left it.
+    #[track_caller]
+    pub(crate) fn expect_error(self, error: CpuError) -> System {
+        assert_eq!(self.result, Err(error));
+        self.system
+    }
+}
+
+#[test]
+fn test_00e0_clear_screen() {
+    Chip8Test::new()
+        .setup(|system| system.display.set(10, 10, 1))
+        .run("00E0")
+        .expect_lit(0)
+        .expect_pc(0x202);
+}
+
+#[test]
+fn test_00ee_return() {
+    Chip8Test::new().stack(&[0x300, 0x1FE]).steps(1).run("00EE")
+        .expect_stack(&[0x300])
+        .expect_pc(0x200);
+}
+
+#[test]
+fn test_0nnn_machine_code() {
+    Chip8Test::new().run("0123")
+        .expect_error(CpuError::MachineCode { address: 0x200, routine: 0x123 });
+    Chip8Test::new()
+        .setup(|system| system.register_routine(0x123, |system| system.registers[0] = 0x42))
+        .run("0123")
+        .expect_v(0, 0x42)
+        .expect_pc(0x202);
+}
+
+#[test]
+fn test_1nnn_jump() {
+    Chip8Test::new().run("1345").expect_pc(0x345).expect_stack(&[]);
+}
+
+#[test]
+fn test_2nnn_call() {
+    Chip8Test::new().run("2345").expect_pc(0x345).expect_stack(&[0x200]);
+}
+
+#[test]
+fn test_stack_at_depth_16() {
+    //Each call goes to the next one, leaving every return address on the stack
+    let calls: Vec<String> = (1..=16).map(|call| format!("2{:03X}", 0x200 + call * 2)).collect();
+    let expected: Vec<u16> = (0..16).map(|call| 0x200 + call * 2).collect();
+    Chip8Test::new().run(&format!("{} 1220", calls.join(" ")))
+        .expect_stack(&expected)
+        .expect_pc(0x220);
+
+    //And unwinds all the way back down, the return landing on the 00EE each time
+    Chip8Test::new().stack(&[0x1FE; 16]).steps(16).run("00EE")
+        .expect_stack(&[])
+        .expect_pc(0x200);
+}
+
+#[test]
+fn test_stack_overflow_and_underflow() {
+    //A 17th call is an error, left on the call with the stack as it was
+    let system = Chip8Test::new().stack(&[0x300; 16]).run("2400").expect_error(CpuError::StackOverflow { address: 0x200 });
+    assert_eq!(system.stack_pointer, 16);
+    assert_eq!(system.program_counter, 0x200);
+
+    let system = Chip8Test::new().run("00EE").expect_error(CpuError::StackUnderflow { address: 0x200 });
+    assert_eq!(system.stack_pointer, 0);
+    assert_eq!(system.program_counter, 0x200);
+}
+
+#[test]
+fn test_3xnn_skip_if_equal() {
+    Chip8Test::new().v(3, 0x42).run("3342").expect_pc(0x204);
+    Chip8Test::new().v(3, 0x41).run("3342").expect_pc(0x202);
+}
+
+#[test]
+fn test_4xnn_skip_if_not_equal() {
+    Chip8Test::new().v(3, 0x41).run("4342").expect_pc(0x204);
+    Chip8Test::new().v(3, 0x42).run("4342").expect_pc(0x202);
+}
+
+#[test]
+fn test_5xy0_skip_if_registers_equal() {
+    Chip8Test::new().regs(&[7, 7]).run("5010").expect_pc(0x204);
+    Chip8Test::new().regs(&[7, 8]).run("5010").expect_pc(0x202);
+}
+
+#[test]
+fn test_9xy0_skip_if_registers_not_equal() {
+    Chip8Test::new().regs(&[7, 8]).run("9010").expect_pc(0x204);
+    Chip8Test::new().regs(&[7, 7]).run("9010").expect_pc(0x202);
+}
+
+#[test]
+fn test_skip_over_long_index() {
+    //F000 NNNN is four bytes, so XO-CHIP skips it whole
+    Chip8Test::new().variant(Variant::XoChip).steps(1).run("3000 F000 1234").expect_pc(0x206);
+    //Anywhere else F0 00 is data and the skip only steps over it
+    Chip8Test::new().steps(1).run("3000 F000 1234").expect_pc(0x204);
+    Chip8Test::new().variant(Variant::SuperChip).steps(1).run("3000 F000 1234").expect_pc(0x204);
+}
+
+#[test]
+fn test_6xnn_set() {
+    Chip8Test::new().run("6A02").expect_v(0xA, 0x02);
+}
+
+#[test]
+fn test_7xnn_add_wraps_without_carry() {
+    Chip8Test::new().regs(&[0xFF]).v(0xF, 5).run("7002")
+        .expect_v(0, 0x01)
+        .expect_vf(5);
+}
+
+#[test]
+fn test_8xy0_copy() {
+    Chip8Test::new().regs(&[1, 0x99]).run("8010").expect_v(0, 0x99).expect_v(1, 0x99);
+}
+
+#[test]
+fn test_8xy1_8xy2_8xy3_logic() {
+    Chip8Test::new().regs(&[0b1100, 0b1010]).v(0xF, 5).run("8011").expect_v(0, 0b1110).expect_vf(0);
+    Chip8Test::new().regs(&[0b1100, 0b1010]).v(0xF, 5).run("8012").expect_v(0, 0b1000).expect_vf(0);
+    Chip8Test::new().regs(&[0b1100, 0b1010]).v(0xF, 5).run("8013").expect_v(0, 0b0110).expect_vf(0);
+    //SUPER-CHIP leaves VF alone
+    Chip8Test::new().quirks(Quirks::super_chip()).regs(&[0b1100, 0b1010]).v(0xF, 5).run("8011")
+        .expect_v(0, 0b1110)
+        .expect_vf(5);
+}
+
+#[test]
+fn test_8xy4_add_with_carry() {
+    Chip8Test::new().regs(&[0, 0xFF, 0x01]).run("8124").expect_v(1, 0x00).expect_vf(1);
+    Chip8Test::new().regs(&[0, 0xFE, 0x01]).run("8124").expect_v(1, 0xFF).expect_vf(0);
+    //The flag wins over the sum when VF is VX
+    Chip8Test::new().v(0xF, 0xFF).v(1, 0x01).run("8F14").expect_vf(1);
+    Chip8Test::new().v(0xF, 0x01).v(1, 0x01).run("8F14").expect_vf(0);
+}
+
+#[test]
+fn test_8xy5_subtract_with_borrow() {
+    Chip8Test::new().regs(&[0x10, 0x20]).run("8015").expect_v(0, 0xF0).expect_vf(0);
+    Chip8Test::new().regs(&[0x20, 0x10]).run("8015").expect_v(0, 0x10).expect_vf(1);
+    //No borrow when they are equal
+    Chip8Test::new().regs(&[0x20, 0x20]).run("8015").expect_v(0, 0x00).expect_vf(1);
+    Chip8Test::new().v(0xF, 0x10).v(1, 0x20).run("8F15").expect_vf(0);
+}
+
+#[test]
+fn test_8xy7_reverse_subtract() {
+    Chip8Test::new().regs(&[0x20, 0x10]).run("8017").expect_v(0, 0xF0).expect_vf(0);
+    Chip8Test::new().regs(&[0x10, 0x20]).run("8017").expect_v(0, 0x10).expect_vf(1);
+    Chip8Test::new().regs(&[0x20, 0x20]).run("8017").expect_v(0, 0x00).expect_vf(1);
+}
+
+#[test]
+fn test_8xy6_shift_right() {
+    //The VIP shifts VY into VX, the bit shifted out going to VF
+    Chip8Test::new().regs(&[0xF0, 0x03]).run("8016").expect_v(0, 0x01).expect_v(1, 0x03).expect_vf(1);
+    Chip8Test::new().regs(&[0xF0, 0x02]).run("8016").expect_v(0, 0x01).expect_vf(0);
+    //SUPER-CHIP shifts VX in place
+    Chip8Test::new().quirks(Quirks::super_chip()).regs(&[0x03, 0xF0]).run("8016")
+        .expect_v(0, 0x01)
+        .expect_vf(1);
+    Chip8Test::new().v(0xF, 0x03).run("8FF6").expect_vf(1);
+}
+
+#[test]
+fn test_8xye_shift_left() {
+    Chip8Test::new().regs(&[0x00, 0x81]).run("801E").expect_v(0, 0x02).expect_vf(1);
+    Chip8Test::new().regs(&[0x00, 0x41]).run("801E").expect_v(0, 0x82).expect_vf(0);
+    Chip8Test::new().quirks(Quirks::super_chip()).regs(&[0x81, 0x00]).run("801E")
+        .expect_v(0, 0x02)
+        .expect_vf(1);
+    Chip8Test::new().v(0xF, 0x80).run("8FFE").expect_vf(1);
+}
+
+#[test]
+fn test_annn_set_index() {
+    Chip8Test::new().run("A2EA").expect_i(0x2EA);
+}
+
+#[test]
+fn test_bnnn_jump_with_offset() {
+    Chip8Test::new().regs(&[0x10, 0x20]).run("B300").expect_pc(0x310);
+    //SUPER-CHIP adds VX, here V3
+    Chip8Test::new().quirks(Quirks::super_chip()).regs(&[0x10, 0, 0, 0x20]).run("B300").expect_pc(0x320);
+    //And wraps around the end of memory
+    Chip8Test::new().regs(&[0xFF]).run("BFFF").expect_pc(0x0FE);
+}
+
+#[test]
+fn test_cxnn_random() {
+    Chip8Test::new().regs(&[0xFF]).run("C000").expect_v(0, 0);
+
+    let expected = Rng::new(1).next_u8() & 0x0F;
+    Chip8Test::new().setup(|system| system.rng = Rng::new(1)).run("C00F").expect_v(0, expected);
+}
+
+#[test]
+fn test_dxyn_draw_and_collide() {
+    //The 0 glyph is 4 wide and 5 high, 14 pixels lit
+    Chip8Test::new().i(0x300).memory(0x300, &[0xF0, 0x90, 0x90, 0x90, 0xF0]).run("D015")
+        .expect_lit(14)
+        .expect_pixel(0, 0, 1)
+        .expect_pixel(1, 1, 0)
+        .expect_vf(0);
+    //Drawing it again erases it and reports the collision
+    Chip8Test::new().i(0x300).memory(0x300, &[0xF0, 0x90, 0x90, 0x90, 0xF0]).run("D015 D015")
+        .expect_lit(0)
+        .expect_vf(1);
+}
+
+#[test]
+fn test_dxyn_edges() {
+    //The VIP clips at the edges, but where the sprite starts wraps
+    Chip8Test::new().regs(&[62, 30]).i(0x300).memory(0x300, &[0xFF, 0xFF, 0xFF]).run("D013")
+        .expect_lit(4)
+        .expect_pixel(63, 31, 1)
+        .expect_pixel(0, 0, 0);
+    Chip8Test::new().regs(&[64 + 2, 32 + 1]).i(0x300).memory(0x300, &[0x80]).run("D011")
+        .expect_pixel(2, 1, 1);
+    //XO-CHIP wraps the rest of the sprite around
+    Chip8Test::new().variant(Variant::XoChip).regs(&[62, 31]).i(0x300).memory(0x300, &[0xC0, 0xC0]).run("D012")
+        .expect_lit(4)
+        .expect_pixel(63, 0, 1);
+}
+
+#[test]
+fn test_ex9e_exa1_keys() {
+    Chip8Test::new().regs(&[0x7]).keys(&[0x7]).run("E09E").expect_pc(0x204);
+    Chip8Test::new().regs(&[0x7]).run("E09E").expect_pc(0x202);
+    Chip8Test::new().regs(&[0x7]).run("E0A1").expect_pc(0x204);
+    Chip8Test::new().regs(&[0x7]).keys(&[0x7]).run("E0A1").expect_pc(0x202);
+    //Only the low nibble picks the key
+    Chip8Test::new().regs(&[0xF7]).keys(&[0x7]).run("E09E").expect_pc(0x204);
+}
+
+#[test]
+fn test_fx07_fx15_fx18_timers() {
+    Chip8Test::new().delay(0x33).sound(0x22).run("F507").expect_v(5, 0x33).expect_sound(0x22);
+    Chip8Test::new().regs(&[0x44]).run("F015").expect_delay(0x44);
+    Chip8Test::new().regs(&[0x55]).run("F018").expect_sound(0x55);
+}
+
+#[test]
+fn test_fx0a_wait_for_key() {
+    Chip8Test::new().steps(3).run("F30A").expect_pc(0x200).expect_v(3, 0);
+    Chip8Test::new().keys(&[0xB]).run("F30A").expect_pc(0x202).expect_v(3, 0xB);
+}
+
+#[test]
+fn test_fx1e_add_to_index() {
+    Chip8Test::new().i(0x300).regs(&[0x20]).run("F01E").expect_i(0x320).expect_vf(0);
+    //VF is left alone even past 0xFFF
+    Chip8Test::new().i(0xFFF).regs(&[0x01]).v(0xF, 5).run("F01E").expect_i(0x1000).expect_vf(5);
+}
+
+#[test]
+fn test_fx29_font_character() {
+    let run = Chip8Test::new().regs(&[0xA]).run("F029");
+    let expected = run.system().font_address + 0xA * 5;
+    run.expect_i(expected);
+    //Only the low nibble picks the digit
+    let run = Chip8Test::new().regs(&[0x1A]).run("F029");
+    let expected = run.system().font_address + 0xA * 5;
+    run.expect_i(expected);
+}
+
+#[test]
+fn test_fx33_bcd() {
+    Chip8Test::new().regs(&[255]).i(0x300).run("F033").expect_memory(0x300, &[2, 5, 5]).expect_i(0x300);
+    Chip8Test::new().regs(&[0]).i(0x300).run("F033").expect_memory(0x300, &[0, 0, 0]);
+    Chip8Test::new().regs(&[109]).i(0x300).run("F033").expect_memory(0x300, &[1, 0, 9]);
+}
+
+#[test]
+fn test_fx55_fx65_store_and_load() {
+    Chip8Test::new().regs(&[1, 2, 3, 4]).i(0x300).run("F255")
+        .expect_memory(0x300, &[1, 2, 3, 0])
+        .expect_i(0x303);
+    Chip8Test::new().i(0x300).memory(0x300, &[9, 8, 7, 6]).run("F265")
+        .expect_v(0, 9)
+        .expect_v(2, 7)
+        .expect_v(3, 0)
+        .expect_i(0x303);
+    //SUPER-CHIP leaves I where it was
+    Chip8Test::new().quirks(Quirks::super_chip()).regs(&[1, 2]).i(0x300).run("F155")
+        .expect_memory(0x300, &[1, 2])
+        .expect_i(0x300);
+    //F055 stores just V0
+    Chip8Test::new().regs(&[0xAB, 0xCD]).i(0x300).run("F055").expect_memory(0x300, &[0xAB, 0x00]).expect_i(0x301);
+}
+
+#[test]
+fn test_schip_screen_opcodes() {
+    let run = Chip8Test::new().variant(Variant::SuperChip).run("00FF");
+    assert!(run.system().hires);
+    assert_eq!(run.system().screen_width(), 128);
+    let run = Chip8Test::new().variant(Variant::SuperChip).run("00FF 00FE");
+    assert!(!run.system().hires);
+
+    let exited = Chip8Test::new().variant(Variant::SuperChip).run("00FD").expect_pc(0x200);
+    assert!(exited.system().exited);
+}
+
+#[test]
+fn test_schip_scrolling() {
+    let lit = |system: &mut System| system.display.set(10, 10, 1);
+    Chip8Test::new().variant(Variant::SuperChip).setup(lit).run("00C3")
+        .expect_pixel(10, 13, 1)
+        .expect_pixel(10, 10, 0);
+    Chip8Test::new().variant(Variant::SuperChip).setup(lit).run("00FB").expect_pixel(14, 10, 1);
+    Chip8Test::new().variant(Variant::SuperChip).setup(lit).run("00FC").expect_pixel(6, 10, 1);
+    Chip8Test::new().variant(Variant::XoChip).setup(lit).run("00D3").expect_pixel(10, 7, 1);
+}
+
+#[test]
+fn test_schip_big_sprites_and_font() {
+    Chip8Test::new().variant(Variant::SuperChip).i(0x300).memory(0x300, &[0xFF; 32]).run("00FF D010")
+        .expect_lit(256)
+        .expect_pixel(15, 15, 1)
+        .expect_vf(0);
+
+    let run = Chip8Test::new().variant(Variant::SuperChip).regs(&[0x3]).run("F030");
+    let expected = run.system().big_font_address() + 3 * 10;
+    run.expect_i(expected);
+}
+
+#[test]
+fn test_schip_flags() {
+    Chip8Test::new().variant(Variant::SuperChip).regs(&[1, 2, 3]).run("F175 6000 6100 F185")
+        .expect_v(0, 1)
+        .expect_v(1, 2);
+    //Only V0 to VX are saved
+    Chip8Test::new().variant(Variant::SuperChip).regs(&[1, 2, 3]).run("F175 F285").expect_v(2, 0);
+}
+
+#[test]
+fn test_xo_chip_register_ranges() {
+    Chip8Test::new().variant(Variant::XoChip).regs(&[0, 1, 2, 3]).i(0x300).run("5132")
+        .expect_memory(0x300, &[1, 2, 3])
+        .expect_i(0x300);
+    //From X down to Y when Y is lower
+    Chip8Test::new().variant(Variant::XoChip).regs(&[0, 1, 2, 3]).i(0x300).run("5312")
+        .expect_memory(0x300, &[3, 2, 1]);
+    Chip8Test::new().variant(Variant::XoChip).i(0x300).memory(0x300, &[7, 8]).run("5213")
+        .expect_v(2, 7)
+        .expect_v(1, 8);
+}
+
+#[test]
+fn test_xo_chip_long_index_planes_and_audio() {
+    Chip8Test::new().variant(Variant::XoChip).run("F000 ABCD").expect_i(0xABCD).expect_pc(0x204);
+
+    let run = Chip8Test::new().variant(Variant::XoChip).run("F201");
+    assert_eq!(run.system().planes, 2);
+
+    let pattern: Vec<u8> = (0..16).collect();
+    let run = Chip8Test::new().variant(Variant::XoChip).i(0x300).memory(0x300, &pattern).run("F002");
+    assert_eq!(run.system().audio_pattern[..], pattern[..]);
+
+    let run = Chip8Test::new().variant(Variant::XoChip).regs(&[0x70]).run("F03A");
+    assert_eq!(run.system().pitch, 0x70);
+}
+
+#[test]
+fn test_chip8x_add_nibbles() {
+    //Each nibble wraps around at 8
+    Chip8Test::new().variant(Variant::Chip8X).regs(&[0x35, 0x64]).run("5011").expect_v(0, 0x11);
+}
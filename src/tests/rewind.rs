@@ -0,0 +1,82 @@
+use crate::chip8_cpu::System;
+use crate::rewind::RewindBuffer;
+
+fn counting_system() -> System {
+    let mut chip = System::default();
+    //v0 += 1 over and over
+    let program: Vec<u8> = [0x70, 0x01].iter().cycle().take(100).cloned().collect();
+    chip.load_program_bytes(&program);
+    chip
+}
+
+#[test]
+fn test_rewind_frames() {
+    let mut chip = counting_system();
+    let mut rewind = RewindBuffer::new(100);
+
+    for _ in 0..5 {
+        rewind.push(&chip);
+        chip.run_frame(2).unwrap();
+    }
+    assert_eq!(chip.registers[0], 10);
+    assert_eq!(rewind.len(), 5);
+
+    assert!(rewind.rewind(&mut chip));
+    assert_eq!(chip.registers[0], 8);
+    assert!(rewind.rewind(&mut chip));
+    assert_eq!(chip.registers[0], 6);
+    assert_eq!(chip.program_counter, 0x20C);
+    assert_eq!(rewind.len(), 3);
+}
+
+#[test]
+fn test_step_back_single_instructions() {
+    let mut chip = counting_system();
+    let mut history = RewindBuffer::new(10);
+
+    for _ in 0..3 {
+        history.push(&chip);
+        chip.step().unwrap();
+    }
+    assert_eq!((chip.registers[0], chip.program_counter), (3, 0x206));
+
+    history.rewind(&mut chip);
+    assert_eq!((chip.registers[0], chip.program_counter), (2, 0x204));
+    history.rewind(&mut chip);
+    assert_eq!((chip.registers[0], chip.program_counter), (1, 0x202));
+    history.rewind(&mut chip);
+    assert_eq!((chip.registers[0], chip.program_counter), (0, 0x200));
+    assert!(!history.rewind(&mut chip));
+}
+
+#[test]
+fn test_capacity_drops_oldest() {
+    let mut chip = counting_system();
+    let mut rewind = RewindBuffer::new(3);
+
+    for _ in 0..10 {
+        rewind.push(&chip);
+        chip.run_frame(2).unwrap();
+    }
+    assert_eq!(rewind.len(), 3);
+
+    while rewind.rewind(&mut chip) {}
+    assert_eq!(chip.registers[0], 14);
+}
+
+#[test]
+fn test_rewind_restores_everything() {
+    let mut chip = counting_system();
+    chip.delay_timer = 10;
+    let mut rewind = RewindBuffer::new(10);
+
+    rewind.push(&chip);
+    let before = chip.save_state();
+    chip.run_frame(4).unwrap();
+    chip.display.set(3, 4, 1);
+    chip.memory[0x300] = 0xAA;
+
+    rewind.rewind(&mut chip);
+
+    assert_eq!(chip.save_state(), before);
+}
@@ -0,0 +1,86 @@
+use crate::chip8_cpu::System;
+use crate::savestate::{SnapshotError, SNAPSHOT_VERSION};
+
+fn busy_system() -> System {
+    let mut chip = System::default();
+    chip.setup_fontset();
+    chip.load_program_bytes(&[0x6A, 0x02, 0xC0, 0xFF, 0xA2, 0xEA]);
+    chip.run_op_at(0x200).unwrap();
+    chip.run_op_at(0x202).unwrap();
+    chip.stack[0] = 0x204;
+    chip.stack_pointer = 1;
+    chip.delay_timer = 30;
+    chip.sound_timer = 4;
+    chip.key[0xB] = 1;
+    chip.display.set(10, 20, 1);
+    chip.quirks.jump_uses_vx = true;
+
+    chip
+}
+
+#[test]
+fn test_round_trip() {
+    let mut original = busy_system();
+    let snapshot = original.save_state();
+
+    let mut restored = System::default();
+    restored.load_state(&snapshot).unwrap();
+
+    assert_eq!(restored.save_state(), snapshot);
+    assert_eq!(restored.registers, original.registers);
+    assert_eq!(restored.program_counter, original.program_counter);
+    assert_eq!(restored.key, original.key);
+    assert_eq!(restored.display.get(10, 20), 1);
+    assert_eq!(restored.quirks, original.quirks);
+
+    //Both carry on with the same random numbers
+    original.run_op_at(0x202).unwrap();
+    restored.run_op_at(0x202).unwrap();
+    assert_eq!(restored.registers[0], original.registers[0]);
+}
+
+#[test]
+fn test_round_trips_a_draw_waiting_for_the_display() {
+    let mut waiting = busy_system();
+    waiting.waiting_for_display = true;
+    let mut idle = busy_system();
+    idle.waiting_for_display = false;
+
+    let mut restored = System::default();
+    restored.load_state(&waiting.save_state()).unwrap();
+    assert!(restored.waiting_for_display);
+    restored.load_state(&idle.save_state()).unwrap();
+    assert!(!restored.waiting_for_display);
+}
+
+#[test]
+fn test_rejects_other_versions() {
+    let mut snapshot = busy_system().save_state();
+    let newer = (SNAPSHOT_VERSION + 1).to_le_bytes();
+    snapshot[4] = newer[0];
+    snapshot[5] = newer[1];
+
+    let mut chip = System::default();
+    chip.registers[3] = 7;
+
+    match chip.load_state(&snapshot) {
+        Err(SnapshotError::UnsupportedVersion(version)) => assert_eq!(version, SNAPSHOT_VERSION + 1),
+        other => panic!("expected a version error, got {:?}", other),
+    }
+    assert_eq!(chip.registers[3], 7);
+}
+
+#[test]
+fn test_rejects_bad_data_without_changes() {
+    let snapshot = busy_system().save_state();
+    let mut chip = System::default();
+
+    assert!(matches!(chip.load_state(b"nonsense"), Err(SnapshotError::NotASnapshot)));
+    assert!(matches!(chip.load_state(&snapshot[..snapshot.len() - 1]), Err(SnapshotError::Truncated)));
+
+    let mut longer = snapshot.clone();
+    longer.push(0);
+    assert!(matches!(chip.load_state(&longer), Err(SnapshotError::Invalid(_))));
+
+    assert_eq!(chip.save_state(), System::default().save_state());
+}
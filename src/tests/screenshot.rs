@@ -0,0 +1,106 @@
+use crate::chip8_cpu::System;
+use crate::screenshot::{self, ImageFormat};
+use crate::variant::Variant;
+
+//The chunks of a PNG after the signature, as (type, data)
+fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut chunks = Vec::new();
+    let mut position = 8;
+
+    while position < png.len() {
+        let mut length = [0; 4];
+        length.copy_from_slice(&png[position..position + 4]);
+        let length = u32::from_be_bytes(length) as usize;
+        let kind = String::from_utf8(png[position + 4..position + 8].to_vec()).unwrap();
+        chunks.push((kind, png[position + 8..position + 8 + length].to_vec()));
+        position += 12 + length;
+    }
+
+    chunks
+}
+
+//Undoes the stored deflate blocks the encoder writes
+fn unstore(zlib: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut position = 2;
+
+    loop {
+        let last = zlib[position] & 1 != 0;
+        let length = u16::from_le_bytes([zlib[position + 1], zlib[position + 2]]) as usize;
+        data.extend_from_slice(&zlib[position + 5..position + 5 + length]);
+        position += 5 + length;
+        if last {
+            return data;
+        }
+    }
+}
+
+#[test]
+fn test_format_from_path() {
+    assert_eq!(ImageFormat::from_path("shot.pbm"), ImageFormat::Pbm);
+    assert_eq!(ImageFormat::from_path("shot.PBM"), ImageFormat::Pbm);
+    assert_eq!(ImageFormat::from_path("shot.png"), ImageFormat::Png);
+    assert_eq!(ImageFormat::from_path("shot"), ImageFormat::Png);
+}
+
+#[test]
+fn test_pbm() {
+    let mut chip = System::default();
+    chip.display.set(0, 0, 1);
+    chip.display.set(63, 31, 2);
+
+    let text = String::from_utf8(screenshot::pbm(&chip.display)).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[..2], ["P1", "64 32"]);
+    assert_eq!(lines.len(), 2 + 32);
+    assert!(lines[2].starts_with("1 0 0"));
+    assert!(lines[33].ends_with("0 0 1"));
+    assert_eq!(lines[2].split(' ').count(), 64);
+}
+
+#[test]
+fn test_png_structure() {
+    let mut chip = System::default();
+    chip.display.set(1, 0, 1);
+
+    let png = screenshot::png(&chip, 2);
+    assert_eq!(png[..8], [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']);
+    //The CRC of an empty IEND is always the same
+    assert_eq!(png[png.len() - 4..], [0xAE, 0x42, 0x60, 0x82]);
+
+    let chunks = chunks(&png);
+    let kinds: Vec<&str> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+    assert_eq!(kinds, ["IHDR", "PLTE", "IDAT", "IEND"]);
+    assert_eq!(chunks[0].1, [0, 0, 0, 128, 0, 0, 0, 64, 8, 3, 0, 0, 0]);
+    //Black is met first, then white
+    assert_eq!(chunks[1].1, [0, 0, 0, 255, 255, 255]);
+
+    let scanlines = unstore(&chunks[2].1);
+    assert_eq!(scanlines.len(), (128 + 1) * 64);
+    //Each row has its filter byte, and the lit pixel is two wide and two high
+    assert_eq!(scanlines[..6], [0, 0, 0, 1, 1, 0]);
+    assert_eq!(scanlines[129..135], [0, 0, 0, 1, 1, 0]);
+    assert_eq!(scanlines[258..264], [0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_png_colours_follow_the_palette() {
+    let mut chip = System::new(Variant::XoChip);
+    chip.display.set(0, 0, 3);
+    chip.display.set(1, 0, 2);
+
+    let png = screenshot::png(&chip, 1);
+    let chunks = chunks(&png);
+    assert_eq!(chunks[1].1, [170, 170, 170, 85, 85, 85, 0, 0, 0]);
+    assert_eq!(unstore(&chunks[2].1)[..4], [0, 0, 1, 2]);
+}
+
+#[test]
+fn test_large_png_splits_blocks() {
+    let chip = System::default();
+
+    //64x32 scaled 8 times is 512x256, well over one stored block
+    let png = screenshot::png(&chip, 8);
+    let chunks = chunks(&png);
+    assert_eq!(unstore(&chunks[2].1).len(), (512 + 1) * 256);
+}
@@ -0,0 +1,73 @@
+use crate::chip8_cpu::System;
+use crate::quirks::Quirks;
+use crate::timing::{Timing, INTERPRETER_CYCLES};
+
+fn system_with(program: &[u8]) -> System {
+    let mut chip = System::default();
+    chip.setup_fontset();
+    chip.load_program_bytes(program);
+    chip
+}
+
+#[test]
+fn test_parse_and_display() {
+    assert_eq!(Timing::parse("vip"), Some(Timing::Vip));
+    assert_eq!(Timing::parse("15"), Some(Timing::Fixed(15)));
+    assert_eq!(Timing::parse("fast"), None);
+    assert_eq!(Timing::Vip.to_string(), "vip");
+    assert_eq!(Timing::Fixed(7).to_string(), "7");
+}
+
+#[test]
+fn test_vip_frame_runs_what_fits() {
+    //ADD V0, 1 then JP 0x200, 50 and 52 cycles, 18 times round fills the frame exactly
+    let mut chip = system_with(&[0x70, 0x01, 0x12, 0x00]);
+
+    Timing::Vip.run_frame(&mut chip).unwrap();
+    assert_eq!(chip.registers[0], 18);
+    assert_eq!(chip.cycles, 0);
+
+    Timing::Vip.run_frame(&mut chip).unwrap();
+    assert_eq!(chip.registers[0], 36);
+}
+
+#[test]
+fn test_skips_cost_more() {
+    //SE V0, 0 is taken, SE V0, 1 is not
+    let mut chip = system_with(&[0x30, 0x00, 0x00, 0x00, 0x30, 0x01]);
+
+    chip.step().unwrap();
+    let taken = chip.cycles;
+    chip.cycles = 0;
+    chip.step().unwrap();
+    assert_eq!(taken, chip.cycles + 4);
+}
+
+#[test]
+fn test_display_wait_ends_the_frame() {
+    //Draw a row of the 0 digit, then count in V1
+    let program = [0xD0, 0x01, 0x71, 0x01, 0x12, 0x02];
+
+    let mut chip = system_with(&program);
+    Timing::Vip.run_frame(&mut chip).unwrap();
+    assert_eq!(chip.display.get(0, 0), 1);
+    assert_eq!(chip.registers[1], 0);
+    //The draw itself is paid for in the next frame
+    assert_eq!(chip.cycles, 40 + 26 + 18);
+    assert!(chip.cycles < INTERPRETER_CYCLES);
+
+    let mut chip = system_with(&program);
+    chip.quirks = Quirks { display_wait: false, ..Quirks::default() };
+    Timing::Vip.run_frame(&mut chip).unwrap();
+    assert_eq!(chip.display.get(0, 0), 1);
+    assert!(chip.registers[1] > 0);
+}
+
+#[test]
+fn test_fixed_timing_ignores_cycles() {
+    let mut chip = system_with(&[0xD0, 0x01, 0x71, 0x01, 0x12, 0x02]);
+
+    Timing::Fixed(5).run_frame(&mut chip).unwrap();
+    assert_eq!(chip.registers[1], 2);
+    assert_eq!(chip.cycles, 0);
+}
@@ -0,0 +1,162 @@
+use crate::chip8_cpu::System;
+use crate::trace::{TraceFormat, Tracer};
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(String::from).collect()
+    }
+}
+
+fn system_with(program: &[u8]) -> System {
+    let mut chip = System::default();
+    chip.load_program_bytes(program);
+    chip
+}
+
+#[test]
+fn test_trace_line_format() {
+    let buffer = SharedBuffer::default();
+    let mut chip = system_with(&[0x6A, 0x02, 0xA2, 0xEA]);
+    chip.tracer = Some(Tracer::new(Box::new(buffer.clone())));
+
+    chip.run_op_at(0x200).unwrap();
+    chip.run_op_at(0x202).unwrap();
+
+    let lines = buffer.lines();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], "PC=0200 OP=6A02 V0=00 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 \
+                          V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=00 I=0000 SP=00 ; LD VA, 0x02");
+    assert!(lines[1].starts_with("PC=0202 OP=A2EA V0=00"));
+    assert!(lines[1].contains(" VA=02 "));
+    assert!(lines[1].ends_with("I=0000 SP=00 ; LD I, 0x2EA"));
+}
+
+#[test]
+fn test_trace_filters() {
+    let buffer = SharedBuffer::default();
+    let mut chip = system_with(&[0x60, 0x01, 0x61, 0x02, 0xA3, 0x00, 0x62, 0x03]);
+    chip.tracer = Some(Tracer::new(Box::new(buffer.clone()))
+        .with_address_range(0x202, 0x206)
+        .with_opcode_classes(&[0x6]));
+
+    for address in [0x200, 0x202, 0x204, 0x206].iter() {
+        chip.run_op_at(*address).unwrap();
+    }
+
+    let lines = buffer.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("PC=0202"));
+    assert!(lines[1].starts_with("PC=0206"));
+}
+
+#[test]
+fn test_ring_buffer_only_written_on_dump() {
+    let buffer = SharedBuffer::default();
+    let mut chip = system_with(&[0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x63, 0x04]);
+    chip.tracer = Some(Tracer::new(Box::new(buffer.clone())).with_ring_buffer(2));
+
+    for address in [0x200, 0x202, 0x204, 0x206].iter() {
+        chip.run_op_at(*address).unwrap();
+    }
+    assert!(buffer.lines().is_empty());
+
+    chip.tracer.as_mut().unwrap().dump().unwrap();
+
+    let lines = buffer.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("PC=0204"));
+    assert!(lines[1].starts_with("PC=0206"));
+}
+
+#[test]
+fn test_ring_buffer_dumped_on_panic() {
+    let buffer = SharedBuffer::default();
+    let tracer_buffer = buffer.clone();
+
+    let result = panic::catch_unwind(AssertUnwindSafe(move || {
+        let mut chip = system_with(&[0x60, 0x01, 0x61, 0x02]);
+        chip.tracer = Some(Tracer::new(Box::new(tracer_buffer)).with_ring_buffer(8));
+
+        chip.run_op_at(0x200).unwrap();
+        chip.run_op_at(0x202).unwrap();
+        panic!("emulation failed");
+    }));
+
+    assert!(result.is_err());
+    assert_eq!(buffer.lines().len(), 2);
+}
+
+#[test]
+fn test_template_format() {
+    let buffer = SharedBuffer::default();
+    let mut chip = system_with(&[0x6A, 0xBC]);
+    chip.memory[0x300] = 0x5A;
+    chip.tracer = Some(Tracer::new(Box::new(buffer.clone()))
+        .with_format(TraceFormat::named("{pc}: {OP} [{M:300}] {{{ASM}}}").unwrap()));
+
+    chip.run_op_at(0x200).unwrap();
+
+    assert_eq!(buffer.lines(), vec![String::from("0200: 6ABC [5A] {LD VA, 0xBC}")]);
+}
+
+#[test]
+fn test_template_widths() {
+    let mut chip = system_with(&[0x6A, 0x02]);
+    chip.index_register = 0x2E;
+    chip.memory[0x300] = 0x5;
+
+    let format = TraceFormat::named("{pc:3}{op} i={i:0} m={M:300:1} {V3:4}").unwrap();
+    let line = format.render(&chip, 0x200, 0x6A, 0x02);
+    assert_eq!(line, "2006a02 i=2e m=5 0000");
+
+    let fields = format.parse_line(&line).unwrap();
+    assert_eq!(fields[0], (String::from("PC"), String::from("200")));
+    assert_eq!(fields[1], (String::from("OP"), String::from("6A02")));
+    assert_eq!(fields[2], (String::from("I"), String::from("2E")));
+    assert_eq!(fields[3], (String::from("M:0300"), String::from("5")));
+}
+
+#[test]
+fn test_template_errors() {
+    assert!(TraceFormat::template("{PC} {XY}").is_err());
+    assert!(TraceFormat::template("{PC").is_err());
+    assert!(TraceFormat::template("{PC:x}").is_err());
+    assert!(TraceFormat::template("{ASM:4}").is_err());
+}
+
+#[test]
+fn test_parse_line_round_trips() {
+    let mut chip = system_with(&[0x6A, 0x02]);
+    chip.registers[0x3] = 0x7F;
+    chip.index_register = 0x2EA;
+
+    for name in ["native", "compact", "{PC}{OP}{V3}|{I}"].iter() {
+        let format = TraceFormat::named(name).unwrap();
+        let line = format.render(&chip, 0x200, 0x6A, 0x02);
+        let fields = format.parse_line(&line).unwrap();
+
+        assert!(fields.contains(&(String::from("PC"), String::from("200"))), "{}", line);
+        assert!(fields.contains(&(String::from("OP"), String::from("6A02"))), "{}", line);
+        assert!(fields.contains(&(String::from("V3"), String::from("7F"))), "{}", line);
+        assert!(fields.contains(&(String::from("I"), String::from("2EA"))), "{}", line);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::trace::TraceFormat;
+use crate::tracediff::diff_traces;
+
+const LEFT: &str = "\
+PC=0200 OP=6003 V0=00 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=00 I=0000 SP=00 ; LD V0, 0x03
+PC=0202 OP=8006 V0=03 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=00 I=0000 SP=00 ; SHR V0, V0
+PC=0204 OP=A300 V0=01 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=01 I=0000 SP=00 ; LD I, 0x300
+";
+
+#[test]
+fn test_identical_traces() {
+    let native = TraceFormat::native();
+
+    assert_eq!(diff_traces(LEFT, &native, LEFT, &native), Ok(None));
+}
+
+#[test]
+fn test_reports_first_divergent_register() {
+    let native = TraceFormat::native();
+    let right = LEFT.replace("VF=01", "VF=00");
+
+    let divergence = diff_traces(LEFT, &native, &right, &native).unwrap().unwrap();
+
+    assert_eq!(divergence.line, 3);
+    assert!(divergence.previous.unwrap().contains("SHR V0, V0"));
+    assert_eq!(divergence.differences.len(), 1);
+    assert_eq!(divergence.differences[0].field, "VF");
+    assert_eq!(divergence.differences[0].left, Some(String::from("1")));
+    assert_eq!(divergence.differences[0].right, Some(String::from("0")));
+}
+
+#[test]
+fn test_compares_across_formats() {
+    let native = TraceFormat::native();
+    let other = TraceFormat::template("{pc} {op} v0:{v0} vf:{vf}").unwrap();
+    let right = "0200 6003 v0:00 vf:00\n0202 8006 v0:03 vf:00\n0204 a300 v0:02 vf:01\n";
+
+    let divergence = diff_traces(LEFT, &native, right, &other).unwrap().unwrap();
+
+    assert_eq!(divergence.line, 3);
+    assert_eq!(divergence.differences[0].field, "V0");
+}
+
+#[test]
+fn test_shorter_trace() {
+    let native = TraceFormat::native();
+    let right: String = LEFT.lines().take(2).map(|line| format!("{}\n", line)).collect();
+
+    let divergence = diff_traces(LEFT, &native, &right, &native).unwrap().unwrap();
+
+    assert_eq!(divergence.line, 3);
+    assert_eq!(divergence.right, None);
+}
+
+#[test]
+fn test_unparseable_line() {
+    let native = TraceFormat::native();
+
+    assert!(diff_traces(LEFT, &native, "garbage\n", &native).is_err());
+}
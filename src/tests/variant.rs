@@ -0,0 +1,148 @@
+use crate::chip8_cpu::{CpuError, System};
+use crate::font::FontSet;
+use crate::palette::{self, CHIP8X_BACKGROUNDS, CHIP8X_COLOURS};
+use crate::quirks::Quirks;
+use crate::variant::Variant;
+
+const VARIANTS: [Variant; 6] = [Variant::Chip8, Variant::HiresChip8, Variant::Chip8X,
+                                Variant::Chip48, Variant::SuperChip, Variant::XoChip];
+
+#[test]
+fn test_names_round_trip() {
+    for &variant in VARIANTS.iter() {
+        assert_eq!(Variant::from_name(variant.name()), Some(variant));
+        assert_eq!(Variant::from_id(variant.to_id()), Some(variant));
+    }
+    assert_eq!(Variant::from_name("nonsense"), None);
+}
+
+#[test]
+fn test_hires_start_and_clear() {
+    let mut chip = System::new(Variant::HiresChip8);
+    chip.load_program_bytes(&[0x12, 0x60]);
+    //V0 = 60, V1 = 62, draw the 0 digit near the bottom, clear
+    chip.memory[0x2C0..0x2C8].copy_from_slice(&[0x60, 0x3C, 0x61, 0x3E, 0xD0, 0x11, 0x02, 0x30]);
+    chip.setup_fontset();
+
+    assert_eq!((chip.screen_width(), chip.screen_height()), (64, 64));
+    chip.step().unwrap();
+    assert_eq!(chip.program_counter, 0x2C0);
+    chip.run_frame(3).unwrap();
+    assert_eq!(chip.display.get(60, 62), 1);
+    chip.step().unwrap();
+    assert_eq!(chip.display.get(60, 62), 0);
+}
+
+#[test]
+fn test_chip8x_loads_at_0x300() {
+    let mut chip = System::new(Variant::Chip8X);
+    chip.load_program_bytes(&[0x60, 0x01]);
+
+    assert_eq!(chip.program_counter, 0x300);
+    assert_eq!(chip.memory[0x300], 0x60);
+    chip.step().unwrap();
+    assert_eq!(chip.registers[0], 1);
+}
+
+#[test]
+fn test_chip8x_nibble_add_and_ports() {
+    //V0 = 0x35, V1 = 0x46, 5011, output V0, input into V2
+    let mut chip = System::new(Variant::Chip8X);
+    chip.load_program_bytes(&[0x60, 0x35, 0x61, 0x46, 0x50, 0x11, 0xF0, 0xF8, 0xF2, 0xFB]);
+    chip.port_input = 0x99;
+
+    chip.run_frame(5).unwrap();
+    assert_eq!(chip.registers[0], 0x73);
+    assert_eq!(chip.port_output, 0x73);
+    assert_eq!(chip.registers[2], 0x99);
+}
+
+#[test]
+fn test_chip8x_colours() {
+    //V0 = 0x11 (zones 1-2 across), V1 = 0x00 (zone 0 down), V2 = 4, B012, then 02A0
+    let mut chip = System::new(Variant::Chip8X);
+    chip.load_program_bytes(&[0x60, 0x11, 0x61, 0x00, 0x62, 0x04, 0xB0, 0x20, 0x02, 0xA0]);
+    chip.display.set(8, 3, 1);
+    chip.display.set(20, 3, 1);
+
+    chip.run_frame(4).unwrap();
+    assert_eq!(chip.program_counter, 0x308);
+    assert_eq!(chip.colour_zones[1][0], 4);
+    assert_eq!(chip.colour_zones[2][3], 4);
+    assert_eq!(chip.colour_zones[2][4], 1);
+    assert_eq!(palette::pixel_colour(&chip, 8, 3), CHIP8X_COLOURS[4]);
+    assert_eq!(palette::pixel_colour(&chip, 0, 0), CHIP8X_BACKGROUNDS[0]);
+
+    chip.step().unwrap();
+    assert_eq!(chip.background, 1);
+    assert_eq!(palette::pixel_colour(&chip, 0, 0), CHIP8X_BACKGROUNDS[1]);
+}
+
+#[test]
+fn test_variant_sets_up_the_system() {
+    let chip = System::new(Variant::XoChip);
+    assert_eq!(chip.memory.len(), 0x10000);
+    assert_eq!(chip.quirks, Quirks::xo_chip());
+
+    let chip = System::new(Variant::Chip48);
+    assert_eq!(chip.memory.len(), 0x1000);
+    assert_eq!(chip.quirks, Quirks::super_chip());
+
+    //Only SUPER-CHIP and XO-CHIP have the big font
+    let mut chip = System::new(Variant::Chip8);
+    chip.setup_fontset();
+    assert!(chip.memory[0x50..0x60].iter().all(|&byte| byte == 0));
+    let mut chip = System::new(Variant::SuperChip);
+    chip.setup_fontset();
+    assert_eq!(chip.memory[0x50..0x60], FontSet::SuperChip.font().big.unwrap()[..0x10]);
+}
+
+#[test]
+fn test_unknown_opcodes_are_errors() {
+    //5XY1 is CHIP-8X only
+    let mut chip = System::new(Variant::Chip8);
+    chip.load_program_bytes(&[0x50, 0x11]);
+
+    let error = chip.step().unwrap_err();
+    assert_eq!(error, CpuError::UnknownOpcode { address: 0x200, opcode: 0x5011, variant: Variant::Chip8 });
+    assert_eq!(chip.program_counter, 0x200);
+
+    //00FF is SUPER-CHIP only, to CHIP-8 it is a call to machine code
+    let mut chip = System::new(Variant::Chip8);
+    chip.load_program_bytes(&[0x00, 0xFF]);
+    assert_eq!(chip.step(), Err(CpuError::MachineCode { address: 0x200, routine: 0x0FF }));
+    assert!(!chip.hires);
+
+    let mut chip = System::new(Variant::SuperChip);
+    chip.load_program_bytes(&[0x00, 0xFF, 0x80, 0x18]);
+    chip.step().unwrap();
+    assert!(chip.hires);
+    assert!(matches!(chip.step(), Err(CpuError::UnknownOpcode { opcode: 0x8018, .. })));
+}
+
+#[test]
+fn test_instruction_sets() {
+    let chip8 = Variant::Chip8.instruction_set();
+    let super_chip = Variant::SuperChip.instruction_set();
+    let xo_chip = Variant::XoChip.instruction_set();
+
+    //Each extension runs everything the variant it extends does
+    for opcode in 0..=0xFFFF {
+        if chip8.decode(opcode).is_some() {
+            assert!(super_chip.decode(opcode).is_some(), "{:04X}", opcode);
+        }
+        if super_chip.decode(opcode).is_some() {
+            assert!(xo_chip.decode(opcode).is_some(), "{:04X}", opcode);
+        }
+    }
+
+    assert_eq!(chip8.decode(0xD120).unwrap().form, "DXYN");
+    assert_eq!(super_chip.decode(0xD120).unwrap().form, "DXY0");
+    assert_eq!(xo_chip.decode(0xF201).unwrap().form, "FN01");
+    assert!(chip8.decode(0xF201).is_none());
+
+    //CHIP-8X replaces the BNNN jump with colour zones
+    let chip8x = Variant::Chip8X.instruction_set();
+    assert!(chip8.decode(0xB123).unwrap().jumps);
+    assert!(!chip8x.decode(0xB123).unwrap().jumps);
+}
@@ -0,0 +1,193 @@
+use crate::chip8_cpu::System;
+use crate::video::{lzw_encode, Recorder, VideoFormat};
+
+//A GIF frame as (left, top, width, height, delay, compressed data)
+type GifFrame = (u16, u16, u16, u16, u16, Vec<u8>);
+
+fn word(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+//Walks the blocks after the header and colour table, checking they end with the trailer
+fn gif_frames(gif: &[u8]) -> Vec<GifFrame> {
+    let mut frames = Vec::new();
+    let mut position = 13 + 16 * 3;
+    let mut delay = 0;
+
+    loop {
+        match gif[position] {
+            0x21 => {
+                if gif[position + 1] == 0xF9 {
+                    delay = word(gif, position + 4);
+                }
+                position += 2;
+                while gif[position] != 0 {
+                    position += gif[position] as usize + 1;
+                }
+                position += 1;
+            },
+            0x2C => {
+                let descriptor = (word(gif, position + 1), word(gif, position + 3), word(gif, position + 5), word(gif, position + 7));
+                position += 11;
+                let mut data = Vec::new();
+                while gif[position] != 0 {
+                    let length = gif[position] as usize;
+                    data.extend_from_slice(&gif[position + 1..position + 1 + length]);
+                    position += length + 1;
+                }
+                position += 1;
+                frames.push((descriptor.0, descriptor.1, descriptor.2, descriptor.3, delay, data));
+            },
+            0x3B => {
+                assert_eq!(position, gif.len() - 1);
+                return frames;
+            },
+            block => { panic!("unexpected block {:02X} at {}", block, position); }
+        }
+    }
+}
+
+//A plain GIF LZW decoder, to check the encoder against
+fn lzw_decode(data: &[u8], minimum_bits: u8) -> Vec<u8> {
+    let clear = 1usize << minimum_bits;
+    let end = clear + 1;
+    let reset = || -> Vec<Vec<u8>> { (0..clear + 2).map(|code| vec![code as u8]).collect() };
+    let mut table = reset();
+    let mut width = minimum_bits + 1;
+    let mut output = Vec::new();
+    let mut previous: Option<usize> = None;
+    let (mut buffer, mut bits, mut position) = (0u32, 0u8, 0usize);
+
+    loop {
+        while bits < width {
+            buffer |= (data[position] as u32) << bits;
+            position += 1;
+            bits += 8;
+        }
+        let code = (buffer & ((1 << width) - 1)) as usize;
+        buffer >>= width;
+        bits -= width;
+
+        if code == clear {
+            table = reset();
+            width = minimum_bits + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            return output;
+        }
+
+        let entry = match previous {
+            None => table[code].clone(),
+            Some(previous) => {
+                let entry = if code < table.len() {
+                    table[code].clone()
+                } else {
+                    let mut entry = table[previous].clone();
+                    entry.push(table[previous][0]);
+                    entry
+                };
+                if table.len() < 4096 {
+                    let mut added = table[previous].clone();
+                    added.push(entry[0]);
+                    table.push(added);
+                }
+                entry
+            }
+        };
+        if table.len() == 1 << width && width < 12 {
+            width += 1;
+        }
+        output.extend_from_slice(&entry);
+        previous = Some(code);
+    }
+}
+
+#[test]
+fn test_format_from_path() {
+    assert_eq!(VideoFormat::from_path("run.y4m"), VideoFormat::Y4m);
+    assert_eq!(VideoFormat::from_path("run.gif"), VideoFormat::Gif);
+    assert_eq!(VideoFormat::from_path("run"), VideoFormat::Gif);
+}
+
+#[test]
+fn test_lzw_round_trip() {
+    let patterns: Vec<Vec<u8>> = vec![
+        vec![],
+        vec![3],
+        vec![0; 10000],
+        (0..20000).map(|index| (index % 7) as u8).collect(),
+        //Enough variety to fill the code table and clear it
+        (0..50000u32).map(|index| (index.wrapping_mul(2654435761) >> 28) as u8).collect(),
+    ];
+
+    for pattern in patterns.iter() {
+        assert_eq!(&lzw_decode(&lzw_encode(pattern, 4), 4), pattern);
+    }
+}
+
+#[test]
+fn test_gif_stores_only_changes() {
+    let mut chip = System::default();
+    let mut recorder = Recorder::new(Vec::new(), VideoFormat::Gif, &chip, 2).unwrap();
+
+    recorder.frame(&chip).unwrap();
+    recorder.frame(&chip).unwrap();
+    chip.display.set(10, 5, 1);
+    recorder.frame(&chip).unwrap();
+    let gif = recorder.finish().unwrap();
+
+    assert_eq!(gif[..6], *b"GIF89a");
+    assert_eq!((word(&gif, 6), word(&gif, 8)), (128, 64));
+
+    let frames = gif_frames(&gif);
+    assert_eq!(frames.len(), 2);
+    //The first frame is whole and lasts two frames, 1/30 of a second
+    let (left, top, width, height, delay, _) = frames[0];
+    assert_eq!((left, top, width, height, delay), (0, 0, 128, 64, 3));
+    assert_eq!(lzw_decode(&frames[0].5, 4), vec![0; 128 * 64]);
+    //The second is just the pixel that lit, scaled up
+    let (left, top, width, height, _, _) = frames[1];
+    assert_eq!((left, top, width, height), (20, 10, 2, 2));
+    assert_eq!(lzw_decode(&frames[1].5, 4), vec![1; 4]);
+}
+
+#[test]
+fn test_y4m_frames() {
+    let mut chip = System::default();
+    let mut recorder = Recorder::new(Vec::new(), VideoFormat::Y4m, &chip, 1).unwrap();
+
+    chip.display.set(1, 0, 1);
+    recorder.frame(&chip).unwrap();
+    recorder.frame(&chip).unwrap();
+    assert_eq!(recorder.frames(), 2);
+    let video = recorder.finish().unwrap();
+
+    let header = b"YUV4MPEG2 W64 H32 F60:1 Ip A1:1 C444\n";
+    assert_eq!(video[..header.len()], header[..]);
+    let frame_size = 6 + 64 * 32 * 3;
+    assert_eq!(video.len(), header.len() + 2 * frame_size);
+
+    let frame = &video[header.len()..header.len() + frame_size];
+    assert_eq!(frame[..6], *b"FRAME\n");
+    //Black then white in the Y plane, no colour in the others
+    assert_eq!(frame[6..8], [16, 235]);
+    assert_eq!(frame[6 + 64 * 32], 128);
+    assert_eq!(frame[6 + 2 * 64 * 32 + 1], 128);
+}
+
+#[test]
+fn test_resolution_change_is_stretched() {
+    let mut chip = System::default();
+    let mut recorder = Recorder::new(Vec::new(), VideoFormat::Y4m, &chip, 1).unwrap();
+
+    chip.display.resize(128, 64);
+    chip.display.set(126, 62, 1);
+    recorder.frame(&chip).unwrap();
+    let video = recorder.finish().unwrap();
+
+    //Still 64x32, the bottom right pixel is sampled from every other one of the bigger screen
+    assert_eq!(video.len(), 37 + 6 + 64 * 32 * 3);
+    assert_eq!(video[37 + 6 + 64 * 32 - 1], 235);
+}
@@ -0,0 +1,120 @@
+use crate::cdp1802::Board;
+use crate::vip::{Vip, VipError, ROM_SIZE};
+
+//A stand-in monitor: sets up the stack and the display page at 0x0E00, turns the display on and
+//loops, with the usual 1861 interrupt routine showing each 8 byte row on four scan lines
+fn display_rom() -> Vec<u8> {
+    let mut rom = vec![0; ROM_SIZE];
+    let start = [
+        0xF8, 0x80, 0xB1, 0xF8, 0x42, 0xA1, //R1 = 0x8042, the interrupt routine
+        0xF8, 0x0F, 0xB2, 0xF8, 0xFF, 0xA2, //R2 = 0x0FFF, the stack
+        0xF8, 0x0E, 0xBB,                   //RB.1 = 0x0E, the display page
+        0xF8, 0x80, 0xB3, 0xF8, 0x18, 0xA3, //R3 = 0x8018
+        0xD3,                               //SEP 3, leaving the ROM at 0000 behind
+        0x00, 0x00,
+        0xE2, 0x69,                         //SEX 2, INP 1 turns the display on
+        0x30, 0x1A,                         //BR to itself
+    ];
+    let interrupt = [
+        0x72, 0x70,                         //LDXA, RET: the exit, leaving R1 at the entry
+        0x22, 0x78, 0x22, 0x52,             //Save T and D
+        0xC4, 0xC4, 0xC4,
+        0x9B, 0xB0, 0xF8, 0x00, 0xA0,       //R0 = the display page
+        0x80, 0xE2, 0xE2, 0x20, 0xA0, 0xE2, 0x20, 0xA0, 0xE2, 0x20, 0xA0, //Repeat each row
+        0x3C, 0x4E,                         //BN1 until the display ends
+        0x30, 0x40,
+    ];
+    rom[..start.len()].copy_from_slice(&start);
+    rom[0x40..0x40 + interrupt.len()].copy_from_slice(&interrupt);
+    rom
+}
+
+#[test]
+fn test_images_must_be_the_right_size() {
+    assert!(matches!(Vip::new(&[0; 100]), Err(VipError::WrongSize { expected: 512, found: 100, .. })));
+    assert!(matches!(Vip::with_chip8(&[0; 512], &[0; 511], &[]), Err(VipError::WrongSize { found: 511, .. })));
+    assert_eq!(Vip::with_chip8(&[0; 512], &[0; 512], &[0; 4000]).unwrap_err(), VipError::ProgramTooBig(4000));
+
+    let vip = Vip::with_chip8(&[0; 512], &[0x11; 512], &[0x22, 0x33]).unwrap();
+    assert_eq!(vip.hardware.ram[0x1FF], 0x11);
+    assert_eq!(vip.hardware.ram[0x200..0x203], [0x22, 0x33, 0x00]);
+}
+
+#[test]
+fn test_rom_answers_at_zero_after_reset() {
+    let mut rom = vec![0; ROM_SIZE];
+    rom[0] = 0xAA;
+    let mut vip = Vip::with_chip8(&rom, &[0x55; 512], &[]).unwrap();
+
+    assert_eq!(vip.hardware.read(0x0000), 0xAA);
+    assert_eq!(vip.hardware.read(0x8000), 0xAA);
+    assert_eq!(vip.hardware.read(0x0000), 0x55);
+
+    vip.reset();
+    assert_eq!(vip.hardware.read(0x0000), 0xAA);
+}
+
+#[test]
+fn test_display_dma() {
+    let mut vip = Vip::new(&display_rom()).unwrap();
+    for row in 0..32 {
+        vip.hardware.ram[0x0E00 + row * 8] = row as u8;
+    }
+    vip.hardware.ram[0x0E00 + 5 * 8 + 7] = 0x01;
+
+    vip.run_frame();
+    assert!(vip.hardware.display_on);
+    vip.run_frame();
+    for line in 0..128 {
+        assert_eq!(vip.scanline(line)[0], (line / 4) as u8, "scan line {}", line);
+    }
+    assert!(vip.pixel(63, 5));
+    assert!(vip.pixel(7, 1));
+    assert!(!vip.pixel(6, 1));
+    assert_eq!(vip.screen_to_text().lines().nth(1).unwrap(), format!("{}#{}", ".".repeat(7), ".".repeat(56)));
+
+    //The routine leaves the main loop as it found it
+    vip.run_frame();
+    assert_eq!(vip.cpu.p, 3);
+    assert_eq!(vip.cpu.registers[2], 0x0FFF);
+    assert!(vip.cpu.interrupts_enabled);
+}
+
+#[test]
+fn test_display_off_is_dark() {
+    let mut rom = display_rom();
+    //OUT 1 instead of INP 1
+    rom[0x19] = 0x61;
+    let mut vip = Vip::new(&rom).unwrap();
+    vip.hardware.ram[0x0E00] = 0xFF;
+
+    vip.run_frame();
+    vip.run_frame();
+    assert!(!vip.hardware.display_on);
+    assert_eq!(*vip.scanline(0), [0; 8]);
+    //No interrupt was taken
+    assert_eq!(vip.cpu.registers[1], 0x8042);
+}
+
+#[test]
+fn test_keypad_latch() {
+    let mut vip = Vip::new(&[0; ROM_SIZE]).unwrap();
+    vip.set_keypad_bits(1 << 0xB);
+
+    vip.hardware.output(2, 0x0B);
+    assert!(vip.hardware.flag(3));
+    vip.hardware.output(2, 0x0A);
+    assert!(!vip.hardware.flag(3));
+}
+
+#[test]
+fn test_q_is_the_tone() {
+    //SEQ then BR to itself
+    let mut rom = vec![0; ROM_SIZE];
+    rom[..3].copy_from_slice(&[0x7B, 0x30, 0x01]);
+    let mut vip = Vip::new(&rom).unwrap();
+
+    assert!(!vip.tone());
+    vip.run_frame();
+    assert!(vip.tone());
+}
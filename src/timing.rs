@@ -0,0 +1,129 @@
+use std::fmt;
+
+use crate::chip8_cpu::{CpuError, System};
+
+/// Machine cycles of the VIP's 1802 in one 60Hz frame, 8 clocks each at 1.76MHz.
+pub const CYCLES_PER_FRAME: u32 = 3668;
+/// What the display interrupt and the CDP1861 DMA take out of every frame.
+pub const DISPLAY_CYCLES: u32 = 1832;
+/// What is left over for the interpreter each frame.
+pub const INTERPRETER_CYCLES: u32 = CYCLES_PER_FRAME - DISPLAY_CYCLES;
+
+//Reading the opcode, moving the program counter on and jumping to the instruction's routine
+const FETCH_CYCLES: u32 = 40;
+//Taking a skip costs a little more than falling through
+pub(crate) const SKIP_CYCLES: u32 = 4;
+//Instructions the VIP interpreter never had are charged as a typical one
+const OTHER_CYCLES: u32 = 40;
+
+/// How much runs each frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// The same number of instructions every frame, whatever they are.
+    Fixed(usize),
+    /// Each instruction costs what it did on a COSMAC VIP, and a frame runs as many as fit in
+    /// the cycles the interpreter had. With the display wait quirk DXYN waits for the display
+    /// interrupt, so nothing else runs in the frame it is reached in.
+    Vip,
+}
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing::Fixed(10)
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Timing::Fixed(instructions) => write!(f, "{}", instructions),
+            Timing::Vip => write!(f, "vip"),
+        }
+    }
+}
+
+impl Timing {
+    /// Reads a timing written by `Display`, an instruction count or `vip`.
+    pub fn parse(text: &str) -> Option<Timing> {
+        match text {
+            "vip" => Some(Timing::Vip),
+            _ => text.parse().ok().map(Timing::Fixed),
+        }
+    }
+
+    /// Whether the frame is over, after `instructions` have run in it.
+    pub fn frame_done(&self, system: &System, instructions: usize) -> bool {
+        //Nothing more runs after the program has exited, so nothing more would fill the frame
+        system.exited || match *self {
+            Timing::Fixed(count) => instructions >= count,
+            Timing::Vip => system.waiting_for_display || system.cycles >= INTERPRETER_CYCLES,
+        }
+    }
+
+    /// Ticks the timers, counts the frame on the display and carries whatever the last
+    /// instruction ran over into the next frame.
+    pub fn end_frame(&self, system: &mut System) {
+        system.cycles = match *self {
+            Timing::Fixed(_) => 0,
+            Timing::Vip => system.cycles.saturating_sub(INTERPRETER_CYCLES),
+        };
+        system.waiting_for_display = false;
+        system.tick_timers();
+        system.display.end_frame();
+    }
+
+    /// Runs a whole frame, stopping at the first instruction that fails.
+    pub fn run_frame(&self, system: &mut System) -> Result<(), CpuError> {
+        let mut instructions = 0;
+        while !self.frame_done(system, instructions) {
+            system.step()?;
+            instructions += 1;
+        }
+        self.end_frame(system);
+
+        Ok(())
+    }
+}
+
+/// The machine cycles the VIP interpreter takes over the instruction of `form`, before it runs.
+/// Taken skips are added when they happen, and waiting for the display interrupt is not
+/// included.
+pub fn instruction_cycles(system: &System, form: &str, first: u8, second: u8) -> u32 {
+    let x = (first & 0x0F) as usize;
+
+    let execute = match form {
+        //The clear routine stores a zero into each of the 256 bytes of display memory, four
+        //two cycle 1802 instructions a byte (LDI, STXD, GLO and BNZ), after 24 cycles setting up
+        "00E0" => 24 + 256 * 4 * 2,
+        "00EE" => 10,
+        "1NNN" => 12,
+        "2NNN" => 26,
+        "3XNN" | "4XNN" => 10,
+        "5XY0" | "9XY0" => 14,
+        "6XNN" => 6,
+        "7XNN" => 10,
+        "8XY0" | "8XY1" | "8XY2" | "8XY3" | "8XY4" | "8XY5" | "8XY6" | "8XY7" | "8XYE" => 44,
+        "ANNN" => 12,
+        "BNNN" => 22,
+        "CXNN" => 36,
+        "DXYN" => {
+            //Every row of the sprite is shifted into place a bit at a time
+            let rows = (second & 0x0F) as u32;
+            let shift = (system.registers[x] % 8) as u32;
+            26 + rows * (18 + shift * 4)
+        },
+        "EX9E" | "EXA1" => 14,
+        "FX07" | "FX0A" | "FX15" | "FX18" => 10,
+        "FX1E" => 18,
+        "FX29" => 20,
+        "FX33" => {
+            //Each digit is found by subtracting until it goes negative
+            let value = system.registers[x] as u32;
+            40 + 8 * (value / 100 + value / 10 % 10 + value % 10)
+        },
+        "FX55" | "FX65" => 14 + 14 * (x as u32 + 1),
+        _ => OTHER_CYCLES,
+    };
+
+    FETCH_CYCLES + execute
+}
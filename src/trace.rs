@@ -0,0 +1,359 @@
+use std::collections::VecDeque;
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::thread;
+
+use crate::chip8_cpu::System;
+use crate::disassembler::disassemble_opcode;
+
+/// Logs executed instructions, one line each. By default lines are in the native format
+///
+/// `PC=0200 OP=6A02 V0=00 V1=00 ... VF=00 I=0000 SP=00 ; LD VA, 0x02`
+///
+/// but any `TraceFormat` can be used to line up with another emulator's log. The state is
+/// captured before the instruction runs. In ring buffer mode nothing is written until `dump` is
+/// called, or the tracer is dropped while panicking, and then only the last lines.
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    format: TraceFormat,
+    address_range: Option<(u16, u16)>,
+    //Indexed by the top nibble of the opcode
+    opcode_classes: Option<[bool; 16]>,
+    ring: Option<(VecDeque<String>, usize)>,
+}
+
+impl Tracer {
+    pub fn new(writer: Box<dyn Write>) -> Tracer {
+        Tracer {
+            writer,
+            format: TraceFormat::native(),
+            address_range: None,
+            opcode_classes: None,
+            ring: None,
+        }
+    }
+
+    pub fn to_file<P: AsRef<Path>>(path: P) -> io::Result<Tracer> {
+        let file = File::create(path)?;
+        Ok(Tracer::new(Box::new(BufWriter::new(file))))
+    }
+
+    pub fn with_format(mut self, format: TraceFormat) -> Tracer {
+        self.format = format;
+        self
+    }
+
+    /// Only trace instructions whose address is within `start..=end`.
+    pub fn with_address_range(mut self, start: u16, end: u16) -> Tracer {
+        self.address_range = Some((start, end));
+        self
+    }
+
+    /// Only trace instructions whose top nibble is one of `classes`, e.g. `0xD` for draws.
+    pub fn with_opcode_classes(mut self, classes: &[u8]) -> Tracer {
+        let mut enabled = [false; 16];
+        for class in classes {
+            enabled[(class & 0x0F) as usize] = true;
+        }
+        self.opcode_classes = Some(enabled);
+        self
+    }
+
+    /// Keep only the last `capacity` lines in memory instead of writing every one out.
+    pub fn with_ring_buffer(mut self, capacity: usize) -> Tracer {
+        self.ring = Some((VecDeque::with_capacity(capacity), capacity));
+        self
+    }
+
+    pub fn trace(&mut self, system: &System, address: u16) {
+        let first = system.memory[address as usize];
+        let second = system.memory[(address as usize + 1) % system.memory.len()];
+
+        if let Some((start, end)) = self.address_range {
+            if address < start || address > end {
+                return;
+            }
+        }
+        if let Some(ref classes) = self.opcode_classes {
+            if !classes[(first >> 4) as usize] {
+                return;
+            }
+        }
+
+        let line = self.format.render(system, address, first, second);
+        match self.ring {
+            Some((ref mut lines, capacity)) => {
+                if capacity == 0 {
+                    return;
+                }
+                if lines.len() == capacity {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            },
+            None => {
+                //A trace is a debugging aid, losing it must not stop the emulator
+                let _ = writeln!(self.writer, "{}", line);
+            }
+        }
+    }
+
+    /// Writes out what the ring buffer holds, oldest first.
+    pub fn dump(&mut self) -> io::Result<()> {
+        if let Some((ref mut lines, _)) = self.ring {
+            for line in lines.drain(..) {
+                writeln!(self.writer, "{}", line)?;
+            }
+        }
+        self.writer.flush()
+    }
+}
+
+impl Drop for Tracer {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let _ = self.dump();
+        } else {
+            let _ = self.writer.flush();
+        }
+    }
+}
+
+const NATIVE_TEMPLATE: &str = "PC={PC} OP={OP} V0={V0} V1={V1} V2={V2} V3={V3} V4={V4} V5={V5} V6={V6} V7={V7} \
+                               V8={V8} V9={V9} VA={VA} VB={VB} VC={VC} VD={VD} VE={VE} VF={VF} I={I} SP={SP} ; {ASM}";
+//Every register as a bare hex column, with no names or disassembly to strip when comparing
+const COMPACT_TEMPLATE: &str = "{PC} {OP} {V0} {V1} {V2} {V3} {V4} {V5} {V6} {V7} {V8} {V9} {VA} {VB} {VC} {VD} {VE} {VF} {I} {SP}";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    ProgramCounter,
+    Opcode,
+    Register(u8),
+    Index,
+    StackPointer,
+    DelayTimer,
+    SoundTimer,
+    Memory(u16),
+    Disassembly,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        let upper = name.to_ascii_uppercase();
+        let field = match upper.as_str() {
+            "PC" => Field::ProgramCounter,
+            "OP" => Field::Opcode,
+            "I" => Field::Index,
+            "SP" => Field::StackPointer,
+            "DT" => Field::DelayTimer,
+            "ST" => Field::SoundTimer,
+            "ASM" => Field::Disassembly,
+            _ => {
+                if let Some(address) = upper.strip_prefix("M:") {
+                    return u16::from_str_radix(address, 16).ok().map(Field::Memory);
+                }
+                match upper.strip_prefix('V') {
+                    Some(digit) if digit.len() == 1 => return u8::from_str_radix(digit, 16).ok().map(Field::Register),
+                    _ => return None,
+                }
+            }
+        };
+
+        Some(field)
+    }
+
+    fn name(&self) -> String {
+        match *self {
+            Field::ProgramCounter => String::from("PC"),
+            Field::Opcode => String::from("OP"),
+            Field::Register(x) => format!("V{:X}", x),
+            Field::Index => String::from("I"),
+            Field::StackPointer => String::from("SP"),
+            Field::DelayTimer => String::from("DT"),
+            Field::SoundTimer => String::from("ST"),
+            Field::Memory(address) => format!("M:{:04X}", address),
+            Field::Disassembly => String::from("ASM"),
+        }
+    }
+
+    //Number of hex digits written for the field when the template doesn't give one
+    fn width(&self) -> usize {
+        match *self {
+            Field::ProgramCounter | Field::Opcode | Field::Index => 4,
+            Field::Disassembly => 0,
+            _ => 2,
+        }
+    }
+}
+
+//A field name with an optional `:width` after it. `M:0300` has a colon of its own, so the
+//whole name is tried as a field first
+fn parse_field(name: &str) -> Option<(Field, usize)> {
+    if let Some(field) = Field::parse(name) {
+        return Some((field, field.width()));
+    }
+
+    let (name, width) = name.rsplit_once(':')?;
+    let field = Field::parse(name)?;
+    if field == Field::Disassembly || !width.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((field, width.parse().ok()?))
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    //The flag is set when the field was written in lower case, which asks for lower case hex,
+    //and the number is how many digits to pad the value to
+    Field(Field, bool, usize),
+}
+
+/// The layout of a trace line, built from a template where `{PC}`, `{OP}`, `{V0}`..`{VF}`, `{I}`,
+/// `{SP}`, `{DT}`, `{ST}` and `{M:0300}` (the byte at 0x300) become hex values and `{ASM}` the
+/// disassembly. Writing a field in lower case, `{pc}`, gives lower case hex, and `{{` is a brace.
+/// Values are zero padded to 4 digits for `PC`, `OP` and `I` and 2 for the rest, which a width
+/// after a colon overrides: `{PC:3}` pads to 3 digits and `{I:0}` not at all.
+///
+/// The same template is used to read lines back for `tracediff`, so a template that copies
+/// another emulator's log lines lets the two traces be compared.
+#[derive(Debug, Clone)]
+pub struct TraceFormat {
+    segments: Vec<Segment>,
+}
+
+impl TraceFormat {
+    pub fn native() -> TraceFormat {
+        TraceFormat::template(NATIVE_TEMPLATE).unwrap()
+    }
+
+    /// Looks up a preset by name, `native` or `compact`, and otherwise treats `spec` as a template.
+    pub fn named(spec: &str) -> Result<TraceFormat, String> {
+        match spec {
+            "native" => TraceFormat::template(NATIVE_TEMPLATE),
+            "compact" => TraceFormat::template(COMPACT_TEMPLATE),
+            _ => TraceFormat::template(spec),
+        }
+    }
+
+    pub fn template(template: &str) -> Result<TraceFormat, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("'{{{}' is never closed", name)),
+                        }
+                    }
+
+                    let (field, width) = parse_field(&name).ok_or_else(|| format!("unknown trace field '{{{}}}'", name))?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(literal.split_off(0)));
+                    }
+                    let lower = name.chars().any(|c| c.is_ascii_lowercase());
+                    segments.push(Segment::Field(field, lower, width));
+                },
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(TraceFormat { segments })
+    }
+
+    pub fn render(&self, system: &System, address: u16, first: u8, second: u8) -> String {
+        let mut line = String::new();
+
+        for segment in self.segments.iter() {
+            let (field, lower, width) = match *segment {
+                Segment::Literal(ref text) => {
+                    line.push_str(text);
+                    continue;
+                },
+                Segment::Field(field, lower, width) => (field, lower, width),
+            };
+
+            let value = match field {
+                Field::ProgramCounter => address as usize,
+                Field::Opcode => (first as usize) << 8 | second as usize,
+                Field::Register(x) => system.registers[x as usize] as usize,
+                Field::Index => system.index_register as usize,
+                Field::StackPointer => system.stack_pointer as usize,
+                Field::DelayTimer => system.delay_timer as usize,
+                Field::SoundTimer => system.sound_timer as usize,
+                Field::Memory(at) => system.memory.bytes().get(at as usize).cloned().unwrap_or(0) as usize,
+                Field::Disassembly => {
+                    line.push_str(&disassemble_opcode(first, second));
+                    continue;
+                }
+            };
+
+            if lower {
+                write!(line, "{:01$x}", value, width).unwrap();
+            } else {
+                write!(line, "{:01$X}", value, width).unwrap();
+            }
+        }
+
+        line
+    }
+
+    /// Reads a line written in this format back into `(field name, value)` pairs, with hex
+    /// values normalised to upper case without leading zeros. `None` if the line does not match.
+    pub fn parse_line(&self, line: &str) -> Option<Vec<(String, String)>> {
+        let mut fields = Vec::new();
+        let mut rest = line;
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            match *segment {
+                Segment::Literal(ref text) => {
+                    rest = rest.strip_prefix(text.as_str())?;
+                },
+                Segment::Field(Field::Disassembly, ..) => {
+                    //Free text, up to wherever the next literal starts
+                    let end = match self.segments.get(index + 1) {
+                        Some(Segment::Literal(ref text)) => rest.find(text.as_str())?,
+                        _ => rest.len(),
+                    };
+                    fields.push((Field::Disassembly.name(), rest[..end].trim().to_string()));
+                    rest = &rest[end..];
+                },
+                Segment::Field(field, _, width) => {
+                    //Fields written next to each other can only be told apart by their width
+                    let limit = match self.segments.get(index + 1) {
+                        Some(Segment::Field(..)) if width > 0 => width,
+                        _ => usize::MAX,
+                    };
+                    let length = rest.chars().take(limit).take_while(|c| c.is_ascii_hexdigit()).count();
+                    if length == 0 {
+                        return None;
+                    }
+                    let value = u32::from_str_radix(&rest[..length], 16).ok()?;
+                    fields.push((field.name(), format!("{:X}", value)));
+                    rest = &rest[length..];
+                }
+            }
+        }
+
+        if rest.trim().is_empty() { Some(fields) } else { None }
+    }
+}
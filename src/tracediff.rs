@@ -0,0 +1,100 @@
+use std::fmt;
+
+use crate::trace::TraceFormat;
+
+/// A field that has a different value in the two traces, `None` when a trace has ended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDifference {
+    pub field: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+/// The first place two traces disagree.
+///
+/// Lines hold the state before their instruction runs, so a differing register usually points
+/// at the instruction on the line before, which is kept in `previous`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    //1 based
+    pub line: usize,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub previous: Option<String>,
+    pub differences: Vec<FieldDifference>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Traces diverge at line {}", self.line)?;
+        if let Some(ref previous) = self.previous {
+            writeln!(f, "  after:  {}", previous)?;
+        }
+        writeln!(f, "  left:   {}", self.left.as_deref().unwrap_or("<end of trace>"))?;
+        writeln!(f, "  right:  {}", self.right.as_deref().unwrap_or("<end of trace>"))?;
+
+        for difference in self.differences.iter() {
+            writeln!(f, "  {:<7} {} != {}", difference.field,
+                     difference.left.as_deref().unwrap_or("-"),
+                     difference.right.as_deref().unwrap_or("-"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares two traces line by line, each read with its own format, and returns the first
+/// divergence. Only fields both formats have are compared, and the disassembly never is since
+/// emulators spell it differently.
+pub fn diff_traces(left: &str, left_format: &TraceFormat, right: &str, right_format: &TraceFormat)
+                   -> Result<Option<Divergence>, String> {
+    let mut left_lines = left.lines().filter(|line| !line.trim().is_empty());
+    let mut right_lines = right.lines().filter(|line| !line.trim().is_empty());
+    let mut previous: Option<&str> = None;
+    let mut line = 0;
+
+    loop {
+        line += 1;
+        let (left_line, right_line) = match (left_lines.next(), right_lines.next()) {
+            (None, None) => return Ok(None),
+            (Some(left_line), Some(right_line)) => (left_line, right_line),
+            (left_line, right_line) => {
+                return Ok(Some(Divergence {
+                    line,
+                    left: left_line.map(String::from),
+                    right: right_line.map(String::from),
+                    previous: previous.map(String::from),
+                    differences: Vec::new(),
+                }));
+            }
+        };
+
+        let left_fields = left_format.parse_line(left_line)
+            .ok_or_else(|| format!("left trace line {} does not match its format: {}", line, left_line))?;
+        let right_fields = right_format.parse_line(right_line)
+            .ok_or_else(|| format!("right trace line {} does not match its format: {}", line, right_line))?;
+
+        let differences: Vec<FieldDifference> = left_fields.iter()
+            .filter(|(name, _)| name != "ASM")
+            .filter_map(|(name, left_value)| {
+                let right_value = right_fields.iter().find(|(other, _)| other == name).map(|pair| &pair.1)?;
+                if left_value == right_value {
+                    return None;
+                }
+                Some(FieldDifference { field: name.clone(), left: Some(left_value.clone()), right: Some(right_value.clone()) })
+            })
+            .collect();
+
+        if !differences.is_empty() {
+            return Ok(Some(Divergence {
+                line,
+                left: Some(left_line.to_string()),
+                right: Some(right_line.to_string()),
+                previous: previous.map(String::from),
+                differences,
+            }));
+        }
+
+        previous = Some(left_line);
+    }
+}
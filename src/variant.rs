@@ -0,0 +1,129 @@
+use crate::chip8_cpu::{MEMORY_SIZE, XO_CHIP_MEMORY_SIZE};
+use crate::font::FontSet;
+use crate::instruction_set::InstructionSet;
+use crate::quirks::Quirks;
+
+/// CHIP-8 dialects that differ by more than a quirk: they change which opcodes exist, the size
+/// of the screen and memory, the fonts and where programs are loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    /// The original COSMAC VIP interpreter.
+    #[default]
+    Chip8,
+    /// The two page 64x64 interpreter for the COSMAC VIP. Programs start with `1260`, which the
+    /// interpreter turns into a jump to the program proper at 0x2C0, and clear the screen with
+    /// `0230`.
+    HiresChip8,
+    /// CHIP-8X for the VP-590 colour board, with colour zones, a background colour and port I/O.
+    Chip8X,
+    /// CHIP-48 on the HP48, the same instructions as CHIP-8 with different quirks.
+    Chip48,
+    /// SUPER-CHIP 1.1, adding the 128x64 high resolution mode, scrolling, 16x16 sprites, the big
+    /// font and the RPL flags.
+    SuperChip,
+    /// XO-CHIP, SUPER-CHIP with 64 KiB of memory, two bitplanes and audio patterns.
+    XoChip,
+}
+
+impl Variant {
+    /// Looks up a variant by name, `chip8`, `chip48`, `schip`, `xochip`, `hires` or `chip8x`.
+    pub fn from_name(name: &str) -> Option<Variant> {
+        match name {
+            "chip8" => Some(Variant::Chip8),
+            "hires" => Some(Variant::HiresChip8),
+            "chip8x" => Some(Variant::Chip8X),
+            "chip48" => Some(Variant::Chip48),
+            "schip" => Some(Variant::SuperChip),
+            "xochip" => Some(Variant::XoChip),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Variant::Chip8 => "chip8",
+            Variant::HiresChip8 => "hires",
+            Variant::Chip8X => "chip8x",
+            Variant::Chip48 => "chip48",
+            Variant::SuperChip => "schip",
+            Variant::XoChip => "xochip",
+        }
+    }
+
+    /// The opcodes the variant runs, anything else is an error.
+    pub fn instruction_set(&self) -> InstructionSet {
+        InstructionSet::for_variant(*self)
+    }
+
+    /// Where programs are loaded and start running.
+    pub fn program_start(&self) -> u16 {
+        match *self {
+            Variant::Chip8X => 0x300,
+            _ => 0x200,
+        }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        match *self {
+            //F000 NNNN addresses a full 64 KiB
+            Variant::XoChip => XO_CHIP_MEMORY_SIZE,
+            _ => MEMORY_SIZE,
+        }
+    }
+
+    /// The screen size in pixels, `hires` being the SUPER-CHIP high resolution mode.
+    pub fn screen_size(&self, hires: bool) -> (usize, usize) {
+        match *self {
+            Variant::SuperChip | Variant::XoChip if hires => (128, 64),
+            Variant::HiresChip8 => (64, 64),
+            _ => (64, 32),
+        }
+    }
+
+    /// Whether the 8x10 digits FX30 points at are loaded along with the usual font.
+    pub fn has_big_font(&self) -> bool {
+        matches!(*self, Variant::SuperChip | Variant::XoChip)
+    }
+
+    /// The font the variant's interpreter came with, used unless another is asked for.
+    pub fn font_set(&self) -> FontSet {
+        match *self {
+            Variant::Chip8 | Variant::HiresChip8 | Variant::Chip8X | Variant::Chip48 => FontSet::Vip,
+            Variant::SuperChip => FontSet::SuperChip,
+            Variant::XoChip => FontSet::Octo,
+        }
+    }
+
+    /// The quirks programs written for the variant expect.
+    pub fn quirks(&self) -> Quirks {
+        match *self {
+            Variant::Chip8 | Variant::HiresChip8 | Variant::Chip8X => Quirks::default(),
+            //CHIP-48 moves I by one less than the VIP on FX55 and FX65, closer to leaving it be
+            Variant::Chip48 | Variant::SuperChip => Quirks::super_chip(),
+            Variant::XoChip => Quirks::xo_chip(),
+        }
+    }
+
+    pub fn to_id(&self) -> u8 {
+        match *self {
+            Variant::Chip8 => 0,
+            Variant::HiresChip8 => 1,
+            Variant::Chip8X => 2,
+            Variant::Chip48 => 3,
+            Variant::SuperChip => 4,
+            Variant::XoChip => 5,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Variant> {
+        match id {
+            0 => Some(Variant::Chip8),
+            1 => Some(Variant::HiresChip8),
+            2 => Some(Variant::Chip8X),
+            3 => Some(Variant::Chip48),
+            4 => Some(Variant::SuperChip),
+            5 => Some(Variant::XoChip),
+            _ => None,
+        }
+    }
+}
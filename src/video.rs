@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::antiflicker::Colour;
+use crate::chip8_cpu::System;
+use crate::palette::{self, CHIP8X_BACKGROUNDS, CHIP8X_COLOURS, GREYS};
+
+const FRAMES_PER_SECOND: u64 = 60;
+//Every colour the palette can give fits in a 16 colour table, indexed with 4 bit codes
+const GIF_TABLE_BITS: u8 = 4;
+const GIF_TABLE_SIZE: usize = 1 << GIF_TABLE_BITS;
+const LZW_MAX_CODES: u16 = 4096;
+const LZW_MAX_BITS: u8 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// An animated GIF, looping, with only what changed stored for each frame.
+    Gif,
+    /// Uncompressed YUV4MPEG2 at 60 frames a second, for video tools to encode.
+    Y4m,
+}
+
+impl VideoFormat {
+    /// Y4M for paths ending in `.y4m`, GIF for anything else.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> VideoFormat {
+        match path.as_ref().extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("y4m") => VideoFormat::Y4m,
+            _ => VideoFormat::Gif,
+        }
+    }
+}
+
+/// Where the frontends put a recording started on `frame`, next to the program.
+pub fn recording_path(program_path: &str, frame: u64) -> PathBuf {
+    PathBuf::from(format!("{}.{}.gif", program_path, frame))
+}
+
+//A GIF frame held back until the next change shows how long it stays up
+struct PendingFrame {
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+    indices: Vec<u8>,
+    //The frame it first showed on
+    shown_at: u64,
+}
+
+/// Records the screen of a `System` a frame at a time. The size is fixed when recording starts,
+/// frames at another resolution are stretched to fit.
+pub struct Recorder<W: Write> {
+    output: W,
+    format: VideoFormat,
+    width: usize,
+    height: usize,
+    frames: u64,
+    //The GIF palette indices of the last frame, to find what changed
+    previous: Vec<u8>,
+    pending: Option<PendingFrame>,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Starts recording `system` to `path`, in the format its extension asks for, each pixel
+    /// `scale` pixels square.
+    pub fn create<P: AsRef<Path>>(path: P, system: &System, scale: usize) -> io::Result<Recorder<BufWriter<File>>> {
+        let format = VideoFormat::from_path(&path);
+        Recorder::new(BufWriter::new(File::create(path)?), format, system, scale)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(output: W, format: VideoFormat, system: &System, scale: usize) -> io::Result<Recorder<W>> {
+        let scale = scale.max(1);
+        let mut recorder = Recorder {
+            output,
+            format,
+            width: system.screen_width() * scale,
+            height: system.screen_height() * scale,
+            frames: 0,
+            previous: Vec::new(),
+            pending: None,
+        };
+
+        match format {
+            VideoFormat::Gif => recorder.write_gif_header()?,
+            VideoFormat::Y4m => {
+                let header = format!("YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444\n", recorder.width, recorder.height, FRAMES_PER_SECOND);
+                recorder.output.write_all(header.as_bytes())?;
+            }
+        }
+
+        Ok(recorder)
+    }
+
+    /// How many frames have been recorded.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Adds the current screen of `system` as the next frame.
+    pub fn frame(&mut self, system: &System) -> io::Result<()> {
+        let colours = self.sample(system);
+
+        match self.format {
+            VideoFormat::Gif => self.gif_frame(&colours)?,
+            VideoFormat::Y4m => self.y4m_frame(&colours)?,
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Ends the recording, returning the output once everything is written to it.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.format == VideoFormat::Gif {
+            self.flush_pending()?;
+            self.output.write_all(&[0x3B])?;
+        }
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    //The screen at the size of the recording, scaled by picking the nearest pixel
+    fn sample(&self, system: &System) -> Vec<Colour> {
+        let (screen_width, screen_height) = (system.screen_width(), system.screen_height());
+        let mut colours = Vec::with_capacity(self.width * self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                colours.push(palette::pixel_colour(system, x * screen_width / self.width, y * screen_height / self.height));
+            }
+        }
+
+        colours
+    }
+
+    fn y4m_frame(&mut self, colours: &[Colour]) -> io::Result<()> {
+        //Studio range BT.601, as video tools expect unless told otherwise
+        let mut planes: Vec<Vec<u8>> = (0..3).map(|_| Vec::with_capacity(colours.len())).collect();
+        for &(r, g, b) in colours {
+            let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+            planes[0].push((16.0 + 65.481 * r + 128.553 * g + 24.966 * b).round() as u8);
+            planes[1].push((128.0 - 37.797 * r - 74.203 * g + 112.0 * b).round() as u8);
+            planes[2].push((128.0 + 112.0 * r - 93.786 * g - 18.214 * b).round() as u8);
+        }
+
+        self.output.write_all(b"FRAME\n")?;
+        for plane in planes.iter() {
+            self.output.write_all(plane)?;
+        }
+        Ok(())
+    }
+
+    fn write_gif_header(&mut self) -> io::Result<()> {
+        let mut header = b"GIF89a".to_vec();
+        header.extend_from_slice(&(self.width as u16).to_le_bytes());
+        header.extend_from_slice(&(self.height as u16).to_le_bytes());
+        //A global colour table of 8 bit colours, its size in the low bits
+        header.extend_from_slice(&[0xF0 | (GIF_TABLE_BITS - 1), 0, 0]);
+        for index in 0..GIF_TABLE_SIZE {
+            let (r, g, b) = gif_colours().get(index).copied().unwrap_or((0, 0, 0));
+            header.extend_from_slice(&[r, g, b]);
+        }
+        //The Netscape extension, looping forever
+        header.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+        header.extend_from_slice(b"NETSCAPE2.0");
+        header.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+        self.output.write_all(&header)
+    }
+
+    fn gif_frame(&mut self, colours: &[Colour]) -> io::Result<()> {
+        let table = gif_colours();
+        let indices: Vec<u8> = colours.iter().map(|colour| {
+            table.iter().position(|known| known == colour).unwrap_or(0) as u8
+        }).collect();
+
+        //Only the rectangle around what changed is stored, the rest stays from the frame before
+        let changed = if self.previous.is_empty() {
+            Some((0, 0, self.width - 1, self.height - 1))
+        } else {
+            let mut bounds: Option<(usize, usize, usize, usize)> = None;
+            for (index, (&now, &before)) in indices.iter().zip(self.previous.iter()).enumerate() {
+                if now != before {
+                    let (x, y) = (index % self.width, index / self.width);
+                    bounds = Some(match bounds {
+                        Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+                        None => (x, y, x, y),
+                    });
+                }
+            }
+            bounds
+        };
+
+        if let Some((left, top, right, bottom)) = changed {
+            self.flush_pending()?;
+            let (width, height) = (right - left + 1, bottom - top + 1);
+            let mut region = Vec::with_capacity(width * height);
+            for row in top..=bottom {
+                region.extend_from_slice(&indices[row * self.width + left..row * self.width + right + 1]);
+            }
+            self.pending = Some(PendingFrame { left, top, width, height, indices: region, shown_at: self.frames });
+            self.previous = indices;
+        }
+
+        Ok(())
+    }
+
+    //Writes out the frame waiting for the next change, which is now known to have lasted until now
+    fn flush_pending(&mut self) -> io::Result<()> {
+        let frame = match self.pending.take() {
+            Some(frame) => frame,
+            None => { return Ok(()); }
+        };
+
+        //GIF delays are in hundredths of a second, so some frames are held a hundredth longer
+        //than others to keep the whole in time
+        let centiseconds = |frame: u64| (frame * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+        let delay = (centiseconds(self.frames) - centiseconds(frame.shown_at)).min(u16::MAX as u64) as u16;
+
+        let mut data = Vec::new();
+        //The graphic control extension: the delay, and leave the frame in place for the next
+        data.extend_from_slice(&[0x21, 0xF9, 0x04, 0x04]);
+        data.extend_from_slice(&delay.to_le_bytes());
+        data.extend_from_slice(&[0x00, 0x00]);
+        //The image descriptor, with no colour table of its own
+        data.push(0x2C);
+        for value in [frame.left, frame.top, frame.width, frame.height].iter() {
+            data.extend_from_slice(&(*value as u16).to_le_bytes());
+        }
+        data.push(0x00);
+        data.push(GIF_TABLE_BITS);
+        for block in lzw_encode(&frame.indices, GIF_TABLE_BITS).chunks(255) {
+            data.push(block.len() as u8);
+            data.extend_from_slice(block);
+        }
+        data.push(0x00);
+
+        self.output.write_all(&data)
+    }
+}
+
+//Every colour palette::pixel_colour can give, each once
+fn gif_colours() -> Vec<Colour> {
+    let mut colours: Vec<Colour> = Vec::with_capacity(GIF_TABLE_SIZE);
+    for &colour in GREYS.iter().chain(CHIP8X_COLOURS.iter()).chain(CHIP8X_BACKGROUNDS.iter()) {
+        if !colours.contains(&colour) {
+            colours.push(colour);
+        }
+    }
+    colours
+}
+
+//Packs codes of varying widths, lowest bit first as GIF wants
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// GIF's variable width LZW compression of `indices`, codes starting one bit wider than
+/// `minimum_bits`.
+pub fn lzw_encode(indices: &[u8], minimum_bits: u8) -> Vec<u8> {
+    let clear = 1u16 << minimum_bits;
+    let end = clear + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut width = minimum_bits + 1;
+    let mut writer = BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 };
+    let mut prefix: Option<u16> = None;
+
+    writer.write(clear, width);
+    for &index in indices {
+        let current = match prefix {
+            Some(current) => current,
+            None => {
+                prefix = Some(index as u16);
+                continue;
+            }
+        };
+        if let Some(&code) = table.get(&(current, index)) {
+            prefix = Some(code);
+            continue;
+        }
+
+        writer.write(current, width);
+        //The decoder adds its codes a step behind, so the width grows after the code that fills it
+        if next == 1 << width && width < LZW_MAX_BITS {
+            width += 1;
+        }
+        if next < LZW_MAX_CODES {
+            table.insert((current, index), next);
+            next += 1;
+        } else {
+            writer.write(clear, width);
+            table.clear();
+            next = end + 1;
+            width = minimum_bits + 1;
+        }
+        prefix = Some(index as u16);
+    }
+
+    if let Some(current) = prefix {
+        writer.write(current, width);
+        if next == 1 << width && width < LZW_MAX_BITS {
+            width += 1;
+        }
+    }
+    writer.write(end, width);
+    writer.finish()
+}
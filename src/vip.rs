@@ -0,0 +1,260 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::cdp1802::{Board, Cdp1802};
+use crate::timing::CYCLES_PER_FRAME;
+
+/// The VIP's RAM, fully expanded. It repeats through the lower half of the address space.
+pub const RAM_SIZE: usize = 4096;
+/// The monitor ROM at 0x8000, repeating through the upper half of the address space.
+pub const ROM_SIZE: usize = 512;
+/// The CHIP-8 interpreter sits in the first two pages of RAM, with programs after it.
+pub const INTERPRETER_SIZE: usize = 512;
+pub const PROGRAM_START: u16 = 0x200;
+
+/// The scan lines the CDP1861 shows each frame, 64 pixels from 8 bytes of DMA each. CHIP-8 shows
+/// each of its 32 rows on four of them.
+pub const DISPLAY_LINES: usize = 128;
+const LINE_CYCLES: u32 = 14;
+const LINE_BYTES: usize = 8;
+//The 1861 interrupts two lines before the first DMA. Requests are taken at the first instruction
+//boundary after they are made, which is what the interrupt routines are counted against
+const INTERRUPT_CYCLE: u32 = 78 * LINE_CYCLES;
+const DISPLAY_START: u32 = INTERRUPT_CYCLE + 30;
+const DISPLAY_END: u32 = DISPLAY_START + DISPLAY_LINES as u32 * LINE_CYCLES;
+//EF1 is asserted for the four lines before the display and the last four of it
+const EF1_CYCLES: u32 = 4 * LINE_CYCLES;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VipError {
+    WrongSize { image: &'static str, expected: usize, found: usize },
+    ProgramTooBig(usize),
+}
+
+impl fmt::Display for VipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VipError::WrongSize { image, expected, found } => {
+                write!(f, "the {} image should be {} bytes, found {}", image, expected, found)
+            },
+            VipError::ProgramTooBig(size) => {
+                write!(f, "program is {} bytes, only {} fit after the interpreter", size, RAM_SIZE - PROGRAM_START as usize)
+            },
+        }
+    }
+}
+
+impl Error for VipError {}
+
+/// Everything on the VIP board but the CPU: memory, the CDP1861 video chip and the hex keypad.
+#[derive(Debug, Clone)]
+pub struct Hardware {
+    pub ram: Vec<u8>,
+    rom: Vec<u8>,
+    //After a reset the ROM also answers at 0000, until the first address with A15 set
+    rom_at_zero: bool,
+    /// The keys held down, bit N for key N.
+    pub keypad: u16,
+    //The key OUT 2 picked for EF3 to test
+    key_latch: u8,
+    /// Turned on by INP 1 and off by OUT 1.
+    pub display_on: bool,
+    //Machine cycles into the frame
+    cycle: u32,
+    //The next scan line waiting for its DMA
+    next_line: usize,
+    raster: [[u8; LINE_BYTES]; DISPLAY_LINES],
+}
+
+impl Hardware {
+    fn line_start(line: usize) -> u32 {
+        DISPLAY_START + line as u32 * LINE_CYCLES
+    }
+
+    //Lines whose time has passed without DMA, with the display off, stay dark
+    fn skip_missed_lines(&mut self) {
+        while self.next_line < DISPLAY_LINES && self.cycle > Hardware::line_start(self.next_line) + LINE_CYCLES {
+            self.raster[self.next_line] = [0; LINE_BYTES];
+            self.next_line += 1;
+        }
+    }
+
+    fn interrupt_due(&self) -> bool {
+        self.display_on && self.cycle > INTERRUPT_CYCLE && self.cycle <= DISPLAY_START
+    }
+
+    fn dma_due(&self) -> bool {
+        self.display_on && self.next_line < DISPLAY_LINES && self.cycle > Hardware::line_start(self.next_line)
+    }
+}
+
+impl Board for Hardware {
+    fn read(&mut self, address: u16) -> u8 {
+        if address & 0x8000 != 0 {
+            self.rom_at_zero = false;
+            self.rom[address as usize % ROM_SIZE]
+        } else if self.rom_at_zero {
+            self.rom[address as usize % ROM_SIZE]
+        } else {
+            self.ram[address as usize % RAM_SIZE]
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address & 0x8000 != 0 {
+            self.rom_at_zero = false;
+        } else {
+            self.ram[address as usize % RAM_SIZE] = value;
+        }
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => { self.display_on = false; },
+            2 => { self.key_latch = value & 0x0F; },
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.display_on = true;
+        }
+        //Nothing drives the bus
+        0xFF
+    }
+
+    fn flag(&self, number: u8) -> bool {
+        match number {
+            1 => {
+                self.display_on
+                    && ((DISPLAY_START - EF1_CYCLES..DISPLAY_START).contains(&self.cycle)
+                        || (DISPLAY_END - EF1_CYCLES..DISPLAY_END).contains(&self.cycle))
+            },
+            3 => self.keypad & (1 << self.key_latch) != 0,
+            _ => false,
+        }
+    }
+}
+
+/// A COSMAC VIP, running real firmware on an emulated 1802 rather than interpreting CHIP-8 itself
+/// like `System` does. The monitor ROM and the CHIP-8 interpreter are not included, they have to
+/// be dumped from a VIP and loaded with `with_chip8`.
+#[derive(Debug, Clone)]
+pub struct Vip {
+    pub cpu: Cdp1802,
+    pub hardware: Hardware,
+}
+
+impl Vip {
+    /// A VIP with `rom` as its monitor and empty RAM, just after a reset.
+    pub fn new(rom: &[u8]) -> Result<Vip, VipError> {
+        check_size("monitor ROM", rom, ROM_SIZE)?;
+
+        Ok(Vip {
+            cpu: Cdp1802::new(),
+            hardware: Hardware {
+                ram: vec![0; RAM_SIZE],
+                rom: rom.to_vec(),
+                rom_at_zero: true,
+                keypad: 0,
+                key_latch: 0,
+                display_on: false,
+                cycle: 0,
+                next_line: 0,
+                raster: [[0; LINE_BYTES]; DISPLAY_LINES],
+            },
+        })
+    }
+
+    /// A VIP set up to run a CHIP-8 program: the interpreter at 0x000 and the program after it at
+    /// 0x200. With no keys held the monitor starts the interpreter after the reset.
+    pub fn with_chip8(rom: &[u8], interpreter: &[u8], program: &[u8]) -> Result<Vip, VipError> {
+        check_size("CHIP-8 interpreter", interpreter, INTERPRETER_SIZE)?;
+        if PROGRAM_START as usize + program.len() > RAM_SIZE {
+            return Err(VipError::ProgramTooBig(program.len()));
+        }
+
+        let mut vip = Vip::new(rom)?;
+        vip.hardware.ram[..INTERPRETER_SIZE].copy_from_slice(interpreter);
+        vip.hardware.ram[PROGRAM_START as usize..PROGRAM_START as usize + program.len()].copy_from_slice(program);
+        Ok(vip)
+    }
+
+    /// Presses the reset switch: RAM is kept, the CPU starts again at 0000 in the ROM.
+    pub fn reset(&mut self) {
+        self.cpu = Cdp1802::new();
+        self.hardware.rom_at_zero = true;
+        self.hardware.display_on = false;
+    }
+
+    /// Runs a 60Hz frame of 3668 machine cycles, with the 1861's interrupt and DMA.
+    pub fn run_frame(&mut self) {
+        let cpu = &mut self.cpu;
+        let hardware = &mut self.hardware;
+
+        while hardware.cycle < CYCLES_PER_FRAME {
+            hardware.skip_missed_lines();
+
+            if hardware.interrupt_due() && cpu.interrupts_enabled {
+                hardware.cycle += cpu.interrupt();
+            } else if hardware.dma_due() {
+                let line = hardware.next_line;
+                for byte in 0..LINE_BYTES {
+                    hardware.raster[line][byte] = cpu.dma_out(hardware);
+                }
+                hardware.next_line += 1;
+                hardware.cycle += LINE_BYTES as u32;
+            } else {
+                hardware.cycle += cpu.step(hardware);
+            }
+        }
+
+        for line in hardware.next_line..DISPLAY_LINES {
+            hardware.raster[line] = [0; LINE_BYTES];
+        }
+        hardware.next_line = 0;
+        hardware.cycle -= CYCLES_PER_FRAME;
+    }
+
+    pub fn set_keypad_bits(&mut self, bits: u16) {
+        self.hardware.keypad = bits;
+    }
+
+    /// Whether the tone generator is on, which Q switches.
+    pub fn tone(&self) -> bool {
+        self.cpu.q
+    }
+
+    /// The 8 bytes shown on scan line `line` in the last frame.
+    pub fn scanline(&self, line: usize) -> &[u8; LINE_BYTES] {
+        &self.hardware.raster[line]
+    }
+
+    /// A pixel of the CHIP-8 screen, 64x32, taken from the first scan line of each row.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.scanline(y * 4)[x / 8] & (0x80 >> (x % 8)) != 0
+    }
+
+    /// The CHIP-8 screen as text, laid out like `headless::screen_to_text` so that the two
+    /// backends can be compared.
+    pub fn screen_to_text(&self) -> String {
+        let mut text = String::with_capacity(65 * 32);
+
+        for y in 0..32 {
+            for x in 0..64 {
+                text.push(if self.pixel(x, y) { '#' } else { '.' });
+            }
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+fn check_size(image: &'static str, bytes: &[u8], expected: usize) -> Result<(), VipError> {
+    if bytes.len() != expected {
+        return Err(VipError::WrongSize { image, expected, found: bytes.len() });
+    }
+    Ok(())
+}